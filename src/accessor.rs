@@ -16,18 +16,25 @@ use std::fmt::Debug;
 use std::io::Result;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use async_trait::async_trait;
+use futures::AsyncWriteExt;
 
+use crate::error::other;
+use crate::error::ObjectError;
+use crate::ops::OpCopy;
 use crate::ops::OpCreate;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
 use crate::ops::OpRead;
+use crate::ops::OpRename;
 use crate::ops::OpStat;
 use crate::ops::OpWrite;
 use crate::BytesReader;
 use crate::BytesWriter;
 use crate::DirStreamer;
 use crate::ObjectMetadata;
+use crate::ObjectMode;
 use crate::Scheme;
 
 /// Underlying trait of all backends for implementors.
@@ -113,6 +120,58 @@ pub trait Accessor: Send + Sync + Debug {
         let _ = args;
         unimplemented!()
     }
+
+    /// Invoke the `copy` operation from `from` to `to`.
+    ///
+    /// # Behavior
+    ///
+    /// - Input paths MUST be file paths, DON'T NEED to check object mode.
+    /// - `copy` MUST return `NotFound` if `from` doesn't exist, even if `to`
+    ///   is the same path.
+    /// - `copy` SHOULD overwrite `to` if it exists.
+    ///
+    /// The default implementation streams content through `read` and
+    /// `write` of this accessor.
+    async fn copy(&self, args: &OpCopy) -> Result<()> {
+        let meta = self.stat(&OpStat::new(args.from())?).await?;
+        if meta.mode() != ObjectMode::FILE {
+            return Err(other(ObjectError::new(
+                "copy",
+                args.from(),
+                anyhow!("only files can be copied"),
+            )));
+        }
+        if args.from() == args.to() {
+            return Ok(());
+        }
+
+        let r = self.read(&OpRead::new(args.from(), ..)?).await?;
+        let mut w = self
+            .write(&OpWrite::new(args.to(), meta.content_length())?)
+            .await?;
+        futures::io::copy(r, &mut w).await?;
+        w.close().await
+    }
+
+    /// Invoke the `rename` operation from `from` to `to`.
+    ///
+    /// # Behavior
+    ///
+    /// - Input paths MUST be file paths, DON'T NEED to check object mode.
+    /// - `rename` MUST return `NotFound` if `from` doesn't exist, even if
+    ///   `to` is the same path.
+    /// - `rename` SHOULD overwrite `to` if it exists.
+    ///
+    /// The default implementation is not atomic, it will `copy` and then
+    /// `delete` the `from`. Both of them exist if the `delete` fails.
+    async fn rename(&self, args: &OpRename) -> Result<()> {
+        self.copy(&OpCopy::new(args.from(), args.to())?).await?;
+        if args.from() == args.to() {
+            return Ok(());
+        }
+
+        self.delete(&OpDelete::new(args.from())?).await
+    }
 }
 
 /// All functions in `Accessor` only requires `&self`, so it's safe to implement
@@ -140,6 +199,12 @@ impl<T: Accessor> Accessor for Arc<T> {
     async fn list(&self, args: &OpList) -> Result<DirStreamer> {
         self.as_ref().list(args).await
     }
+    async fn copy(&self, args: &OpCopy) -> Result<()> {
+        self.as_ref().copy(args).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<()> {
+        self.as_ref().rename(args).await
+    }
 }
 
 /// Metadata for accessor, users can use this metadata to get information of underlying backend.
@@ -148,6 +213,7 @@ pub struct AccessorMetadata {
     scheme: Scheme,
     root: String,
     name: String,
+    read_only: bool,
 }
 
 impl AccessorMetadata {
@@ -185,4 +251,15 @@ impl AccessorMetadata {
         self.name = name.to_string();
         self
     }
+
+    /// Whether the backend is read-only, all mutating operations on
+    /// read-only backends will fail.
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub(crate) fn set_read_only(&mut self, read_only: bool) -> &mut Self {
+        self.read_only = read_only;
+        self
+    }
 }
@@ -36,6 +36,7 @@
 
 use std::collections::HashMap;
 use std::io;
+use std::time::Duration;
 
 use thiserror::Error;
 
@@ -73,6 +74,7 @@ pub(crate) struct ObjectError {
     op: &'static str,
     path: String,
     source: anyhow::Error,
+    retry_after: Option<Duration>,
 }
 
 impl ObjectError {
@@ -81,8 +83,25 @@ impl ObjectError {
             op,
             path: path.to_string(),
             source: source.into(),
+            retry_after: None,
         }
     }
+
+    /// Set the duration that services asked us to wait before retrying.
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+}
+
+/// Get the duration that services asked us to wait before retrying.
+///
+/// Returns `None` if the error doesn't carry such a hint.
+#[cfg(feature = "retry")]
+pub(crate) fn retry_after(err: &io::Error) -> Option<Duration> {
+    err.get_ref()
+        .and_then(|e| e.downcast_ref::<ObjectError>())
+        .and_then(|e| e.retry_after)
 }
 
 /// Copied for [`io::Error::other`], should be removed after `io_error_other` stable.
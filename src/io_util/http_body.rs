@@ -21,6 +21,7 @@ use std::io::Result;
 use std::pin::Pin;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use bytes::Bytes;
@@ -31,6 +32,7 @@ use futures::AsyncWrite;
 use futures::SinkExt;
 use futures::StreamExt;
 use http::response::Parts;
+use http::HeaderMap;
 use http::Response;
 use http::StatusCode;
 use hyper::body::HttpBody;
@@ -38,6 +40,9 @@ use hyper::client::ResponseFuture;
 use hyper::Body;
 use log::debug;
 use pin_project::pin_project;
+use serde::Deserialize;
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
 
 use crate::error::other;
 use crate::error::ObjectError;
@@ -58,7 +63,7 @@ pub struct HttpBodyWriter {
     tx: Sender<Bytes>,
     state: State,
     accepted_codes: HashSet<http::StatusCode>,
-    error_parser: fn(http::StatusCode) -> ErrorKind,
+    error_parser: ErrorKindParser,
 }
 
 enum State {
@@ -83,7 +88,7 @@ impl HttpBodyWriter {
         tx: Sender<Bytes>,
         fut: ResponseFuture,
         accepted_codes: HashSet<StatusCode>,
-        error_parser: fn(StatusCode) -> ErrorKind,
+        error_parser: ErrorKindParser,
     ) -> HttpBodyWriter {
         HttpBodyWriter {
             op: op.clone(),
@@ -114,8 +119,10 @@ impl HttpBodyWriter {
                     )));
                     self.poll_response(cx)
                 }
-                // TODO: we need to inject an object error here.
-                Poll::Ready(Err(e)) => Poll::Ready(Err(other(e))),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(Error::new(
+                    parse_hyper_error_kind(&e),
+                    ObjectError::new("write", op.path(), e),
+                ))),
                 Poll::Pending => Poll::Pending,
             },
             State::ParseError(resp) => Poll::Ready(Err(ready!(Pin::new(resp).poll(cx)))),
@@ -156,11 +163,42 @@ impl AsyncWrite for HttpBodyWriter {
     }
 }
 
+/// ErrorKindParser will decide the [`ErrorKind`] via the status code and
+/// the error code returned by services.
+pub type ErrorKindParser = fn(StatusCode, Option<&str>) -> ErrorKind;
+
+/// Decide the [`ErrorKind`] of errors returned by hyper.
+///
+/// Errors that happen before the request has been handled, or while the
+/// connection is broken, are retryable and will be `Interrupted`.
+pub fn parse_hyper_error_kind(err: &hyper::Error) -> ErrorKind {
+    if err.is_connect() || err.is_incomplete_message() || err.is_closed() || err.is_timeout() {
+        return ErrorKind::Interrupted;
+    }
+
+    let mut source = std::error::Error::source(err);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<Error>() {
+            return match e.kind() {
+                ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::TimedOut
+                | ErrorKind::UnexpectedEof => ErrorKind::Interrupted,
+                _ => ErrorKind::Other,
+            };
+        }
+        source = e.source();
+    }
+
+    ErrorKind::Other
+}
+
 /// parse_error_response will try to read and parse error response.
 pub fn parse_error_response(
     op: &'static str,
     path: &str,
-    parser: fn(StatusCode) -> ErrorKind,
+    parser: ErrorKindParser,
     resp: Response<Body>,
 ) -> ParseErrorResponse {
     let (parts, body) = resp.into_parts();
@@ -178,31 +216,50 @@ pub fn parse_error_response(
 pub struct ParseErrorResponse {
     op: &'static str,
     path: String,
-    parser: fn(StatusCode) -> ErrorKind,
+    parser: ErrorKindParser,
     parts: Parts,
     body: Body,
 
     buf: Vec<u8>,
 }
 
+impl ParseErrorResponse {
+    fn build_error(&self, read_err: Option<hyper::Error>) -> Error {
+        let code = parse_error_code(&self.parts.headers, &self.buf);
+        let kind = (self.parser)(self.parts.status, code.as_deref());
+
+        let err = match read_err {
+            None => anyhow!(
+                "status code: {:?}, error code: {:?}, headers: {:?}, body: {:?}",
+                self.parts.status,
+                code,
+                self.parts.headers,
+                String::from_utf8_lossy(&self.buf)
+            ),
+            Some(e) => anyhow!(
+                "status code: {:?}, error code: {:?}, headers: {:?}, read body: {:?}, remaining {:?}",
+                self.parts.status,
+                code,
+                self.parts.headers,
+                String::from_utf8_lossy(&self.buf),
+                e
+            ),
+        };
+
+        Error::new(
+            kind,
+            ObjectError::new(self.op, &self.path, err)
+                .with_retry_after(parse_retry_after(&self.parts.headers)),
+        )
+    }
+}
+
 impl Future for ParseErrorResponse {
     type Output = Error;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match ready!(Pin::new(&mut self.body).poll_data(cx)) {
-            None => Poll::Ready(Error::new(
-                (self.parser)(self.parts.status),
-                ObjectError::new(
-                    self.op,
-                    &self.path,
-                    anyhow!(
-                        "status code: {:?}, headers: {:?}, body: {:?}",
-                        self.parts.status,
-                        self.parts.headers,
-                        String::from_utf8_lossy(&self.buf)
-                    ),
-                ),
-            )),
+            None => Poll::Ready(self.build_error(None)),
             Some(Ok(data)) => {
                 // Only read 4KiB from the response to avoid broken services.
                 if self.buf.len() < 4 * 1024 {
@@ -212,27 +269,62 @@ impl Future for ParseErrorResponse {
                 // Make sure the whole body consumed, even we don't need them.
                 self.poll(cx)
             }
-            Some(Err(e)) => Poll::Ready(Error::new(
-                (self.parser)(self.parts.status),
-                ObjectError::new(
-                    self.op,
-                    &self.path,
-                    anyhow!(
-                        "status code: {:?}, headers: {:?}, read body: {:?}, remaining {:?}",
-                        self.parts.status,
-                        self.parts.headers,
-                        String::from_utf8_lossy(&self.buf),
-                        e
-                    ),
-                ),
-            )),
+            Some(Err(e)) => Poll::Ready(self.build_error(Some(e))),
         }
     }
 }
 
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct ErrorResponse {
+    code: String,
+}
+
+/// Parse the error code returned by services.
+///
+/// Azure Storage returns the error code in `x-ms-error-code`, and both
+/// AWS S3 and Azure Storage return it in the `<Code>` of the XML body.
+fn parse_error_code(headers: &HeaderMap, body: &[u8]) -> Option<String> {
+    if let Some(code) = headers.get("x-ms-error-code").and_then(|v| v.to_str().ok()) {
+        return Some(code.to_string());
+    }
+
+    let body = std::str::from_utf8(body).ok()?;
+    match quick_xml::de::from_str::<ErrorResponse>(body) {
+        Ok(resp) if !resp.code.is_empty() => Some(resp.code),
+        _ => None,
+    }
+}
+
+/// Parse the duration that services asked us to wait before retrying.
+///
+/// Both `x-ms-retry-after-ms` and `Retry-After` (in seconds or HTTP-date)
+/// are supported.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(ms) = headers
+        .get("x-ms-retry-after-ms")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+    {
+        return Some(Duration::from_millis(ms));
+    }
+
+    let v = headers
+        .get(http::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())?
+        .trim();
+    if let Ok(secs) = v.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = OffsetDateTime::parse(v, &Rfc2822).ok()?;
+    let secs = (at - OffsetDateTime::now_utc()).whole_seconds();
+    Some(Duration::from_secs(secs.max(0) as u64))
+}
+
 #[cfg(test)]
 mod tests {
     use futures::SinkExt;
+    use http::HeaderValue;
     use serde::Deserialize;
 
     use super::*;
@@ -267,4 +359,49 @@ mod tests {
         let content = fut.await.expect("future must polled");
         assert_eq!(&content.data, "Hello, World!")
     }
+
+    #[test]
+    fn test_parse_error_code() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error>
+  <Code>SlowDown</Code>
+  <Message>Please reduce your request rate.</Message>
+  <RequestId>4442587FB7D0A2F9</RequestId>
+</Error>"#;
+        assert_eq!(
+            parse_error_code(&HeaderMap::new(), body.as_bytes()),
+            Some("SlowDown".to_string())
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ms-error-code", HeaderValue::from_static("ServerBusy"));
+        assert_eq!(
+            parse_error_code(&headers, b""),
+            Some("ServerBusy".to_string())
+        );
+
+        assert_eq!(parse_error_code(&HeaderMap::new(), b"not xml"), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(http::header::RETRY_AFTER, HeaderValue::from_static("3"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(3)));
+
+        // Dates in the past should not be waited.
+        headers.insert(
+            http::header::RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(parse_retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert("x-ms-retry-after-ms", HeaderValue::from_static("1500"));
+        assert_eq!(
+            parse_retry_after(&headers),
+            Some(Duration::from_millis(1500))
+        );
+    }
 }
@@ -39,6 +39,7 @@ pub use write_observer::WriteObserver;
 mod http_body;
 pub(crate) use http_body::new_http_channel;
 pub(crate) use http_body::parse_error_response;
+pub(crate) use http_body::parse_hyper_error_kind;
 pub(crate) use http_body::HttpBodyWriter;
 
 mod http_client;
@@ -0,0 +1,726 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::ready;
+use futures::AsyncWrite;
+use futures::FutureExt;
+use log::debug;
+use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::error::other;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::Object;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+use crate::Operator;
+
+/// AuditRecord is the record of a mutating operation.
+///
+/// Records will be serialized as JSON lines into [`AuditSink`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Time that the operation finished, in RFC 3339 format.
+    pub timestamp: String,
+    /// Name of the operator, which is the name in [`AccessorMetadata`].
+    pub operator: String,
+    /// Operation, one of `create`, `write` and `delete`.
+    pub op: String,
+    /// Path of the object.
+    pub path: String,
+    /// Bytes written, only set for `write`.
+    pub size: Option<u64>,
+    /// ETag of the object after the operation.
+    pub etag: Option<String>,
+    /// Principal supplied by [`AuditLayer::with_principal`].
+    pub principal: Option<String>,
+    /// Context supplied by [`AuditLayer::with_context`].
+    pub context: BTreeMap<String, String>,
+    /// Error message if the operation failed.
+    pub error: Option<String>,
+}
+
+/// AuditSink is the destination of [`AuditRecord`].
+#[async_trait]
+pub trait AuditSink: Send + Sync + Debug + 'static {
+    /// Write a record into the sink.
+    ///
+    /// Errors will be logged, or returned from the operation if
+    /// [`AuditLayer::with_fail_on_sink_error`] is enabled.
+    async fn write(&self, record: &AuditRecord) -> Result<()>;
+}
+
+#[async_trait]
+impl<T: AuditSink> AuditSink for Arc<T> {
+    async fn write(&self, record: &AuditRecord) -> Result<()> {
+        self.as_ref().write(record).await
+    }
+}
+
+/// AuditLayer will write an [`AuditRecord`] into the sink for every
+/// `create`, `write` and `delete`.
+///
+/// # Behavior
+///
+/// - Records of `write` will be written while closing the writer.
+/// - Failed operations will also be recorded with the error.
+/// - Errors of the sink will be logged as warnings by default, enable
+///   [`AuditLayer::with_fail_on_sink_error`] to return them instead. The
+///   operation has been applied to the storage in either case.
+/// - ETag will be fetched by an extra `stat` after succeeded `create` and
+///   `write`.
+///
+/// `copy` and `rename` are streamed through this layer, so they are
+/// recorded as a `write` of the target (and a `delete` of the source).
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::AuditLayer;
+/// use opendal::layers::ObjectSink;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// # let audit = Operator::new(memory::Backend::build().finish().await?);
+/// let op = Operator::new(accessor).layer(
+///     AuditLayer::new(ObjectSink::rolling(audit, "audit/", 16 * 1024 * 1024))
+///         .with_principal("alice")
+///         .with_context("request_id", "42"),
+/// );
+///
+/// op.object("file").write("Hello, World!").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AuditLayer {
+    sink: Arc<dyn AuditSink>,
+    principal: Option<String>,
+    context: BTreeMap<String, String>,
+    fail_on_sink_error: bool,
+}
+
+impl AuditLayer {
+    /// Create a new AuditLayer which writes records into `sink`.
+    pub fn new(sink: impl AuditSink) -> Self {
+        AuditLayer {
+            sink: Arc::new(sink),
+            principal: None,
+            context: BTreeMap::new(),
+            fail_on_sink_error: false,
+        }
+    }
+
+    /// Set the principal that performs operations.
+    #[must_use]
+    pub fn with_principal(mut self, principal: &str) -> Self {
+        self.principal = Some(principal.to_string());
+        self
+    }
+
+    /// Add a key-value pair into the context of records.
+    #[must_use]
+    pub fn with_context(mut self, key: &str, value: &str) -> Self {
+        self.context.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Set whether operations should fail if their records can't be
+    /// written into the sink.
+    ///
+    /// Default to `false`. Note that the operation has been applied to the
+    /// storage even if it fails with the sink error.
+    #[must_use]
+    pub fn with_fail_on_sink_error(mut self, enabled: bool) -> Self {
+        self.fail_on_sink_error = enabled;
+        self
+    }
+}
+
+impl Layer for AuditLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(AuditAccessor {
+            core: Arc::new(AuditCore {
+                operator: inner.metadata().name().to_string(),
+                inner,
+                sink: self.sink.clone(),
+                principal: self.principal.clone(),
+                context: self.context.clone(),
+                fail_on_sink_error: self.fail_on_sink_error,
+            }),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct AuditCore {
+    inner: Arc<dyn Accessor>,
+    sink: Arc<dyn AuditSink>,
+    operator: String,
+    principal: Option<String>,
+    context: BTreeMap<String, String>,
+    fail_on_sink_error: bool,
+}
+
+impl AuditCore {
+    async fn etag(&self, path: &str) -> Option<String> {
+        let meta = self.inner.stat(&OpStat::new(path).ok()?).await.ok()?;
+        meta.etag().map(|v| v.to_string())
+    }
+
+    /// Build and write the record of operation.
+    async fn audit(
+        &self,
+        op: &str,
+        path: &str,
+        size: Option<u64>,
+        etag: Option<String>,
+        error: Option<String>,
+    ) -> Result<()> {
+        let record = AuditRecord {
+            timestamp: OffsetDateTime::now_utc().format(&Rfc3339).map_err(other)?,
+            operator: self.operator.clone(),
+            op: op.to_string(),
+            path: path.to_string(),
+            size,
+            etag,
+            principal: self.principal.clone(),
+            context: self.context.clone(),
+            error,
+        };
+
+        match self.sink.write(&record).await {
+            Ok(_) => {
+                debug!("object {} {} audited", path, op);
+                Ok(())
+            }
+            Err(e) if self.fail_on_sink_error => Err(e),
+            Err(e) => {
+                warn!("object {} {} audit failed: {:?}", path, op, e);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AuditAccessor {
+    core: Arc<AuditCore>,
+}
+
+#[async_trait]
+impl Accessor for AuditAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.core.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        let res = self.core.inner.create(args).await;
+
+        let etag = match (&res, args.mode()) {
+            (Ok(_), ObjectMode::FILE) => self.core.etag(args.path()).await,
+            _ => None,
+        };
+        let error = res.as_ref().err().map(|e| e.to_string());
+        self.core
+            .audit("create", args.path(), None, etag, error)
+            .await?;
+
+        res
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.core.inner.read(args).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        match self.core.inner.write(args).await {
+            Ok(w) => Ok(Box::new(AuditWriter {
+                core: self.core.clone(),
+                path: args.path().to_string(),
+                inner: w,
+                written: 0,
+                state: WriterState::Writing,
+            })),
+            Err(e) => {
+                self.core
+                    .audit("write", args.path(), None, None, Some(e.to_string()))
+                    .await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.core.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let res = self.core.inner.delete(args).await;
+
+        let error = res.as_ref().err().map(|e| e.to_string());
+        self.core
+            .audit("delete", args.path(), None, None, error)
+            .await?;
+
+        res
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.core.inner.list(args).await
+    }
+}
+
+enum WriterState {
+    Writing,
+    /// Writing the record, carries the result of closing inner writer.
+    Auditing(BoxFuture<'static, Result<()>>, Result<()>),
+    Done,
+}
+
+struct AuditWriter {
+    core: Arc<AuditCore>,
+    path: String,
+    inner: BytesWriter,
+    written: u64,
+    state: WriterState,
+}
+
+impl AsyncWrite for AuditWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.written += n as u64;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        loop {
+            match &mut self.state {
+                WriterState::Writing => {
+                    let res = ready!(Pin::new(&mut self.inner).poll_close(cx));
+
+                    let core = self.core.clone();
+                    let path = self.path.clone();
+                    let written = self.written;
+                    let error = res.as_ref().err().map(|e| e.to_string());
+                    let fut = async move {
+                        let etag = match error {
+                            None => core.etag(&path).await,
+                            Some(_) => None,
+                        };
+                        core.audit("write", &path, Some(written), etag, error).await
+                    };
+                    self.state = WriterState::Auditing(fut.boxed(), res);
+                }
+                WriterState::Auditing(fut, _) => {
+                    let audit_res = ready!(fut.poll_unpin(cx));
+                    let res = match std::mem::replace(&mut self.state, WriterState::Done) {
+                        WriterState::Auditing(_, res) => res,
+                        _ => unreachable!(),
+                    };
+                    return Poll::Ready(res.and(audit_res));
+                }
+                WriterState::Done => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+/// Default max size of objects written by [`ObjectSink::append`].
+const DEFAULT_MAX_OBJECT_SIZE: u64 = 16 * 1024 * 1024;
+
+enum SinkMode {
+    Append(String),
+    Rolling(String),
+}
+
+/// State of [`ObjectSink`], keeps content of the current object so that we
+/// don't need to read it back for every flush.
+#[derive(Default)]
+struct SinkState {
+    path: Option<String>,
+    buf: Vec<u8>,
+    /// Records that haven't been flushed.
+    pending: Vec<u8>,
+    pending_records: usize,
+}
+
+/// ObjectSink writes JSON lines of [`AuditRecord`] into OpenDAL objects.
+///
+/// Services don't support appending to objects, so the current object will
+/// be rewritten for every flush. Once it would exceed the max size, records
+/// will roll over to a new object, so the cost of every flush and the
+/// memory kept by the sink are bounded by the max size:
+///
+/// - Use [`ObjectSink::with_max_size`] to change the max size.
+/// - Use [`ObjectSink::with_batch_size`] to flush records in batches.
+///   Pending records will be lost if the process exits before
+///   [`ObjectSink::flush`], wrap the sink in an `Arc` to keep a handle.
+///
+/// Failed flushes will keep records pending and retry with the next flush.
+///
+/// Records are serialized inside the process only, multiple processes
+/// must not write into the same object.
+pub struct ObjectSink {
+    op: Operator,
+    mode: SinkMode,
+    max_size: u64,
+    batch_size: usize,
+    state: tokio::sync::Mutex<SinkState>,
+}
+
+impl Debug for ObjectSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut de = f.debug_struct("ObjectSink");
+        match &self.mode {
+            SinkMode::Append(path) => de.field("path", path),
+            SinkMode::Rolling(dir) => de.field("dir", dir),
+        };
+        de.field("max_size", &self.max_size)
+            .field("batch_size", &self.batch_size)
+            .finish()
+    }
+}
+
+impl ObjectSink {
+    /// Append records into `object`, existing content will be kept.
+    ///
+    /// Once `object` is full, records will go into objects named as
+    /// `<object>.<unix_timestamp_nanos>`. The max size is default to 16 MiB.
+    pub fn append(object: Object) -> Self {
+        ObjectSink {
+            op: Operator::new(object.accessor()),
+            mode: SinkMode::Append(object.path().to_string()),
+            max_size: DEFAULT_MAX_OBJECT_SIZE,
+            batch_size: 1,
+            state: Default::default(),
+        }
+    }
+
+    /// Write records into objects under `dir`, a new object will be created
+    /// once the current one reaches `max_size`.
+    ///
+    /// Objects are named as `audit-<unix_timestamp_nanos>.jsonl`.
+    pub fn rolling(op: Operator, dir: &str, max_size: u64) -> Self {
+        let mut dir = dir.trim_start_matches('/').to_string();
+        if !dir.is_empty() && !dir.ends_with('/') {
+            dir.push('/');
+        }
+
+        ObjectSink {
+            op,
+            mode: SinkMode::Rolling(dir),
+            max_size,
+            batch_size: 1,
+            state: Default::default(),
+        }
+    }
+
+    /// Set the max size of objects before rolling over to a new one.
+    ///
+    /// A single flush larger than this will still be written into one
+    /// object.
+    #[must_use]
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Flush records once `batch_size` records are pending.
+    ///
+    /// Default to 1, which flushes every record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is zero.
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be greater than 0");
+
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Flush all pending records.
+    pub async fn flush(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        self.flush_locked(&mut state).await
+    }
+
+    async fn flush_locked(&self, state: &mut SinkState) -> Result<()> {
+        if state.pending.is_empty() {
+            return Ok(());
+        }
+
+        let pending = state.pending.len() as u64;
+        if state.path.is_none() {
+            if let SinkMode::Append(path) = &self.mode {
+                // Existing content will only be read if it has room left.
+                let size = match self.op.object(path).metadata().await {
+                    Ok(meta) => Some(meta.content_length()),
+                    Err(e) if e.kind() == ErrorKind::NotFound => None,
+                    Err(e) => return Err(e),
+                };
+                match size {
+                    // Full already, roll over to a new object below.
+                    Some(size) if size > 0 && size + pending > self.max_size => {}
+                    Some(size) if size > 0 => {
+                        state.buf = self.op.object(path).read().await?;
+                        state.path = Some(path.clone());
+                    }
+                    _ => {
+                        state.buf.clear();
+                        state.path = Some(path.clone());
+                    }
+                }
+            }
+        }
+        let full = !state.buf.is_empty() && state.buf.len() as u64 + pending > self.max_size;
+        if state.path.is_none() || full {
+            let nanos = OffsetDateTime::now_utc().unix_timestamp_nanos();
+            state.path = Some(match &self.mode {
+                SinkMode::Append(path) => format!("{path}.{nanos:020}"),
+                SinkMode::Rolling(dir) => format!("{dir}audit-{nanos:020}.jsonl"),
+            });
+            state.buf.clear();
+        }
+
+        let path = state.path.clone().expect("path must be set");
+        let mut content = Vec::with_capacity(state.buf.len() + state.pending.len());
+        content.extend_from_slice(&state.buf);
+        content.extend_from_slice(&state.pending);
+        self.op.object(&path).write(content.clone()).await?;
+
+        debug!("audit sink flushed {} records", state.pending_records);
+        state.buf = content;
+        state.pending.clear();
+        state.pending_records = 0;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuditSink for ObjectSink {
+    async fn write(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record).map_err(other)?;
+        line.push(b'\n');
+
+        let mut state = self.state.lock().await;
+        state.pending.extend_from_slice(&line);
+        state.pending_records += 1;
+        if state.pending_records < self.batch_size {
+            return Ok(());
+        }
+
+        self.flush_locked(&mut state).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::services::memory;
+    use crate::DirEntry;
+
+    #[derive(Debug, Default, Clone)]
+    struct VecSink(Arc<Mutex<Vec<AuditRecord>>>);
+
+    #[async_trait]
+    impl AuditSink for VecSink {
+        async fn write(&self, record: &AuditRecord) -> Result<()> {
+            self.0.lock().push(record.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit() -> anyhow::Result<()> {
+        let sink = VecSink::default();
+        let op = Operator::new(memory::Backend::build().finish().await?).layer(
+            AuditLayer::new(sink.clone())
+                .with_principal("alice")
+                .with_context("request_id", "42"),
+        );
+
+        op.object("file").write("Hello, World!").await?;
+        op.object("file").read().await?;
+        op.object("file").delete().await?;
+
+        let records = sink.0.lock().clone();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].op, "write");
+        assert_eq!(records[0].path, "file");
+        assert_eq!(records[0].size, Some(13));
+        assert_eq!(records[0].operator, "memory");
+        assert_eq!(records[0].principal.as_deref(), Some("alice"));
+        assert_eq!(records[0].context.get("request_id").unwrap(), "42");
+        assert_eq!(records[1].op, "delete");
+        assert_eq!(records[1].error, None);
+
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct BrokenSink;
+
+    #[async_trait]
+    impl AuditSink for BrokenSink {
+        async fn write(&self, _: &AuditRecord) -> Result<()> {
+            Err(other(anyhow::anyhow!("sink is broken")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit_sink_error() -> anyhow::Result<()> {
+        let acc = memory::Backend::build().finish().await?;
+
+        let op = Operator::new(acc.clone()).layer(AuditLayer::new(BrokenSink));
+        op.object("file").write("Hello, World!").await?;
+        op.object("file").delete().await?;
+
+        let op = Operator::new(acc.clone())
+            .layer(AuditLayer::new(BrokenSink).with_fail_on_sink_error(true));
+        let err = op.object("file").write("Hello, World!").await.unwrap_err();
+        assert!(err.to_string().contains("sink is broken"));
+        // The write has been applied anyway.
+        assert!(Operator::new(acc).object("file").is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_sink_batch() -> anyhow::Result<()> {
+        let audit = Operator::new(memory::Backend::build().finish().await?);
+        let sink = Arc::new(ObjectSink::append(audit.object("audit.jsonl")).with_batch_size(2));
+
+        let op = Operator::new(memory::Backend::build().finish().await?)
+            .layer(AuditLayer::new(sink.clone()));
+        op.object("a").write("Hello").await?;
+        assert!(!audit.object("audit.jsonl").is_exist().await?);
+        op.object("b").write("World").await?;
+        op.object("c").write("!").await?;
+
+        let lines = |bs: Vec<u8>| bs.iter().filter(|v| **v == b'\n').count();
+        assert_eq!(lines(audit.object("audit.jsonl").read().await?), 2);
+        sink.flush().await?;
+        assert_eq!(lines(audit.object("audit.jsonl").read().await?), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_sink() -> anyhow::Result<()> {
+        let audit = Operator::new(memory::Backend::build().finish().await?);
+        audit.object("audit/").create().await?;
+
+        let op = Operator::new(memory::Backend::build().finish().await?).layer(AuditLayer::new(
+            ObjectSink::append(audit.object("audit.jsonl")),
+        ));
+        op.object("a").write("Hello").await?;
+        op.object("b").write("World").await?;
+
+        let content = audit.object("audit.jsonl").read().await?;
+        let records: Vec<AuditRecord> = content
+            .split(|v| *v == b'\n')
+            .filter(|v| !v.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<std::result::Result<_, _>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].path, "b");
+
+        // Every record is larger than 10 bytes, so each one rolls a new object.
+        let op = Operator::new(memory::Backend::build().finish().await?).layer(AuditLayer::new(
+            ObjectSink::rolling(audit.clone(), "audit/", 10),
+        ));
+        op.object("a").write("Hello").await?;
+        op.object("b").write("World").await?;
+
+        let entries: Vec<DirEntry> = audit.object("audit/").list().await?.try_collect().await?;
+        assert_eq!(entries.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_sink_append_max_size() -> anyhow::Result<()> {
+        let audit = Operator::new(memory::Backend::build().finish().await?);
+        audit.object("logs/").create().await?;
+        audit.object("logs/audit.jsonl").write("existing\n").await?;
+
+        // Records are larger than the rest of the object, so every one of
+        // them rolls over to a new object.
+        let op = Operator::new(memory::Backend::build().finish().await?).layer(AuditLayer::new(
+            ObjectSink::append(audit.object("logs/audit.jsonl")).with_max_size(20),
+        ));
+        op.object("a").write("Hello").await?;
+        op.object("b").write("World").await?;
+
+        assert_eq!(
+            audit.object("logs/audit.jsonl").read().await?,
+            b"existing\n"
+        );
+        let mut entries: Vec<String> = audit
+            .object("logs/")
+            .list()
+            .await?
+            .map_ok(|de| de.path().to_string())
+            .try_collect()
+            .await?;
+        entries.sort();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[1].starts_with("logs/audit.jsonl."));
+
+        Ok(())
+    }
+}
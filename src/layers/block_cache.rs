@@ -0,0 +1,593 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::min;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::io::Result;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::io::Cursor;
+use futures::stream;
+use futures::AsyncReadExt;
+use futures::AsyncWriteExt;
+use futures::FutureExt;
+use futures::StreamExt;
+use log::debug;
+use log::warn;
+use parking_lot::Mutex;
+
+use super::cache::InvalidateWriter;
+use super::cache_index::CacheIndex;
+use super::singleflight::Group;
+use crate::error::other;
+use crate::error::ObjectError;
+use crate::io_util::into_reader;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// Default size of a block: 1 MiB.
+const DEFAULT_BLOCK_SIZE: u64 = 1024 * 1024;
+/// Default time that object metadata will be trusted: 10 seconds.
+const DEFAULT_TTL: Duration = Duration::from_secs(10);
+
+/// BlockCacheLayer will split objects into fixed-size aligned blocks and
+/// cache them in another accessor.
+///
+/// # Behavior
+///
+/// - `read` will be served by blocks that cover the requested range, only
+///   missing blocks will be fetched from the underlying storage.
+/// - Concurrent fetches of the same block will be deduplicated.
+/// - `write`, `create` and `delete` will invalidate all cached blocks of
+///   the object.
+/// - `read` will `stat` the underlying storage if the object metadata has
+///   not been checked within `ttl`, cached blocks will be dropped if the
+///   size or `ETag` of the object has been changed.
+/// - Cached blocks will be evicted in LRU order once the total size
+///   exceeds `max_bytes`.
+///
+/// Both [`Object::range_read`][crate::Object::range_read] and
+/// [`SeekableReader`][crate::io_util::SeekableReader] benefit from this layer.
+///
+/// # Notes
+///
+/// Use [`memory`][crate::services::memory] backend to cache blocks in memory,
+/// or [`fs`][crate::services::fs] backend to cache blocks on local disk. The
+/// cache storage SHOULD be dedicated to this layer.
+///
+/// Changes that don't go through this layer could be served stale for up
+/// to `ttl`.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::BlockCacheLayer;
+/// use opendal::services::fs;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let cache = fs::Backend::build().root("/tmp/opendal/blocks").finish().await?;
+/// let op = Operator::new(accessor).layer(
+///     BlockCacheLayer::new(cache)
+///         .with_block_size(4 * 1024 * 1024)
+///         .with_max_bytes(1024 * 1024 * 1024),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BlockCacheLayer {
+    cache: Arc<dyn Accessor>,
+    block_size: u64,
+    max_bytes: Option<u64>,
+    ttl: Duration,
+}
+
+impl BlockCacheLayer {
+    /// Create a new BlockCacheLayer which uses `cache` as the block storage.
+    pub fn new(cache: Arc<dyn Accessor>) -> Self {
+        BlockCacheLayer {
+            cache,
+            block_size: DEFAULT_BLOCK_SIZE,
+            max_bytes: None,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Set the size of every block.
+    ///
+    /// Default to 1 MiB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is zero.
+    #[must_use]
+    pub fn with_block_size(mut self, block_size: u64) -> Self {
+        assert!(block_size > 0, "block size must be greater than 0");
+
+        self.block_size = block_size;
+        self
+    }
+
+    /// Set the max total bytes of cached blocks.
+    ///
+    /// Least recently used blocks will be evicted once exceeded.
+    /// By default, there is no limit.
+    #[must_use]
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Set the time that object metadata will be trusted before checked
+    /// by `stat` again.
+    ///
+    /// Default to 10 seconds, set to `Duration::ZERO` to check on every read.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+impl Layer for BlockCacheLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(BlockCacheAccessor {
+            inner,
+            cache: self.cache.clone(),
+            block_size: self.block_size,
+            ttl: self.ttl,
+            state: Arc::new(BlockCacheState {
+                index: Mutex::new(CacheIndex::new(self.max_bytes)),
+                objects: Mutex::new(HashMap::new()),
+                inflight: Arc::new(Group::default()),
+                next_id: AtomicU64::new(0),
+            }),
+        })
+    }
+}
+
+/// ObjectEntry records the object that blocks belongs to.
+///
+/// `id` changes every time the object has been invalidated so that
+/// in-flight fetches of an invalidated object won't be cached.
+#[derive(Debug, Clone)]
+struct ObjectEntry {
+    id: u64,
+    size: u64,
+    etag: Option<String>,
+    /// Time that the metadata has been checked against the storage.
+    checked: Instant,
+}
+
+struct BlockCacheState {
+    index: Mutex<CacheIndex>,
+    objects: Mutex<HashMap<String, ObjectEntry>>,
+    /// In-flight fetches by block key and object id.
+    inflight: Arc<Group<(String, u64), Bytes>>,
+    next_id: AtomicU64,
+}
+
+impl BlockCacheState {
+    /// Forget the object and all its blocks, returns the keys of
+    /// blocks that need to be removed from cache storage.
+    fn forget(&self, path: &str, block_size: u64) -> Vec<String> {
+        let entry = match self.objects.lock().remove(path) {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+
+        let mut index = self.index.lock();
+        (0..entry.size.div_ceil(block_size))
+            .map(|idx| block_key(path, idx))
+            .filter(|key| index.remove(key).is_some())
+            .collect()
+    }
+}
+
+#[derive(Clone)]
+struct BlockCacheAccessor {
+    inner: Arc<dyn Accessor>,
+    cache: Arc<dyn Accessor>,
+    block_size: u64,
+    ttl: Duration,
+    state: Arc<BlockCacheState>,
+}
+
+impl Debug for BlockCacheAccessor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockCacheAccessor")
+            .field("inner", &self.inner)
+            .field("cache", &self.cache)
+            .field("block_size", &self.block_size)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BlockCacheAccessor {
+    /// Get the object entry, cached blocks will be invalidated if the
+    /// object has been changed in the underlying storage.
+    async fn object(&self, path: &str) -> Result<ObjectEntry> {
+        let cached = self.state.objects.lock().get(path).cloned();
+        if let Some(entry) = &cached {
+            if entry.checked.elapsed() < self.ttl {
+                return Ok(entry.clone());
+            }
+        }
+
+        let meta = self.inner.stat(&OpStat::new(path)?).await?;
+        let etag = meta.etag().map(String::from);
+
+        if let Some(entry) = cached {
+            if entry.size == meta.content_length() && entry.etag == etag {
+                if let Some(v) = self.state.objects.lock().get_mut(path) {
+                    if v.id == entry.id {
+                        v.checked = Instant::now();
+                    }
+                }
+                return Ok(entry);
+            }
+            debug!("object {} has been changed, drop cached blocks", path);
+            self.invalidate(path).await;
+        }
+
+        let entry = ObjectEntry {
+            id: self.state.next_id.fetch_add(1, Ordering::Relaxed),
+            size: meta.content_length(),
+            etag,
+            checked: Instant::now(),
+        };
+        Ok(self
+            .state
+            .objects
+            .lock()
+            .entry(path.to_string())
+            .or_insert(entry)
+            .clone())
+    }
+
+    async fn invalidate(&self, path: &str) {
+        for key in self.state.forget(path, self.block_size) {
+            self.evict(&key).await
+        }
+    }
+
+    async fn evict(&self, key: &str) {
+        let op = match OpDelete::new(key) {
+            Ok(op) => op,
+            Err(_) => return,
+        };
+        if let Err(e) = self.cache.delete(&op).await {
+            warn!("block {} evict from cache: {:?}", key, e);
+        }
+    }
+
+    /// Fetch the block from cache storage, or from the underlying storage
+    /// while it's not cached.
+    async fn fetch_block(&self, path: &str, entry: &ObjectEntry, idx: u64) -> Result<Bytes> {
+        let key = block_key(path, idx);
+
+        if self.state.index.lock().get(&key).is_some() {
+            match read_all(&self.cache, &key).await {
+                Ok(bs) => {
+                    debug!("object {} block {} read from cache", path, idx);
+                    return Ok(bs);
+                }
+                Err(e) => {
+                    warn!("object {} block {} read from cache: {:?}", path, idx, e);
+                    self.state.index.lock().remove(&key);
+                }
+            }
+        }
+
+        // Fetches of invalidated objects must not be joined.
+        self.state
+            .inflight
+            .work((key.clone(), entry.id), || {
+                let acc = self.clone();
+                let path = path.to_string();
+                let entry = entry.clone();
+                async move { acc.load_block(&path, &key, &entry, idx).await }
+            })
+            .await
+    }
+
+    /// Load the block from the underlying storage and fill it into cache.
+    async fn load_block(
+        &self,
+        path: &str,
+        key: &str,
+        entry: &ObjectEntry,
+        idx: u64,
+    ) -> Result<Bytes> {
+        let offset = idx * self.block_size;
+        let size = min(self.block_size, entry.size - offset);
+
+        let op = OpRead::new_with_offset(path, Some(offset), Some(size))?;
+        let mut r = self.inner.read(&op).await?;
+        let mut bs = Vec::with_capacity(size as usize);
+        r.read_to_end(&mut bs).await?;
+        debug!("object {} block {} fetched: size {}", path, idx, bs.len());
+
+        if let Err(e) = write_all(&self.cache, key, &bs).await {
+            warn!("object {} block {} fill into cache: {:?}", path, idx, e);
+            return Ok(Bytes::from(bs));
+        }
+
+        // Object has been invalidated during fetching.
+        let current = self.state.objects.lock().get(path).map(|v| v.id);
+        if current != Some(entry.id) {
+            self.evict(key).await;
+            return Ok(Bytes::from(bs));
+        }
+
+        let evicted = self.state.index.lock().insert(key, bs.len() as u64, None);
+        for key in evicted {
+            self.evict(&key).await;
+        }
+
+        Ok(Bytes::from(bs))
+    }
+}
+
+#[async_trait]
+impl Accessor for BlockCacheAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inner.create(args).await?;
+        self.invalidate(args.path()).await;
+        Ok(())
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let path = args.path();
+        let entry = self.object(path).await?;
+
+        let start = args.offset().unwrap_or_default();
+        let end = match args.size() {
+            Some(size) => start + size,
+            None => entry.size,
+        };
+        if start > entry.size || end > entry.size {
+            return Err(other(ObjectError::new(
+                "read",
+                path,
+                anyhow!("range out of bound {}..{} > {}", start, end, entry.size),
+            )));
+        }
+        if start == end {
+            return Ok(Box::new(Cursor::new(Bytes::new())));
+        }
+
+        let block_size = self.block_size;
+        let acc = self.clone();
+        let path = path.to_string();
+        let blocks = stream::iter(start / block_size..=(end - 1) / block_size).then(move |idx| {
+            let acc = acc.clone();
+            let path = path.clone();
+            let entry = entry.clone();
+            async move {
+                let bs = acc.fetch_block(&path, &entry, idx).await?;
+
+                let block_start = idx * block_size;
+                let from = start.saturating_sub(block_start) as usize;
+                let to = min(end - block_start, bs.len() as u64) as usize;
+                Ok(bs.slice(from..to))
+            }
+        });
+
+        Ok(Box::new(into_reader(Box::pin(blocks))))
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.invalidate(args.path()).await;
+
+        let w = self.inner.write(args).await?;
+
+        // Make sure the blocks filled by reads during writing are dropped too.
+        let acc = self.clone();
+        let path = args.path().to_string();
+        Ok(Box::new(InvalidateWriter::new(
+            w,
+            async move { acc.invalidate(&path).await }.boxed(),
+        )))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await?;
+        self.invalidate(args.path()).await;
+        Ok(())
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+/// Build the key of block in cache storage.
+///
+/// Path will be hashed so that blocks of `abc` and `abc/def` won't conflict.
+fn block_key(path: &str, idx: u64) -> String {
+    format!("{:x}/{}", md5::compute(path), idx)
+}
+
+async fn read_all(acc: &Arc<dyn Accessor>, path: &str) -> Result<Bytes> {
+    let mut r = acc.read(&OpRead::new(path, ..)?).await?;
+    let mut bs = Vec::new();
+    r.read_to_end(&mut bs).await?;
+    Ok(Bytes::from(bs))
+}
+
+async fn write_all(acc: &Arc<dyn Accessor>, path: &str, bs: &[u8]) -> Result<()> {
+    let mut w = acc.write(&OpWrite::new(path, bs.len() as u64)?).await?;
+    w.write_all(bs).await?;
+    w.close().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::SeekFrom;
+    use std::time::Duration;
+
+    use futures::AsyncSeekExt;
+
+    use super::*;
+    use crate::layers::test_util::CountingAccessor;
+    use crate::services::memory;
+    use crate::Operator;
+
+    async fn new_operator(ttl: Duration) -> anyhow::Result<(Arc<CountingAccessor>, Operator)> {
+        // Delay reads to make sure concurrent reads overlap.
+        let srv = CountingAccessor::new(Duration::from_millis(10)).await?;
+        let cache = memory::Backend::build().finish().await?;
+        let op = Operator::new(srv.clone())
+            .layer(BlockCacheLayer::new(cache).with_block_size(4).with_ttl(ttl));
+
+        let content: Vec<u8> = (0..26).map(|v| b'a' + v).collect();
+        op.object("data").write(content).await?;
+
+        Ok((srv, op))
+    }
+
+    #[tokio::test]
+    async fn test_block_cache_range_read() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(DEFAULT_TTL).await?;
+        let o = op.object("data");
+
+        // Blocks 0, 1, 2
+        assert_eq!(o.range_read(2..10).await?, b"cdefghij");
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 3);
+
+        // Blocks 1, 2 are cached, only block 3 needs to be fetched.
+        assert_eq!(o.range_read(5..14).await?, b"fghijklmn");
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 4);
+
+        // Blocks 5, 6 and the last block is shorter than block size.
+        assert_eq!(o.range_read(23..).await?, b"xyz");
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 6);
+        // Object metadata is only checked once within ttl.
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 1);
+
+        let mut r = o.seekable_reader(..);
+        r.seek(SeekFrom::Start(4)).await?;
+        let mut bs = vec![0; 4];
+        r.read_exact(&mut bs).await?;
+        assert_eq!(bs, b"efgh");
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 6);
+
+        // Write through the layer should invalidate all blocks.
+        o.write("Hello, World!").await?;
+        assert_eq!(o.range_read(7..12).await?, b"World");
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 8);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_cache_stale() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(Duration::from_millis(50)).await?;
+        assert_eq!(op.object("data").range_read(0..4).await?, b"abcd");
+
+        // Changed without going through the layer.
+        Operator::new(srv.clone())
+            .object("data")
+            .write("Hello, World!")
+            .await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(op.object("data").read().await?, b"Hello, World!");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_cache_fetch_error() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(DEFAULT_TTL).await?;
+        assert_eq!(op.object("data").range_read(0..4).await?, b"abcd");
+
+        // Removed without going through the layer, fetching uncached blocks
+        // fails with the original error.
+        Operator::new(srv.clone()).object("data").delete().await?;
+        let err = op.object("data").range_read(4..8).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(err.get_ref().unwrap().is::<ObjectError>());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_cache_fill_during_write() -> anyhow::Result<()> {
+        let cache = memory::Backend::build().finish().await?;
+        let op = Operator::new(memory::Backend::build().finish().await?)
+            .layer(BlockCacheLayer::new(cache.clone()).with_block_size(4));
+        op.object("data").write("abcdef").await?;
+
+        // Fill blocks while the writer is still open.
+        let mut w = op.object("data").writer(2).await?;
+        w.write_all(b"gh").await?;
+        assert_eq!(op.object("data").read().await?, b"abcdef");
+
+        w.close().await?;
+        let key = block_key("data", 0);
+        assert!(!Operator::new(cache).object(&key).is_exist().await?);
+        assert_eq!(op.object("data").read().await?, b"gh");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_cache_dedup() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(DEFAULT_TTL).await?;
+
+        let objects: Vec<_> = (0..8).map(|_| op.object("data")).collect();
+        let reads = objects.iter().map(|o| o.range_read(0..4));
+        for bs in futures::future::join_all(reads).await {
+            assert_eq!(bs?, b"abcd");
+        }
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,623 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::io::Cursor;
+use futures::ready;
+use futures::AsyncReadExt;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use futures::FutureExt;
+use log::debug;
+use log::warn;
+use parking_lot::Mutex;
+
+use super::cache_index::CacheEntry;
+use super::cache_index::CacheIndex;
+use crate::error::other;
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// Default max size of a single object that can be cached: 8 MiB.
+const DEFAULT_MAX_OBJECT_SIZE: u64 = 8 * 1024 * 1024;
+
+/// CacheLayer will cache whole objects in another accessor.
+///
+/// # Behavior
+///
+/// - `read` will be served from the cache storage if the object has been
+///   cached, otherwise the whole object will be fetched from the underlying
+///   storage and written into the cache storage.
+/// - `write`, `create` and `delete` will invalidate the cached object, fills
+///   started before the invalidation will be dropped.
+/// - Reads with `if_match` will bypass the cache.
+/// - Objects larger than `max_object_size` will not be cached.
+/// - Cached objects will be evicted in LRU order once the total size
+///   exceeds `max_bytes`.
+/// - With `revalidate` enabled, every cache hit will `stat` the underlying
+///   storage and drop the cached object if its `ETag` changed.
+///
+/// # Notes
+///
+/// The cache index is kept in memory, the cache storage SHOULD be dedicated
+/// to this layer.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::CacheLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let cache = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(
+///     CacheLayer::new(cache)
+///         .with_max_bytes(64 * 1024 * 1024)
+///         .with_revalidate(true),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CacheLayer {
+    cache: Arc<dyn Accessor>,
+    max_bytes: Option<u64>,
+    max_object_size: u64,
+    revalidate: bool,
+}
+
+impl CacheLayer {
+    /// Create a new CacheLayer which uses `cache` as the cache storage.
+    pub fn new(cache: Arc<dyn Accessor>) -> Self {
+        CacheLayer {
+            cache,
+            max_bytes: None,
+            max_object_size: DEFAULT_MAX_OBJECT_SIZE,
+            revalidate: false,
+        }
+    }
+
+    /// Set the max total bytes of cached objects.
+    ///
+    /// Least recently used objects will be evicted once exceeded.
+    /// By default, there is no limit.
+    #[must_use]
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Set the max size of a single object that can be cached.
+    ///
+    /// Default to 8 MiB.
+    #[must_use]
+    pub fn with_max_object_size(mut self, size: u64) -> Self {
+        self.max_object_size = size;
+        self
+    }
+
+    /// Set whether cache hits should be revalidated by `stat`.
+    ///
+    /// Default to `false`.
+    #[must_use]
+    pub fn with_revalidate(mut self, revalidate: bool) -> Self {
+        self.revalidate = revalidate;
+        self
+    }
+}
+
+impl Layer for CacheLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(CacheAccessor {
+            inner,
+            cache: self.cache.clone(),
+            index: Arc::new(Mutex::new(CacheIndex::new(self.max_bytes))),
+            fills: Arc::new(Mutex::new(HashMap::new())),
+            max_object_size: self.max_object_size,
+            revalidate: self.revalidate,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheAccessor {
+    inner: Arc<dyn Accessor>,
+    cache: Arc<dyn Accessor>,
+    index: Arc<Mutex<CacheIndex>>,
+    /// In-flight fills by path, set to `true` if the path has been
+    /// invalidated during filling.
+    ///
+    /// Lock `fills` before `index` while both are needed.
+    fills: Arc<Mutex<HashMap<String, bool>>>,
+    max_object_size: u64,
+    revalidate: bool,
+}
+
+impl CacheAccessor {
+    /// Drop the cached object of path.
+    async fn invalidate(&self, path: &str) {
+        let removed = {
+            let mut fills = self.fills.lock();
+            if let Some(stale) = fills.get_mut(path) {
+                *stale = true;
+            }
+            self.index.lock().remove(path)
+        };
+        if removed.is_none() {
+            return;
+        }
+
+        debug!("object {} invalidated in cache", path);
+        self.evict(path).await
+    }
+
+    /// Remove cached data from the cache storage, failures will be ignored
+    /// since the cache index has been updated.
+    async fn evict(&self, path: &str) {
+        let op = match OpDelete::new(path) {
+            Ok(op) => op,
+            Err(_) => return,
+        };
+        if let Err(e) = self.cache.delete(&op).await {
+            warn!("object {} evict from cache: {:?}", path, e);
+        }
+    }
+
+    /// Write the whole object into cache storage.
+    async fn fill(&self, path: &str, bs: &[u8]) -> Result<()> {
+        let op = OpWrite::new(path, bs.len() as u64)?;
+        let mut w = self.cache.write(&op).await?;
+        w.write_all(bs).await?;
+        w.close().await
+    }
+
+    /// Check whether the cached object is still the same with the underlying
+    /// storage.
+    ///
+    /// Returns the fetched metadata if the cached object is stale.
+    async fn validate(&self, path: &str, entry: &CacheEntry) -> Result<Option<ObjectMetadata>> {
+        if !self.revalidate {
+            return Ok(None);
+        }
+
+        let meta = self.inner.stat(&OpStat::new(path)?).await?;
+        if meta.etag() == entry.etag.as_deref() && meta.content_length() == entry.size {
+            Ok(None)
+        } else {
+            debug!("object {} cache is stale", path);
+            Ok(Some(meta))
+        }
+    }
+}
+
+#[async_trait]
+impl Accessor for CacheAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inner.create(args).await?;
+        self.invalidate(args.path()).await;
+        Ok(())
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let path = args.path();
+        if args.if_match().is_some() {
+            return self.inner.read(args).await;
+        }
+
+        let entry = self.index.lock().get(path);
+        let mut meta = None;
+        if let Some(entry) = entry {
+            match self.validate(path, &entry).await {
+                Ok(None) => match self.cache.read(args).await {
+                    Ok(r) => {
+                        debug!("object {} read from cache", path);
+                        return Ok(r);
+                    }
+                    Err(e) => warn!("object {} read from cache: {:?}", path, e),
+                },
+                Ok(Some(m)) => meta = Some(m),
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    self.invalidate(path).await;
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
+            self.invalidate(path).await;
+        }
+
+        // Only one read fills the path at the same time, others will read
+        // from the underlying storage directly.
+        let guard = match FillGuard::claim(&self.fills, path) {
+            Some(guard) => guard,
+            None => return self.inner.read(args).await,
+        };
+
+        let meta = match meta {
+            Some(meta) => meta,
+            None => self.inner.stat(&OpStat::new(path)?).await?,
+        };
+        if meta.content_length() > self.max_object_size {
+            return self.inner.read(args).await;
+        }
+
+        let mut r = self.inner.read(&OpRead::new(path, ..)?).await?;
+        let mut bs = Vec::with_capacity(meta.content_length() as usize);
+        r.read_to_end(&mut bs).await?;
+
+        match self.fill(path, &bs).await {
+            Ok(_) => {
+                let evicted = {
+                    let fills = self.fills.lock();
+                    if fills.get(path) == Some(&true) {
+                        None
+                    } else {
+                        Some(self.index.lock().insert(
+                            path,
+                            bs.len() as u64,
+                            meta.etag().map(String::from),
+                        ))
+                    }
+                };
+                match evicted {
+                    Some(evicted) => {
+                        debug!("object {} filled into cache: size {}", path, bs.len());
+                        for p in evicted {
+                            debug!("object {} evicted from cache", p);
+                            self.evict(&p).await;
+                        }
+                    }
+                    None => {
+                        debug!("object {} invalidated while filling", path);
+                        self.evict(path).await;
+                    }
+                }
+            }
+            Err(e) => warn!("object {} fill into cache: {:?}", path, e),
+        }
+        drop(guard);
+
+        let bs = slice_range(path, Bytes::from(bs), args.offset(), args.size())?;
+        Ok(Box::new(Cursor::new(bs)))
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.invalidate(args.path()).await;
+
+        let w = self.inner.write(args).await?;
+
+        // Make sure the object filled by reads during writing is dropped too.
+        let acc = self.clone();
+        let path = args.path().to_string();
+        Ok(Box::new(InvalidateWriter::new(
+            w,
+            async move { acc.invalidate(&path).await }.boxed(),
+        )))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await?;
+        self.invalidate(args.path()).await;
+        Ok(())
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+/// FillGuard claims the in-flight fill of the path, and releases it after
+/// dropped.
+struct FillGuard {
+    fills: Arc<Mutex<HashMap<String, bool>>>,
+    path: String,
+}
+
+impl FillGuard {
+    /// Returns `None` if the path is being filled by others.
+    fn claim(fills: &Arc<Mutex<HashMap<String, bool>>>, path: &str) -> Option<Self> {
+        let mut guard = fills.lock();
+        if guard.contains_key(path) {
+            return None;
+        }
+        guard.insert(path.to_string(), false);
+
+        Some(FillGuard {
+            fills: fills.clone(),
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Drop for FillGuard {
+    fn drop(&mut self) {
+        self.fills.lock().remove(&self.path);
+    }
+}
+
+/// InvalidateWriter runs the invalidation after the inner writer has been
+/// closed successfully.
+pub(super) struct InvalidateWriter {
+    inner: BytesWriter,
+    closed: bool,
+    invalidate: Option<BoxFuture<'static, ()>>,
+}
+
+impl InvalidateWriter {
+    pub(super) fn new(inner: BytesWriter, invalidate: BoxFuture<'static, ()>) -> Self {
+        InvalidateWriter {
+            inner,
+            closed: false,
+            invalidate: Some(invalidate),
+        }
+    }
+}
+
+impl AsyncWrite for InvalidateWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if !self.closed {
+            ready!(Pin::new(&mut self.inner).poll_close(cx))?;
+            self.closed = true;
+        }
+
+        if let Some(fut) = self.invalidate.as_mut() {
+            ready!(fut.poll_unpin(cx));
+            self.invalidate = None;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Slice the requested range from the whole object content.
+fn slice_range(path: &str, bs: Bytes, offset: Option<u64>, size: Option<u64>) -> Result<Bytes> {
+    let start = offset.unwrap_or_default();
+    if start > bs.len() as u64 {
+        return Err(other(ObjectError::new(
+            "read",
+            path,
+            anyhow!("offset out of bound {} > {}", start, bs.len()),
+        )));
+    }
+
+    let end = match size {
+        Some(size) if start + size > bs.len() as u64 => {
+            return Err(other(ObjectError::new(
+                "read",
+                path,
+                anyhow!("size out of bound {} > {}", start + size, bs.len()),
+            )));
+        }
+        Some(size) => start + size,
+        None => bs.len() as u64,
+    };
+
+    Ok(bs.slice(start as usize..end as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::layers::test_util::CountingAccessor;
+    use crate::services::memory;
+    use crate::Operator;
+
+    async fn new_counting() -> Arc<CountingAccessor> {
+        CountingAccessor::new(Duration::ZERO).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cache_read() -> anyhow::Result<()> {
+        let srv = new_counting().await;
+        let cache = memory::Backend::build().finish().await?;
+        let op = Operator::new(srv.clone()).layer(CacheLayer::new(cache.clone()));
+
+        op.object("config").write("Hello, World!").await?;
+
+        assert_eq!(op.object("config").read().await?, b"Hello, World!");
+        assert_eq!(op.object("config").range_read(7..12).await?, b"World");
+        assert_eq!(op.object("config").read().await?, b"Hello, World!");
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 1);
+
+        // Write through the layer should invalidate the cache.
+        op.object("config").write("Hello, OpenDAL!").await?;
+        assert_eq!(op.object("config").read().await?, b"Hello, OpenDAL!");
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 2);
+
+        // Delete through the layer should invalidate the cache.
+        op.object("config").delete().await?;
+        let err = op.object("config").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(Operator::new(cache).object("config").read().await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_fill_during_write() -> anyhow::Result<()> {
+        let srv = new_counting().await;
+        let cache = memory::Backend::build().finish().await?;
+        let op = Operator::new(srv.clone()).layer(CacheLayer::new(cache.clone()));
+        op.object("config").write("v1").await?;
+
+        // Fill the cache while the writer is still open.
+        let mut w = op.object("config").writer(2).await?;
+        w.write_all(b"v2").await?;
+        assert_eq!(op.object("config").read().await?, b"v1");
+        assert!(
+            Operator::new(cache.clone())
+                .object("config")
+                .is_exist()
+                .await?
+        );
+
+        w.close().await?;
+        assert!(!Operator::new(cache).object("config").is_exist().await?);
+        assert_eq!(op.object("config").read().await?, b"v2");
+
+        Ok(())
+    }
+
+    /// SlowCache delays writes so that fills can be raced.
+    #[derive(Debug)]
+    struct SlowCache {
+        inner: Arc<dyn Accessor>,
+    }
+
+    #[async_trait]
+    impl Accessor for SlowCache {
+        async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+            self.inner.read(args).await
+        }
+        async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.inner.write(args).await
+        }
+        async fn delete(&self, args: &OpDelete) -> Result<()> {
+            self.inner.delete(args).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_fill_before_write() -> anyhow::Result<()> {
+        let srv = new_counting().await;
+        let cache = Arc::new(SlowCache {
+            inner: memory::Backend::build().finish().await?,
+        });
+        let op = Operator::new(srv.clone()).layer(CacheLayer::new(cache));
+        op.object("config").write("v1").await?;
+
+        // The write lands while the read is filling the old content.
+        let o = op.object("config");
+        let (bs, res) = futures::join!(o.read(), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            op.object("config").write("v2").await
+        });
+        assert_eq!(bs?, b"v1");
+        res?;
+        assert_eq!(op.object("config").read().await?, b"v2");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_if_match() -> anyhow::Result<()> {
+        let srv = new_counting().await;
+        let cache = memory::Backend::build().finish().await?;
+        let op = Operator::new(srv.clone()).layer(CacheLayer::new(cache.clone()));
+        op.object("config").write("Hello").await?;
+
+        let acc = CacheLayer::new(cache.clone()).layer(srv.clone());
+        let args = OpRead::new("config", ..)?.with_if_match("\"etag\"");
+        let mut bs = Vec::new();
+        acc.read(&args).await?.read_to_end(&mut bs).await?;
+        assert_eq!(bs, b"Hello");
+        assert!(!Operator::new(cache).object("config").is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_evict() -> anyhow::Result<()> {
+        let srv = new_counting().await;
+        let cache = memory::Backend::build().finish().await?;
+        let op = Operator::new(srv.clone()).layer(CacheLayer::new(cache.clone()).with_max_bytes(8));
+
+        op.object("a").write("aaaa").await?;
+        op.object("b").write("bbbb").await?;
+        op.object("c").write("cccc").await?;
+
+        op.object("a").read().await?;
+        op.object("b").read().await?;
+        // Touch `a` so that `b` becomes the least recently used.
+        op.object("a").read().await?;
+        op.object("c").read().await?;
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 3);
+
+        let cache = Operator::new(cache);
+        assert!(cache.object("a").is_exist().await?);
+        assert!(!cache.object("b").is_exist().await?);
+        assert!(cache.object("c").is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_revalidate() -> anyhow::Result<()> {
+        let srv = new_counting().await;
+        let cache = memory::Backend::build().finish().await?;
+        let op = Operator::new(srv.clone()).layer(CacheLayer::new(cache).with_revalidate(true));
+
+        op.object("index").write("v1").await?;
+        assert_eq!(op.object("index").read().await?, b"v1");
+
+        // Changed without going through the layer.
+        Operator::new(srv.clone())
+            .object("index")
+            .write("v2.0")
+            .await?;
+        assert_eq!(op.object("index").read().await?, b"v2.0");
+        assert_eq!(srv.reads.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub size: u64,
+    pub etag: Option<String>,
+    tick: u64,
+}
+
+/// CacheIndex tracks all cached entries and their access order.
+#[derive(Debug)]
+pub struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    /// Access tick => path, the first one is the least recently used.
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+    used: u64,
+    capacity: Option<u64>,
+}
+
+impl CacheIndex {
+    pub fn new(capacity: Option<u64>) -> Self {
+        CacheIndex {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            used: 0,
+            capacity,
+        }
+    }
+
+    /// Get the entry of path and mark it as recently used.
+    pub fn get(&mut self, path: &str) -> Option<CacheEntry> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        let entry = self.entries.get_mut(path)?;
+        self.lru.remove(&entry.tick);
+        self.lru.insert(tick, path.to_string());
+        entry.tick = tick;
+
+        Some(entry.clone())
+    }
+
+    /// Insert a new entry, returns the evicted paths.
+    ///
+    /// Objects larger than the capacity will not be inserted.
+    pub fn insert(&mut self, path: &str, size: u64, etag: Option<String>) -> Vec<String> {
+        self.remove(path);
+
+        if matches!(self.capacity, Some(cap) if size > cap) {
+            return vec![path.to_string()];
+        }
+
+        let mut evicted = Vec::new();
+        while matches!(self.capacity, Some(cap) if self.used + size > cap) {
+            let p = match self.lru.values().next() {
+                Some(p) => p.clone(),
+                None => break,
+            };
+            self.remove(&p);
+            evicted.push(p);
+        }
+
+        self.tick += 1;
+        self.lru.insert(self.tick, path.to_string());
+        self.entries.insert(
+            path.to_string(),
+            CacheEntry {
+                size,
+                etag,
+                tick: self.tick,
+            },
+        );
+        self.used += size;
+
+        evicted
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<CacheEntry> {
+        let entry = self.entries.remove(path)?;
+        self.lru.remove(&entry.tick);
+        self.used -= entry.size;
+
+        Some(entry)
+    }
+}
@@ -0,0 +1,450 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Provide fault injection support via [`ChaosLayer`].
+
+use std::cmp::min;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::ready;
+use futures::AsyncRead;
+use futures::AsyncWrite;
+use log::debug;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// ChaosLayer will make the underlying storage misbehave on purpose.
+///
+/// All faults are disabled by default, users can enable them by:
+///
+/// - [`ChaosLayer::with_error`]: fail operations with the given error kind.
+/// - [`ChaosLayer::with_latency`]: add latency before every operation.
+/// - [`ChaosLayer::with_truncate_ratio`]: end read streams early.
+/// - [`ChaosLayer::with_interrupt_ratio`]: fail read streams mid-way.
+/// - [`ChaosLayer::with_corrupt_ratio`]: flip a byte of read streams.
+/// - [`ChaosLayer::with_close_error_ratio`]: fail writers at close.
+///
+/// All faults are decided by a random generator seeded by the input seed,
+/// so the same sequence of operations will always meet the same faults.
+///
+/// # Feature
+///
+/// This layer needs to enable feature `chaos`.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use std::io::ErrorKind;
+///
+/// use opendal::layers::ChaosLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(
+///     ChaosLayer::new(42)
+///         .with_error(ErrorKind::Interrupted, 0.1)
+///         .with_truncate_ratio(0.05),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChaosLayer {
+    seed: u64,
+    error_kind: ErrorKind,
+    error_ratio: f64,
+    latency: Duration,
+    truncate_ratio: f64,
+    interrupt_ratio: f64,
+    corrupt_ratio: f64,
+    close_error_ratio: f64,
+}
+
+impl ChaosLayer {
+    /// Create a new ChaosLayer with the seed of random generator.
+    pub fn new(seed: u64) -> Self {
+        ChaosLayer {
+            seed,
+            error_kind: ErrorKind::Interrupted,
+            error_ratio: 0.0,
+            latency: Duration::ZERO,
+            truncate_ratio: 0.0,
+            interrupt_ratio: 0.0,
+            corrupt_ratio: 0.0,
+            close_error_ratio: 0.0,
+        }
+    }
+
+    /// Fail operations with error `kind` in `ratio`.
+    ///
+    /// The `kind` will also be used by read stream interruptions and
+    /// writer close failures. Default to [`ErrorKind::Interrupted`].
+    #[must_use]
+    pub fn with_error(mut self, kind: ErrorKind, ratio: f64) -> Self {
+        self.error_kind = kind;
+        self.error_ratio = ratio;
+        self
+    }
+
+    /// Add `latency` before every operation.
+    #[must_use]
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// End read streams at a random position in `ratio`.
+    #[must_use]
+    pub fn with_truncate_ratio(mut self, ratio: f64) -> Self {
+        self.truncate_ratio = ratio;
+        self
+    }
+
+    /// Fail read streams at a random position in `ratio`.
+    #[must_use]
+    pub fn with_interrupt_ratio(mut self, ratio: f64) -> Self {
+        self.interrupt_ratio = ratio;
+        self
+    }
+
+    /// Flip a byte at a random position of read streams in `ratio`.
+    #[must_use]
+    pub fn with_corrupt_ratio(mut self, ratio: f64) -> Self {
+        self.corrupt_ratio = ratio;
+        self
+    }
+
+    /// Fail writers at close in `ratio`.
+    ///
+    /// The inner writer will not be closed, but the written data could have
+    /// been persisted already: backends like `memory` commit data at close,
+    /// while `fs` has written data into the file during `write`. Callers
+    /// should not assume the object is absent after the failure.
+    #[must_use]
+    pub fn with_close_error_ratio(mut self, ratio: f64) -> Self {
+        self.close_error_ratio = ratio;
+        self
+    }
+}
+
+impl Layer for ChaosLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ChaosAccessor {
+            inner,
+            layer: self.clone(),
+            rng: Mutex::new(StdRng::seed_from_u64(self.seed)),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ChaosAccessor {
+    inner: Arc<dyn Accessor>,
+    layer: ChaosLayer,
+    rng: Mutex<StdRng>,
+}
+
+impl ChaosAccessor {
+    fn hit(&self, ratio: f64) -> bool {
+        ratio > 0.0 && self.rng.lock().gen_bool(ratio.min(1.0))
+    }
+
+    /// Inject latency and errors before operations.
+    async fn inject(&self, op: &'static str, path: &str) -> Result<()> {
+        if !self.layer.latency.is_zero() {
+            tokio::time::sleep(self.layer.latency).await;
+        }
+
+        if self.hit(self.layer.error_ratio) {
+            debug!("object {} {} injected error", path, op);
+            return Err(Error::new(
+                self.layer.error_kind,
+                ObjectError::new(op, path, anyhow!("injected error")),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Pick a random position in `[0, size)` for the fault in `ratio`.
+    fn position(&self, ratio: f64, size: u64) -> Option<u64> {
+        if size == 0 || !self.hit(ratio) {
+            return None;
+        }
+
+        Some(self.rng.lock().gen_range(0..size))
+    }
+}
+
+#[async_trait]
+impl Accessor for ChaosAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inject("create", args.path()).await?;
+        self.inner.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.inject("read", args.path()).await?;
+        let r = self.inner.read(args).await?;
+
+        let layer = &self.layer;
+        if layer.truncate_ratio <= 0.0 && layer.interrupt_ratio <= 0.0 && layer.corrupt_ratio <= 0.0
+        {
+            return Ok(r);
+        }
+
+        let size = match args.size() {
+            Some(size) => size,
+            None => {
+                let meta = self.inner.stat(&OpStat::new(args.path())?).await?;
+                meta.content_length()
+                    - min(args.offset().unwrap_or_default(), meta.content_length())
+            }
+        };
+
+        Ok(Box::new(ChaosReader {
+            inner: r,
+            path: args.path().to_string(),
+            kind: layer.error_kind,
+            pos: 0,
+            truncate_at: self.position(layer.truncate_ratio, size),
+            interrupt_at: self.position(layer.interrupt_ratio, size),
+            corrupt_at: self.position(layer.corrupt_ratio, size),
+        }))
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.inject("write", args.path()).await?;
+        let w = self.inner.write(args).await?;
+
+        Ok(Box::new(ChaosWriter {
+            inner: w,
+            path: args.path().to_string(),
+            kind: self.layer.error_kind,
+            fail_close: self.hit(self.layer.close_error_ratio),
+        }))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.inject("stat", args.path()).await?;
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inject("delete", args.path()).await?;
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inject("list", args.path()).await?;
+        self.inner.list(args).await
+    }
+}
+
+struct ChaosReader {
+    inner: BytesReader,
+    path: String,
+    kind: ErrorKind,
+    pos: u64,
+
+    truncate_at: Option<u64>,
+    interrupt_at: Option<u64>,
+    corrupt_at: Option<u64>,
+}
+
+impl AsyncRead for ChaosReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let pos = self.pos;
+        if self.truncate_at == Some(pos) {
+            debug!("object {} read truncated at {}", self.path, pos);
+            return Poll::Ready(Ok(0));
+        }
+        if self.interrupt_at == Some(pos) {
+            debug!("object {} read interrupted at {}", self.path, pos);
+            return Poll::Ready(Err(Error::new(
+                self.kind,
+                ObjectError::new("read", &self.path, anyhow!("injected interruption")),
+            )));
+        }
+
+        // Make sure we will stop at the next fault position.
+        let limit = [self.truncate_at, self.interrupt_at]
+            .into_iter()
+            .flatten()
+            .filter(|v| *v > pos)
+            .map(|v| (v - pos) as usize)
+            .fold(buf.len(), min);
+
+        let n = ready!(Pin::new(&mut self.inner).poll_read(cx, &mut buf[..limit]))?;
+        if let Some(at) = self.corrupt_at {
+            if at >= pos && at < pos + n as u64 {
+                debug!("object {} read corrupted at {}", self.path, at);
+                buf[(at - pos) as usize] ^= 0xff;
+            }
+        }
+        self.pos += n as u64;
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+struct ChaosWriter {
+    inner: BytesWriter,
+    path: String,
+    kind: ErrorKind,
+    fail_close: bool,
+}
+
+impl AsyncWrite for ChaosWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.fail_close {
+            debug!("object {} write failed at close", self.path);
+            return Poll::Ready(Err(Error::new(
+                self.kind,
+                ObjectError::new("write", &self.path, anyhow!("injected close failure")),
+            )));
+        }
+
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    async fn new_operator(layer: ChaosLayer) -> anyhow::Result<Operator> {
+        let acc = memory::Backend::build().finish().await?;
+        Operator::new(acc.clone())
+            .object("file")
+            .write("Hello, World!")
+            .await?;
+
+        Ok(Operator::new(acc).layer(layer))
+    }
+
+    #[tokio::test]
+    async fn test_chaos_error() -> anyhow::Result<()> {
+        let op =
+            new_operator(ChaosLayer::new(0).with_error(ErrorKind::PermissionDenied, 1.0)).await?;
+
+        let err = op.object("file").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chaos_deterministic() -> anyhow::Result<()> {
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let op =
+                new_operator(ChaosLayer::new(42).with_error(ErrorKind::Interrupted, 0.5)).await?;
+
+            let mut rs = Vec::new();
+            for _ in 0..32 {
+                rs.push(op.object("file").metadata().await.is_ok());
+            }
+            results.push(rs);
+        }
+
+        assert_eq!(results[0], results[1]);
+        assert!(results[0].contains(&true));
+        assert!(results[0].contains(&false));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chaos_read() -> anyhow::Result<()> {
+        let op = new_operator(ChaosLayer::new(0).with_truncate_ratio(1.0)).await?;
+        let bs = op.object("file").read().await?;
+        assert!(bs.len() < 13);
+        assert!(b"Hello, World!".starts_with(&bs));
+
+        let op = new_operator(ChaosLayer::new(0).with_corrupt_ratio(1.0)).await?;
+        let bs = op.object("file").read().await?;
+        assert_eq!(bs.len(), 13);
+        assert_ne!(bs, b"Hello, World!");
+
+        let op = new_operator(ChaosLayer::new(0).with_interrupt_ratio(1.0)).await?;
+        let err = op.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chaos_close_error() -> anyhow::Result<()> {
+        let op = new_operator(ChaosLayer::new(0).with_close_error_ratio(1.0)).await?;
+
+        let err = op.object("new_file").write("abc").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+        assert!(!op.object("new_file").is_exist().await?);
+
+        Ok(())
+    }
+}
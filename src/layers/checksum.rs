@@ -0,0 +1,703 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Provide end-to-end checksum support via [`ChecksumLayer`].
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::ready;
+use futures::AsyncRead;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use log::debug;
+use log::warn;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::error::other;
+use crate::error::ObjectError;
+use crate::ops::ChecksumAlgorithm;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// Default max size of objects that could be buffered before writing.
+const DEFAULT_MAX_BUFFER_SIZE: u64 = 8 * 1024 * 1024;
+
+/// ChecksumLayer will verify object content end-to-end.
+///
+/// # Behavior
+///
+/// - `write` will calculate MD5 (and the extra algorithm set by
+///   [`ChecksumLayer::with_algorithm`]) and send it along with the
+///   content, so that services can reject corrupted uploads. Objects
+///   larger than [`ChecksumLayer::with_max_buffer_size`] can't be buffered,
+///   they will be verified via `stat` after written instead. Mismatched
+///   objects are left in place, it's up to users to remove them.
+/// - `read` of the whole object will be verified against the `content_md5`
+///   or the `etag` returned by `stat`, and pinned to that `etag` so that
+///   objects overwritten in between will fail the read instead of being
+///   verified against a stale checksum. Range reads will not be verified.
+///
+/// Mismatched content will return `ErrorKind::InvalidData`.
+///
+/// # Notes
+///
+/// Not all `etag` are MD5 of the content, for example, objects uploaded
+/// via multipart upload or encrypted by SSE-KMS, and they can't be told
+/// apart from MD5. So `etag` is not used by default, users can enable it
+/// via [`ChecksumLayer::with_etag_verification`] if their services always
+/// return MD5 `etag`.
+///
+/// # Feature
+///
+/// This layer needs to enable feature `checksum`.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::ChecksumLayer;
+/// use opendal::ops::ChecksumAlgorithm;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor)
+///     .layer(ChecksumLayer::new().with_algorithm(ChecksumAlgorithm::Crc32c));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChecksumLayer {
+    algorithm: Option<ChecksumAlgorithm>,
+    max_buffer_size: u64,
+    verify_etag: bool,
+}
+
+impl Default for ChecksumLayer {
+    fn default() -> Self {
+        ChecksumLayer {
+            algorithm: None,
+            max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+            verify_etag: false,
+        }
+    }
+}
+
+impl ChecksumLayer {
+    /// Create a new ChecksumLayer which only uses MD5.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calculate the checksum of `algorithm` besides MD5 while writing.
+    #[must_use]
+    pub fn with_algorithm(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Set the max size of objects that could be buffered before writing.
+    ///
+    /// Default to 8 MiB.
+    #[must_use]
+    pub fn with_max_buffer_size(mut self, size: u64) -> Self {
+        self.max_buffer_size = size;
+        self
+    }
+
+    /// Set whether MD5-like `etag` should be used to verify content.
+    ///
+    /// Default to `false`.
+    #[must_use]
+    pub fn with_etag_verification(mut self, enabled: bool) -> Self {
+        self.verify_etag = enabled;
+        self
+    }
+}
+
+impl Layer for ChecksumLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ChecksumAccessor {
+            inner,
+            layer: self.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ChecksumAccessor {
+    inner: Arc<dyn Accessor>,
+    layer: ChecksumLayer,
+}
+
+/// Hasher calculates MD5 and the extra checksum at the same time.
+struct Hasher {
+    md5: md5::Context,
+    extra: Option<ExtraHasher>,
+}
+
+enum ExtraHasher {
+    Crc32c(u32),
+    Sha256(Box<Sha256>),
+}
+
+impl Hasher {
+    fn new(algorithm: Option<ChecksumAlgorithm>) -> Self {
+        Hasher {
+            md5: md5::Context::new(),
+            extra: algorithm.map(|v| match v {
+                ChecksumAlgorithm::Crc32c => ExtraHasher::Crc32c(0),
+                ChecksumAlgorithm::Sha256 => ExtraHasher::Sha256(Box::new(Sha256::new())),
+            }),
+        }
+    }
+
+    fn update(&mut self, bs: &[u8]) {
+        self.md5.consume(bs);
+        match &mut self.extra {
+            Some(ExtraHasher::Crc32c(v)) => *v = crc32c::crc32c_append(*v, bs),
+            Some(ExtraHasher::Sha256(v)) => v.update(bs),
+            None => {}
+        }
+    }
+
+    /// Build the `OpWrite` with checksums.
+    fn finish(self, op: OpWrite) -> OpWrite {
+        let op = op.with_content_md5(&base64::encode(self.md5.compute().0));
+
+        match self.extra {
+            Some(ExtraHasher::Crc32c(v)) => {
+                op.with_checksum(ChecksumAlgorithm::Crc32c, &base64::encode(v.to_be_bytes()))
+            }
+            Some(ExtraHasher::Sha256(v)) => {
+                op.with_checksum(ChecksumAlgorithm::Sha256, &base64::encode(v.finalize()))
+            }
+            None => op,
+        }
+    }
+}
+
+/// Get the hex encoded MD5 of the object from metadata.
+fn expected_md5(meta: &ObjectMetadata, verify_etag: bool) -> Option<String> {
+    if let Some(v) = meta.content_md5() {
+        if let Ok(bs) = base64::decode(v) {
+            if bs.len() == 16 {
+                return Some(bs.iter().map(|b| format!("{b:02x}")).collect());
+            }
+        }
+    }
+
+    if !verify_etag {
+        return None;
+    }
+    let etag = meta.etag()?.trim_start_matches("W/").trim_matches('"');
+    if etag.len() == 32 && etag.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Some(etag.to_ascii_lowercase());
+    }
+    None
+}
+
+fn checksum_mismatch(op: &'static str, path: &str, expected: &str, actual: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        ObjectError::new(
+            op,
+            path,
+            anyhow!("checksum mismatch: expected md5 {expected}, actual {actual}"),
+        ),
+    )
+}
+
+#[async_trait]
+impl Accessor for ChecksumAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inner.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        if args.offset().is_some() || args.size().is_some() {
+            return self.inner.read(args).await;
+        }
+
+        let meta = self.inner.stat(&OpStat::new(args.path())?).await?;
+        let r = match (meta.etag(), args.if_match()) {
+            (Some(etag), None) => self.inner.read(&args.clone().with_if_match(etag)).await?,
+            _ => self.inner.read(args).await?,
+        };
+
+        match expected_md5(&meta, self.layer.verify_etag) {
+            Some(expected) => Ok(Box::new(VerifyReader {
+                inner: r,
+                path: args.path().to_string(),
+                md5: Some(md5::Context::new()),
+                expected,
+            })),
+            None => {
+                debug!("object {} doesn't have md5, skip verifying", args.path());
+                Ok(r)
+            }
+        }
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        if args.size() <= self.layer.max_buffer_size {
+            return Ok(Box::new(BufferWriter {
+                inner: self.inner.clone(),
+                op: Some(args.clone()),
+                hasher: Some(Hasher::new(self.layer.algorithm)),
+                buf: Vec::with_capacity(args.size() as usize),
+                fut: None,
+            }));
+        }
+
+        let w = self.inner.write(args).await?;
+        Ok(Box::new(StreamWriter {
+            inner: w,
+            acc: self.inner.clone(),
+            path: args.path().to_string(),
+            verify_etag: self.layer.verify_etag,
+            md5: Some(md5::Context::new()),
+            fut: None,
+        }))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+/// VerifyReader verifies the content after all data has been read.
+struct VerifyReader {
+    inner: BytesReader,
+    path: String,
+    /// Will be taken after verified.
+    md5: Option<md5::Context>,
+    expected: String,
+}
+
+impl AsyncRead for VerifyReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let n = ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+
+        if n > 0 {
+            if let Some(md5) = self.md5.as_mut() {
+                md5.consume(&buf[..n]);
+            }
+        } else if let Some(md5) = self.md5.take() {
+            let actual = format!("{:x}", md5.compute());
+            if actual != self.expected {
+                return Poll::Ready(Err(checksum_mismatch(
+                    "read",
+                    &self.path,
+                    &self.expected,
+                    &actual,
+                )));
+            }
+        }
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// BufferWriter buffers the whole object so that checksums can be sent
+/// before the content.
+struct BufferWriter {
+    inner: Arc<dyn Accessor>,
+    /// Will be taken while uploading.
+    op: Option<OpWrite>,
+    hasher: Option<Hasher>,
+    buf: Vec<u8>,
+    fut: Option<BoxFuture<'static, Result<()>>>,
+}
+
+impl AsyncWrite for BufferWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        let op = match &self.op {
+            Some(op) => op,
+            None => return Poll::Ready(Err(other(anyhow!("writer has been closed")))),
+        };
+        if self.buf.len() + buf.len() > op.size() as usize {
+            return Poll::Ready(Err(Error::new(
+                ErrorKind::InvalidInput,
+                ObjectError::new(
+                    "write",
+                    op.path(),
+                    anyhow!("write more than expected size {}", op.size()),
+                ),
+            )));
+        }
+
+        self.buf.extend_from_slice(buf);
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(buf);
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.fut.is_none() {
+            let (op, hasher) = match (self.op.take(), self.hasher.take()) {
+                (Some(op), Some(hasher)) => (op, hasher),
+                _ => return Poll::Ready(Ok(())),
+            };
+            let op = hasher.finish(op);
+            let acc = self.inner.clone();
+            let buf = std::mem::take(&mut self.buf);
+
+            self.fut = Some(Box::pin(async move {
+                let mut w = acc.write(&op).await?;
+                w.write_all(&buf).await?;
+                w.close().await
+            }));
+        }
+
+        let res = ready!(self
+            .fut
+            .as_mut()
+            .expect("future must be set")
+            .as_mut()
+            .poll(cx));
+        self.fut = None;
+        Poll::Ready(res)
+    }
+}
+
+/// StreamWriter verifies the written object via `stat` after closed.
+struct StreamWriter {
+    inner: BytesWriter,
+    acc: Arc<dyn Accessor>,
+    path: String,
+    verify_etag: bool,
+    /// Will be taken after closed.
+    md5: Option<md5::Context>,
+    fut: Option<BoxFuture<'static, Result<()>>>,
+}
+
+impl AsyncWrite for StreamWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        if let Some(md5) = self.md5.as_mut() {
+            md5.consume(&buf[..n]);
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.fut.is_none() {
+            ready!(Pin::new(&mut self.inner).poll_close(cx))?;
+
+            let md5 = match self.md5.take() {
+                Some(md5) => md5,
+                None => return Poll::Ready(Ok(())),
+            };
+            let actual = format!("{:x}", md5.compute());
+            let acc = self.acc.clone();
+            let path = self.path.clone();
+            let verify_etag = self.verify_etag;
+
+            self.fut = Some(Box::pin(async move {
+                let meta = acc.stat(&OpStat::new(&path)?).await?;
+                match expected_md5(&meta, verify_etag) {
+                    Some(expected) if expected != actual => {
+                        // The object may have been overwritten by others or
+                        // have a non-MD5 `etag`, it's not safe to delete it.
+                        warn!("object {} checksum mismatch after written", path);
+                        Err(checksum_mismatch("write", &path, &expected, &actual))
+                    }
+                    _ => Ok(()),
+                }
+            }));
+        }
+
+        let res = ready!(self
+            .fut
+            .as_mut()
+            .expect("future must be set")
+            .as_mut()
+            .poll(cx));
+        self.fut = None;
+        Poll::Ready(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+
+    use futures::AsyncReadExt;
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::io_util::into_reader;
+    use crate::services::memory;
+    use crate::Operator;
+
+    /// Md5Service reports MD5 via `stat` and could corrupt data on read.
+    #[derive(Debug)]
+    struct Md5Service {
+        inner: Arc<dyn Accessor>,
+        corrupt: AtomicBool,
+        corrupt_stat: AtomicBool,
+        /// Report this `etag` instead of MD5 if set.
+        fixed_etag: Mutex<Option<String>>,
+        /// Overwrite the object with this content after `stat` if set.
+        overwrite: Mutex<Option<Vec<u8>>>,
+        writes: Mutex<Vec<OpWrite>>,
+    }
+
+    impl Md5Service {
+        async fn etag(&self, path: &str) -> Result<String> {
+            if let Some(etag) = self.fixed_etag.lock().clone() {
+                return Ok(etag);
+            }
+            let mut bs = Vec::new();
+            self.inner
+                .read(&OpRead::new(path, ..)?)
+                .await?
+                .read_to_end(&mut bs)
+                .await?;
+            if self.corrupt_stat.load(Ordering::SeqCst) {
+                bs.push(0);
+            }
+            Ok(format!("\"{:x}\"", md5::compute(&bs)))
+        }
+    }
+
+    #[async_trait]
+    impl Accessor for Md5Service {
+        async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+            if let Some(v) = args.if_match() {
+                if v != self.etag(args.path()).await? {
+                    return Err(other(anyhow!("etag mismatch")));
+                }
+            }
+            let mut bs = Vec::new();
+            self.inner.read(args).await?.read_to_end(&mut bs).await?;
+            if self.corrupt.load(Ordering::SeqCst) {
+                bs[0] ^= 1;
+            }
+            Ok(Box::new(into_reader(futures::stream::iter(vec![Ok(
+                bytes::Bytes::from(bs),
+            )]))))
+        }
+        async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+            self.writes.lock().push(args.clone());
+            self.inner.write(args).await
+        }
+        async fn delete(&self, args: &OpDelete) -> Result<()> {
+            self.inner.delete(args).await
+        }
+        async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+            let mut meta = self.inner.stat(args).await?;
+            meta.set_etag(&self.etag(args.path()).await?);
+
+            let overwrite = self.overwrite.lock().take();
+            if let Some(bs) = overwrite {
+                let mut w = self
+                    .inner
+                    .write(&OpWrite::new(args.path(), bs.len() as u64)?)
+                    .await?;
+                w.write_all(&bs).await?;
+                w.close().await?;
+            }
+            Ok(meta)
+        }
+    }
+
+    async fn new_operator(layer: ChecksumLayer) -> anyhow::Result<(Arc<Md5Service>, Operator)> {
+        let srv = Arc::new(Md5Service {
+            inner: memory::Backend::build().finish().await?,
+            corrupt: AtomicBool::new(false),
+            corrupt_stat: AtomicBool::new(false),
+            fixed_etag: Mutex::new(None),
+            overwrite: Mutex::new(None),
+            writes: Mutex::new(Vec::new()),
+        });
+        Ok((srv.clone(), Operator::new(srv).layer(layer)))
+    }
+
+    #[tokio::test]
+    async fn test_checksum_write() -> anyhow::Result<()> {
+        let (srv, op) =
+            new_operator(ChecksumLayer::new().with_algorithm(ChecksumAlgorithm::Sha256)).await?;
+
+        op.object("file").write("Hello, World!").await?;
+        assert_eq!(op.object("file").read().await?, b"Hello, World!");
+
+        let writes = srv.writes.lock();
+        assert_eq!(writes[0].content_md5(), Some("ZajifYh5KDgxtmS9i38K1A=="));
+        assert_eq!(
+            writes[0].checksum(),
+            Some((
+                ChecksumAlgorithm::Sha256,
+                "3/1gIbsr1bCvZ2KQgJ7DpTGR3YHH9wpLKGiKNiGCmG8="
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksum_write_stream() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(
+            ChecksumLayer::new()
+                .with_algorithm(ChecksumAlgorithm::Crc32c)
+                .with_max_buffer_size(4),
+        )
+        .await?;
+
+        op.object("file").write("Hello, World!").await?;
+        assert_eq!(op.object("file").read().await?, b"Hello, World!");
+        assert_eq!(srv.writes.lock()[0].content_md5(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksum_write_stream_mismatch() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(
+            ChecksumLayer::new()
+                .with_max_buffer_size(4)
+                .with_etag_verification(true),
+        )
+        .await?;
+        srv.corrupt_stat.store(true, Ordering::SeqCst);
+
+        let err = op.object("file").write("Hello, World!").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        // Mismatched objects are never deleted.
+        assert!(
+            Operator::new(srv.inner.clone())
+                .object("file")
+                .is_exist()
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksum_non_md5_etag() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(ChecksumLayer::new().with_max_buffer_size(4)).await?;
+        *srv.fixed_etag.lock() = Some("\"0123456789abcdef0123456789abcdef\"".to_string());
+
+        // `etag` is not used by default.
+        op.object("file").write("Hello, World!").await?;
+        assert_eq!(op.object("file").read().await?, b"Hello, World!");
+
+        // Enabled `etag` verification fails, but the object survives.
+        let op = Operator::new(srv.clone()).layer(
+            ChecksumLayer::new()
+                .with_max_buffer_size(4)
+                .with_etag_verification(true),
+        );
+        let err = op.object("file").write("Hello, World!").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            Operator::new(srv.inner.clone())
+                .object("file")
+                .read()
+                .await?,
+            b"Hello, World!"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksum_read_overwritten() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(ChecksumLayer::new()).await?;
+
+        op.object("file").write("Hello, World!").await?;
+        *srv.overwrite.lock() = Some(b"Hello, Rust!".to_vec());
+
+        // The read is pinned to the `etag` returned by `stat`.
+        assert!(op.object("file").read().await.is_err());
+        assert_eq!(op.object("file").read().await?, b"Hello, Rust!");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksum_read_mismatch() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(ChecksumLayer::new().with_etag_verification(true)).await?;
+
+        op.object("file").write("Hello, World!").await?;
+        srv.corrupt.store(true, Ordering::SeqCst);
+
+        let err = op.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // Range reads are not verified, corrupted data will be returned.
+        assert_eq!(op.object("file").range_read(0..5).await?, b"Iello");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,703 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::max;
+use std::cmp::min;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future;
+use futures::future::BoxFuture;
+use futures::ready;
+use futures::stream;
+use futures::AsyncReadExt;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use futures::FutureExt;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use log::debug;
+use log::warn;
+use time::OffsetDateTime;
+
+use crate::error::other;
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+
+/// Magic line at the beginning of manifests.
+const MANIFEST_MAGIC: &str = "opendal-chunk-manifest-v1";
+/// Objects larger than this will never be treated as manifests.
+const MAX_MANIFEST_SIZE: u64 = 256;
+/// Objects smaller than this will never be treated as manifests, which is
+/// the magic line plus three single-digit fields.
+const MIN_MANIFEST_SIZE: u64 = MANIFEST_MAGIC.len() as u64 + 7;
+
+/// ChunkLayer will store objects as fixed-size chunks plus a small manifest.
+///
+/// # Layout
+///
+/// The object path holds the manifest, and chunks are stored at
+/// `<dir><id>/<index>`, where `id` changes on every write. Chunks of the
+/// previous write will be removed after [`ChunkLayer::with_gc_delay`] once
+/// the new manifest written, and the chunk dir is hidden from listing of
+/// the root.
+///
+/// # Behavior
+///
+/// - `write` will split the content into chunks while writing.
+/// - `read` will only fetch the chunks covered by the range, and fetch them
+///   concurrently.
+/// - `stat` will report the logical size of the object.
+/// - Objects without a manifest (for example, written before applying this
+///   layer) will be served as-is.
+///
+/// The manifest is the only thing that makes a write visible:
+///
+/// - Readers which loaded a manifest before it's overwritten or deleted
+///   can keep reading its chunks until the gc delay passes, slower readers
+///   will fail with `ErrorKind::NotFound`.
+/// - Concurrent writers to the same path race on the manifest, the last
+///   closed one wins. Writers look up the manifest again while closing and
+///   remove their own chunks if they lost, but a writer closing between
+///   another's two lookups may still leak its chunks in the chunk dir.
+/// - Chunks of writers dropped without closing will be left in the chunk
+///   dir.
+///
+/// # Overhead
+///
+/// Manifests can only be told apart from plain objects by their content:
+///
+/// - Every `read`, `stat` and `delete` will `stat` the object first to look
+///   for the manifest, and `write` will look for it twice while closing.
+/// - Objects sized between 32 and 256 bytes will be read in full to check
+///   whether they are manifests, so reading such plain objects costs two
+///   requests.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::ChunkLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(ChunkLayer::new(4 * 1024 * 1024).with_concurrency(8));
+///
+/// op.object("file").write("Hello, World!").await?;
+/// assert_eq!(op.object("file").range_read(7..).await?, b"World!");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChunkLayer {
+    chunk_size: u64,
+    concurrency: usize,
+    dir: String,
+    gc_delay: Duration,
+}
+
+impl ChunkLayer {
+    /// Create a new ChunkLayer with `chunk_size` bytes per chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn new(chunk_size: u64) -> Self {
+        assert!(chunk_size > 0, "chunk size must be larger than zero");
+
+        Self {
+            chunk_size,
+            concurrency: 4,
+            dir: ".chunks/".to_string(),
+            gc_delay: Duration::from_secs(60),
+        }
+    }
+
+    /// Set the max chunks fetched concurrently while reading, default to 4.
+    #[must_use]
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = max(concurrency, 1);
+        self
+    }
+
+    /// Set the dir to store chunks, default to `.chunks/`.
+    #[must_use]
+    pub fn with_dir(mut self, dir: &str) -> Self {
+        let dir = dir.trim_matches('/');
+        self.dir = format!("{}/", dir);
+        self
+    }
+
+    /// Set the delay before removing chunks of overwritten or deleted
+    /// objects, default to 60 seconds.
+    ///
+    /// Chunks will be removed in background, so they may be left behind if
+    /// the runtime is shut down earlier. Zero removes them immediately.
+    #[must_use]
+    pub fn with_gc_delay(mut self, delay: Duration) -> Self {
+        self.gc_delay = delay;
+        self
+    }
+}
+
+impl Layer for ChunkLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ChunkAccessor {
+            inner,
+            chunk_size: self.chunk_size,
+            concurrency: self.concurrency,
+            dir: self.dir.clone(),
+            gc_delay: self.gc_delay,
+            last_id: Arc::new(AtomicU64::new(0)),
+        })
+    }
+}
+
+/// Manifest of a chunked object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Manifest {
+    chunk_size: u64,
+    size: u64,
+    id: u64,
+}
+
+impl Manifest {
+    fn encode(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}\n",
+            MANIFEST_MAGIC, self.chunk_size, self.size, self.id
+        )
+    }
+
+    fn decode(bs: &[u8]) -> Option<Self> {
+        let s = std::str::from_utf8(bs).ok()?;
+        let mut lines = s.lines();
+        if lines.next()? != MANIFEST_MAGIC {
+            return None;
+        }
+
+        let chunk_size = lines.next()?.parse().ok()?;
+        let size = lines.next()?.parse().ok()?;
+        let id = lines.next()?.parse().ok()?;
+        if chunk_size == 0 {
+            return None;
+        }
+        Some(Manifest {
+            chunk_size,
+            size,
+            id,
+        })
+    }
+
+    fn chunks(&self) -> u64 {
+        self.size.div_ceil(self.chunk_size)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChunkAccessor {
+    inner: Arc<dyn Accessor>,
+    chunk_size: u64,
+    concurrency: usize,
+    dir: String,
+    gc_delay: Duration,
+    last_id: Arc<AtomicU64>,
+}
+
+impl ChunkAccessor {
+    fn chunk_path(&self, id: u64, idx: u64) -> String {
+        format!("{}{}/{}", self.dir, id, idx)
+    }
+
+    /// Generate a new chunk id which is larger than all previous ones.
+    fn next_id(&self) -> u64 {
+        let now = OffsetDateTime::now_utc().unix_timestamp_nanos() as u64;
+        let prev = self
+            .last_id
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| {
+                Some(max(now, v + 1))
+            })
+            .expect("closure always returns Some");
+        max(now, prev + 1)
+    }
+
+    /// Load the manifest of the object, returns `None` if the object is
+    /// not chunked.
+    async fn manifest(&self, path: &str) -> Result<Option<(Manifest, ObjectMetadata)>> {
+        if path.ends_with('/') || path.starts_with(&self.dir) {
+            return Ok(None);
+        }
+
+        let meta = match self.inner.stat(&OpStat::new(path)?).await {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if meta.mode() != ObjectMode::FILE
+            || meta.content_length() < MIN_MANIFEST_SIZE
+            || meta.content_length() > MAX_MANIFEST_SIZE
+        {
+            return Ok(None);
+        }
+
+        let op = OpRead::new_with_offset(path, Some(0), Some(meta.content_length()))?;
+        let mut r = self.inner.read(&op).await?;
+        let mut bs = Vec::with_capacity(meta.content_length() as usize);
+        r.read_to_end(&mut bs).await?;
+
+        Ok(Manifest::decode(&bs).map(|m| (m, meta)))
+    }
+
+    async fn write_chunk(&self, path: String, bs: Vec<u8>) -> Result<()> {
+        let mut w = self
+            .inner
+            .write(&OpWrite::new(&path, bs.len() as u64)?)
+            .await?;
+        w.write_all(&bs).await?;
+        w.close().await
+    }
+
+    async fn read_chunk(&self, path: String, offset: u64, size: u64) -> Result<Bytes> {
+        let op = OpRead::new_with_offset(&path, Some(offset), Some(size))?;
+        let mut r = self.inner.read(&op).await?;
+        let mut bs = Vec::with_capacity(size as usize);
+        r.read_to_end(&mut bs).await?;
+        if bs.len() as u64 != size {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                ObjectError::new("read", &path, anyhow!("chunk is truncated")),
+            ));
+        }
+        Ok(Bytes::from(bs))
+    }
+
+    /// Remove all chunks of the manifest.
+    async fn remove_chunks(&self, path: &str, m: Manifest) {
+        for idx in 0..m.chunks() {
+            let chunk = self.chunk_path(m.id, idx);
+            let res = match OpDelete::new(&chunk) {
+                Ok(op) => self.inner.delete(&op).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                warn!("object {} chunk {} remove: {:?}", path, chunk, e);
+            }
+        }
+    }
+
+    /// Remove all chunks of the manifest after the gc delay, so that
+    /// readers which loaded it could still finish.
+    fn retire(&self, path: &str, m: Manifest) -> BoxFuture<'static, ()> {
+        let acc = self.clone();
+        let path = path.to_string();
+        if self.gc_delay.is_zero() {
+            return async move { acc.remove_chunks(&path, m).await }.boxed();
+        }
+
+        tokio::spawn(async move {
+            tokio::time::sleep(acc.gc_delay).await;
+            acc.remove_chunks(&path, m).await
+        });
+        future::ready(()).boxed()
+    }
+
+    /// Write the manifest of the new chunks, and retire the chunks it
+    /// replaced.
+    async fn commit(&self, path: &str, manifest: Manifest) -> Result<()> {
+        // Look up the manifest now instead of while opening the writer, so
+        // that chunks committed by others in between are retired too.
+        let old = self.manifest(path).await?.map(|(m, _)| m);
+
+        let bs = manifest.encode();
+        let mut w = self
+            .inner
+            .write(&OpWrite::new(path, bs.len() as u64)?)
+            .await?;
+        w.write_all(bs.as_bytes()).await?;
+        w.close().await?;
+        debug!(
+            "object {} written: size {}, chunks {}",
+            path,
+            manifest.size,
+            manifest.chunks()
+        );
+
+        if let Some(old) = old {
+            self.retire(path, old).await;
+        }
+        // Another writer overwrote the manifest in between, our chunks will
+        // never be reachable.
+        match self.manifest(path).await {
+            Ok(Some((m, _))) if m == manifest => {}
+            _ => {
+                debug!("object {} overwritten while committing", path);
+                self.retire(path, manifest).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Accessor for ChunkAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inner.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let m = match self.manifest(args.path()).await? {
+            Some((m, _)) => m,
+            None => return self.inner.read(args).await,
+        };
+
+        let start = args.offset().unwrap_or_default();
+        if start > m.size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                ObjectError::new(
+                    "read",
+                    args.path(),
+                    anyhow!("offset out of bound {} > {}", start, m.size),
+                ),
+            ));
+        }
+        let end = match args.size() {
+            Some(size) => min(start.saturating_add(size), m.size),
+            None => m.size,
+        };
+
+        // Calculate the range to read in every chunk.
+        let chunks = if start < end {
+            start / m.chunk_size..(end - 1) / m.chunk_size + 1
+        } else {
+            0..0
+        };
+        let ranges: Vec<(u64, u64, u64)> = chunks
+            .map(|idx| (idx, idx * m.chunk_size))
+            .map(|(idx, chunk_start)| {
+                let from = max(start, chunk_start) - chunk_start;
+                let to = min(end, chunk_start + m.chunk_size) - chunk_start;
+                (idx, from, to - from)
+            })
+            .collect();
+
+        let acc = self.clone();
+        let s = stream::iter(ranges)
+            .map(move |(idx, offset, size)| {
+                let acc = acc.clone();
+                async move {
+                    acc.read_chunk(acc.chunk_path(m.id, idx), offset, size)
+                        .await
+                }
+            })
+            .buffered(self.concurrency);
+
+        Ok(Box::new(s.boxed().into_async_read()))
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        Ok(Box::new(ChunkWriter {
+            acc: self.clone(),
+            path: args.path().to_string(),
+            id: self.next_id(),
+            buf: Vec::with_capacity(min(args.size(), self.chunk_size) as usize),
+            chunks: 0,
+            written: 0,
+            state: WriterState::Idle,
+        }))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        match self.manifest(args.path()).await? {
+            Some((m, meta)) => {
+                let mut logical = ObjectMetadata::default();
+                logical
+                    .set_mode(ObjectMode::FILE)
+                    .set_content_length(m.size);
+                if let Some(v) = meta.last_modified() {
+                    logical.set_last_modified(v);
+                }
+                Ok(logical)
+            }
+            None => self.inner.stat(args).await,
+        }
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let m = self.manifest(args.path()).await?;
+        self.inner.delete(args).await?;
+
+        if let Some((m, _)) = m {
+            self.retire(args.path(), m).await;
+        }
+        Ok(())
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let s = self.inner.list(args).await?;
+
+        // Entries must be rebuilt so that operations on them go through
+        // the chunks.
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        let dir = self.dir.clone();
+        Ok(Box::new(
+            s.try_filter(move |de| future::ready(de.path() != dir))
+                .map_ok(move |de| DirEntry::new(acc.clone(), de.mode(), de.path())),
+        ))
+    }
+}
+
+enum WriterState {
+    Idle,
+    Uploading(BoxFuture<'static, Result<()>>),
+    Closing(BoxFuture<'static, Result<()>>),
+    Done,
+}
+
+/// ChunkWriter will upload a chunk once the buffer is full, and write the
+/// manifest after closed.
+struct ChunkWriter {
+    acc: ChunkAccessor,
+    path: String,
+    id: u64,
+    buf: Vec<u8>,
+    /// Count of uploaded chunks.
+    chunks: u64,
+    written: u64,
+    state: WriterState,
+}
+
+impl ChunkWriter {
+    fn upload(&mut self) -> BoxFuture<'static, Result<()>> {
+        let acc = self.acc.clone();
+        let path = acc.chunk_path(self.id, self.chunks);
+        let bs = std::mem::take(&mut self.buf);
+        self.chunks += 1;
+        async move { acc.write_chunk(path, bs).await }.boxed()
+    }
+
+    fn poll_upload(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if let WriterState::Uploading(fut) = &mut self.state {
+            let res = ready!(fut.poll_unpin(cx));
+            self.state = WriterState::Idle;
+            res?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ChunkWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        loop {
+            ready!(self.poll_upload(cx))?;
+            if !matches!(self.state, WriterState::Idle) {
+                return Poll::Ready(Err(other(ObjectError::new(
+                    "write",
+                    &self.path,
+                    anyhow!("writer has been closed"),
+                ))));
+            }
+
+            if self.buf.len() as u64 >= self.acc.chunk_size {
+                let fut = self.upload();
+                self.state = WriterState::Uploading(fut);
+                continue;
+            }
+
+            let n = min(buf.len(), (self.acc.chunk_size as usize) - self.buf.len());
+            self.buf.extend_from_slice(&buf[..n]);
+            self.written += n as u64;
+            return Poll::Ready(Ok(n));
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_upload(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        loop {
+            match &mut self.state {
+                WriterState::Idle => {
+                    let last = (!self.buf.is_empty()).then(|| self.upload());
+                    let acc = self.acc.clone();
+                    let path = self.path.clone();
+                    let manifest = Manifest {
+                        chunk_size: acc.chunk_size,
+                        size: self.written,
+                        id: self.id,
+                    };
+                    let fut = async move {
+                        if let Some(last) = last {
+                            last.await?;
+                        }
+                        acc.commit(&path, manifest).await
+                    };
+                    self.state = WriterState::Closing(fut.boxed());
+                }
+                WriterState::Uploading(_) => ready!(self.poll_upload(cx))?,
+                WriterState::Closing(fut) => {
+                    let res = ready!(fut.poll_unpin(cx));
+                    self.state = WriterState::Done;
+                    return Poll::Ready(res);
+                }
+                WriterState::Done => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    #[tokio::test]
+    async fn test_chunk() -> anyhow::Result<()> {
+        let root = Operator::new(memory::Backend::build().finish().await?);
+        let op = root
+            .clone()
+            .layer(ChunkLayer::new(4).with_gc_delay(Duration::ZERO));
+
+        op.object("file").write("Hello, World!").await?;
+        assert_eq!(op.object("file").read().await?, b"Hello, World!");
+        assert_eq!(op.object("file").range_read(5..9).await?, b", Wo");
+        assert_eq!(op.object("file").range_read(7..).await?, b"World!");
+        assert_eq!(op.object("file").range_read(13..).await?, b"");
+        assert_eq!(op.object("file").metadata().await?.content_length(), 13);
+
+        // Chunks are stored separately.
+        let manifest = root.object("file").read().await?;
+        let m = Manifest::decode(&manifest).expect("manifest must be valid");
+        assert_eq!(m.chunks(), 4);
+        let chunk = root.object(&format!(".chunks/{}/3", m.id)).read().await?;
+        assert_eq!(chunk, b"!");
+
+        // Overwriting removes old chunks.
+        op.object("file").write("Hello").await?;
+        assert_eq!(op.object("file").read().await?, b"Hello");
+        assert!(
+            !root
+                .object(&format!(".chunks/{}/0", m.id))
+                .is_exist()
+                .await?
+        );
+
+        op.object("file").delete().await?;
+        assert!(!op.object("file").is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunk_gc_delay() -> anyhow::Result<()> {
+        let root = Operator::new(memory::Backend::build().finish().await?);
+        let op = root
+            .clone()
+            .layer(ChunkLayer::new(4).with_gc_delay(Duration::from_millis(100)));
+
+        op.object("file").write("Hello, World!").await?;
+        let m = Manifest::decode(&root.object("file").read().await?).expect("manifest");
+        let old = root.object(&format!(".chunks/{}/0", m.id));
+
+        // Readers of the old manifest can still read its chunks for a while.
+        op.object("file").write("Hello").await?;
+        assert_eq!(op.object("file").read().await?, b"Hello");
+        assert!(old.is_exist().await?);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!old.is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunk_concurrent_writers() -> anyhow::Result<()> {
+        let inner = memory::Backend::build().finish().await?;
+        let root = Operator::new(inner.clone());
+        let acc = ChunkLayer::new(4)
+            .with_gc_delay(Duration::ZERO)
+            .layer(inner);
+
+        // Both writers are opened before either is closed.
+        let mut first = acc.write(&OpWrite::new("file", 5)?).await?;
+        let mut second = acc.write(&OpWrite::new("file", 5)?).await?;
+        first.write_all(b"first").await?;
+        second.write_all(b"other").await?;
+        first.close().await?;
+        let m = Manifest::decode(&root.object("file").read().await?).expect("manifest");
+        second.close().await?;
+
+        let op = Operator::new(acc);
+        assert_eq!(op.object("file").read().await?, b"other");
+        // Chunks of the first writer are not leaked.
+        assert!(
+            !root
+                .object(&format!(".chunks/{}/0", m.id))
+                .is_exist()
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunk_plain_object() -> anyhow::Result<()> {
+        let root = Operator::new(memory::Backend::build().finish().await?);
+        root.object("plain").write("Hello, World!").await?;
+
+        let op = root.clone().layer(ChunkLayer::new(4));
+        assert_eq!(op.object("plain").range_read(7..).await?, b"World!");
+        assert_eq!(op.object("plain").metadata().await?.content_length(), 13);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,812 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Provide client-side encryption support via [`EncryptionLayer`].
+
+use std::cmp::max;
+use std::cmp::min;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use aes_gcm::aead::Aead;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::KeyInit;
+use aes_gcm::Nonce;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::io::Cursor;
+use futures::ready;
+use futures::AsyncRead;
+use futures::AsyncReadExt;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::error::other;
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+
+/// Magic bytes at the beginning of encrypted objects.
+const MAGIC: &[u8; 4] = b"ODE\x01";
+/// Size of magic, chunk size and wrapped key length.
+const HEADER_PREFIX_SIZE: u64 = 4 + 4 + 2;
+/// Bytes fetched at once for the header, so that wrapped keys up to 502
+/// bytes can be read along with the prefix.
+const HEADER_READ_SIZE: u64 = 512;
+/// Size of AES-256-GCM authentication tag.
+const TAG_SIZE: u64 = 16;
+/// Size of AES-256 key.
+const KEY_SIZE: usize = 32;
+/// Size of AES-256-GCM nonce.
+const NONCE_SIZE: usize = 12;
+/// Default plaintext size of every chunk.
+const DEFAULT_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// KeyProvider wraps and unwraps the data keys of objects.
+///
+/// Every object is encrypted by its own random data key, the data key will
+/// be wrapped by `KeyProvider` and stored along with the object. Users can
+/// implement `KeyProvider` with their own KMS.
+#[async_trait]
+pub trait KeyProvider: Debug + Send + Sync + 'static {
+    /// Wrap the data key so that it can be stored along with the object.
+    async fn wrap_key(&self, key: &[u8]) -> Result<Vec<u8>>;
+
+    /// Unwrap the wrapped key that stored along with the object.
+    async fn unwrap_key(&self, wrapped: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// AesKeyProvider wraps data keys with a static AES-256-GCM master key.
+pub struct AesKeyProvider {
+    cipher: Aes256Gcm,
+}
+
+impl AesKeyProvider {
+    /// Create a new AesKeyProvider with 256-bit master key.
+    pub fn new(key: &[u8; KEY_SIZE]) -> Self {
+        AesKeyProvider {
+            cipher: Aes256Gcm::new(key.into()),
+        }
+    }
+}
+
+impl Debug for AesKeyProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AesKeyProvider").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl KeyProvider for AesKeyProvider {
+    async fn wrap_key(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+
+        let wrapped = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), key)
+            .map_err(|e| other(anyhow!("wrap key: {e}")))?;
+
+        Ok([&nonce[..], &wrapped].concat())
+    }
+
+    async fn unwrap_key(&self, wrapped: &[u8]) -> Result<Vec<u8>> {
+        if wrapped.len() < NONCE_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                anyhow!("wrapped key is too short"),
+            ));
+        }
+
+        let (nonce, wrapped) = wrapped.split_at(NONCE_SIZE);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), wrapped)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, anyhow!("unwrap key: {e}")))
+    }
+}
+
+/// EncryptionLayer will encrypt objects before they are sent to the
+/// underlying storage.
+///
+/// # Format
+///
+/// Encrypted objects start with a header which carries the chunk size and
+/// the wrapped data key, followed by fixed-size chunks encrypted by
+/// AES-256-GCM. Every chunk uses its index as nonce, and the last chunk is
+/// marked so that truncated objects can be detected. Empty objects carry
+/// one empty chunk.
+///
+/// # Behavior
+///
+/// - `read` will decrypt transparently, range reads only fetch the chunks
+///   that cover the range.
+/// - `stat` will report the plaintext size, which needs to read the header.
+/// - Objects that are not encrypted, or fail the authentication, will
+///   return `ErrorKind::InvalidData`.
+///
+/// # Feature
+///
+/// This layer needs to enable feature `encryption`.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::AesKeyProvider;
+/// use opendal::layers::EncryptionLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor)
+///     .layer(EncryptionLayer::new(AesKeyProvider::new(&[42; 32])));
+///
+/// op.object("secret").write("Hello, World!").await?;
+/// assert_eq!(op.object("secret").read().await?, b"Hello, World!");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EncryptionLayer {
+    provider: Arc<dyn KeyProvider>,
+    chunk_size: u64,
+}
+
+impl EncryptionLayer {
+    /// Create a new EncryptionLayer which wraps data keys via `provider`.
+    pub fn new(provider: impl KeyProvider) -> Self {
+        EncryptionLayer {
+            provider: Arc::new(provider),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Set the plaintext size of every chunk.
+    ///
+    /// Default to 64 KiB. Only affects newly written objects.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero or larger than `u32::MAX`.
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        assert!(
+            chunk_size > 0 && chunk_size <= u32::MAX as u64,
+            "chunk size must be in 1..=u32::MAX"
+        );
+
+        self.chunk_size = chunk_size;
+        self
+    }
+}
+
+impl Layer for EncryptionLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(EncryptionAccessor {
+            inner,
+            provider: self.provider.clone(),
+            chunk_size: self.chunk_size,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct EncryptionAccessor {
+    inner: Arc<dyn Accessor>,
+    provider: Arc<dyn KeyProvider>,
+    chunk_size: u64,
+}
+
+/// Header of encrypted objects.
+struct Header {
+    /// Size of the header.
+    size: u64,
+    chunk_size: u64,
+    cipher: Aes256Gcm,
+}
+
+impl Header {
+    /// Calculate the plaintext size via the encrypted object size.
+    fn plaintext_size(&self, path: &str, size: u64) -> Result<u64> {
+        let body = size
+            .checked_sub(self.size)
+            .ok_or_else(|| invalid_data("stat", path, anyhow!("object is shorter than header")))?;
+        // Even empty objects have one chunk.
+        if body == 0 {
+            return Err(invalid_data("stat", path, anyhow!("object has no chunk")));
+        }
+        if body == TAG_SIZE {
+            return Ok(0);
+        }
+
+        let full = body / (self.chunk_size + TAG_SIZE);
+        match body % (self.chunk_size + TAG_SIZE) {
+            0 => Ok(full * self.chunk_size),
+            rem if rem > TAG_SIZE => Ok(full * self.chunk_size + rem - TAG_SIZE),
+            _ => Err(invalid_data(
+                "stat",
+                path,
+                anyhow!("object size {size} is not a valid encrypted size"),
+            )),
+        }
+    }
+}
+
+fn invalid_data(op: &'static str, path: &str, err: anyhow::Error) -> Error {
+    Error::new(ErrorKind::InvalidData, ObjectError::new(op, path, err))
+}
+
+/// Build the nonce of chunk `idx`.
+fn chunk_nonce(idx: u64, last: bool) -> [u8; NONCE_SIZE] {
+    let mut nonce = [0; NONCE_SIZE];
+    nonce[..8].copy_from_slice(&idx.to_be_bytes());
+    nonce[NONCE_SIZE - 1] = last as u8;
+    nonce
+}
+
+impl EncryptionAccessor {
+    /// Generate a new data key and build the header of it.
+    async fn new_header(&self) -> Result<(Vec<u8>, Aes256Gcm)> {
+        let mut key = [0; KEY_SIZE];
+        OsRng.fill_bytes(&mut key);
+
+        let wrapped = self.provider.wrap_key(&key).await?;
+        let wrapped_size = u16::try_from(wrapped.len())
+            .map_err(|_| other(anyhow!("wrapped key is too long: {}", wrapped.len())))?;
+
+        let mut bs = Vec::with_capacity(HEADER_PREFIX_SIZE as usize + wrapped.len());
+        bs.extend_from_slice(MAGIC);
+        bs.extend_from_slice(&(self.chunk_size as u32).to_le_bytes());
+        bs.extend_from_slice(&wrapped_size.to_le_bytes());
+        bs.extend_from_slice(&wrapped);
+
+        Ok((bs, Aes256Gcm::new(&key.into())))
+    }
+
+    /// Read the header of object whose encrypted size is `object_size`.
+    async fn read_header(&self, op: &'static str, path: &str, object_size: u64) -> Result<Header> {
+        if object_size < HEADER_PREFIX_SIZE {
+            return Err(invalid_data(
+                op,
+                path,
+                anyhow!("object header is truncated"),
+            ));
+        }
+        let mut bs = self
+            .read_exact(op, path, 0, min(object_size, HEADER_READ_SIZE))
+            .await?;
+        if &bs[..4] != MAGIC {
+            return Err(invalid_data(op, path, anyhow!("object is not encrypted")));
+        }
+        let chunk_size = u32::from_le_bytes(bs[4..8].try_into().unwrap()) as u64;
+        let wrapped_size = u16::from_le_bytes(bs[8..10].try_into().unwrap()) as u64;
+        if chunk_size == 0 {
+            return Err(invalid_data(op, path, anyhow!("chunk size is zero")));
+        }
+
+        let header_size = HEADER_PREFIX_SIZE + wrapped_size;
+        if header_size > object_size {
+            return Err(invalid_data(
+                op,
+                path,
+                anyhow!("object header is truncated"),
+            ));
+        }
+        // Wrapped key is too long to be covered by the first read.
+        if header_size > bs.len() as u64 {
+            let rest = self
+                .read_exact(op, path, bs.len() as u64, header_size - bs.len() as u64)
+                .await?;
+            bs.extend_from_slice(&rest);
+        }
+
+        let key = self
+            .provider
+            .unwrap_key(&bs[HEADER_PREFIX_SIZE as usize..header_size as usize])
+            .await?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|e| invalid_data(op, path, anyhow!("invalid data key: {e}")))?;
+
+        Ok(Header {
+            size: header_size,
+            chunk_size,
+            cipher,
+        })
+    }
+
+    async fn read_exact(
+        &self,
+        op: &'static str,
+        path: &str,
+        offset: u64,
+        size: u64,
+    ) -> Result<Vec<u8>> {
+        let mut r = self
+            .inner
+            .read(&OpRead::new_with_offset(path, Some(offset), Some(size))?)
+            .await?;
+
+        let mut bs = Vec::with_capacity(size as usize);
+        r.read_to_end(&mut bs).await?;
+        if bs.len() as u64 != size {
+            return Err(invalid_data(
+                op,
+                path,
+                anyhow!("object header is truncated"),
+            ));
+        }
+        Ok(bs)
+    }
+}
+
+#[async_trait]
+impl Accessor for EncryptionAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        match args.mode() {
+            // Empty files still need a header.
+            ObjectMode::FILE => {
+                let mut w = self.write(&OpWrite::new(args.path(), 0)?).await?;
+                w.close().await
+            }
+            _ => self.inner.create(args).await,
+        }
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let meta = self.inner.stat(&OpStat::new(args.path())?).await?;
+        let header = self
+            .read_header("read", args.path(), meta.content_length())
+            .await?;
+        let total = header.plaintext_size(args.path(), meta.content_length())?;
+
+        let start = args.offset().unwrap_or_default();
+        if start > total {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                ObjectError::new(
+                    "read",
+                    args.path(),
+                    anyhow!("offset out of bound {} > {}", start, total),
+                ),
+            ));
+        }
+        let end = match args.size() {
+            Some(size) => min(start + size, total),
+            None => total,
+        };
+        if total == 0 {
+            // Authenticate the empty chunk so that truncated objects can't
+            // be read as empty.
+            let bs = self
+                .read_exact("read", args.path(), header.size, TAG_SIZE)
+                .await?;
+            header
+                .cipher
+                .decrypt(Nonce::from_slice(&chunk_nonce(0, true)), bs.as_slice())
+                .map_err(|_| {
+                    invalid_data(
+                        "read",
+                        args.path(),
+                        anyhow!("chunk 0 failed authentication"),
+                    )
+                })?;
+        }
+        if start == end {
+            return Ok(Box::new(Cursor::new(Vec::new())));
+        }
+
+        let chunk_size = header.chunk_size;
+        let first = start / chunk_size;
+        let last = (end - 1) / chunk_size;
+        let offset = header.size + first * (chunk_size + TAG_SIZE);
+        let size = min(
+            header.size + (last + 1) * (chunk_size + TAG_SIZE),
+            meta.content_length(),
+        ) - offset;
+
+        let r = self
+            .inner
+            .read(&OpRead::new_with_offset(
+                args.path(),
+                Some(offset),
+                Some(size),
+            )?)
+            .await?;
+
+        Ok(Box::new(DecryptReader {
+            inner: r,
+            path: args.path().to_string(),
+            cipher: header.cipher,
+            chunk_size,
+            total,
+            idx: first,
+            skip: (start - first * chunk_size) as usize,
+            remaining: end - start,
+            cbuf: Vec::new(),
+            filled: 0,
+            pbuf: Vec::new(),
+            ppos: 0,
+        }))
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        let (header, cipher) = self.new_header().await?;
+
+        let chunks = max(args.size().div_ceil(self.chunk_size), 1);
+        let size = header.len() as u64 + args.size() + chunks * TAG_SIZE;
+        let w = self.inner.write(&OpWrite::new(args.path(), size)?).await?;
+
+        Ok(Box::new(EncryptWriter {
+            inner: w,
+            path: args.path().to_string(),
+            cipher,
+            chunk_size: self.chunk_size,
+            size: args.size(),
+            written: 0,
+            idx: 0,
+            plain: Vec::with_capacity(self.chunk_size as usize),
+            out: header,
+            out_pos: 0,
+        }))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        let meta = self.inner.stat(args).await?;
+        if meta.mode() != ObjectMode::FILE {
+            return Ok(meta);
+        }
+
+        let header = self
+            .read_header("stat", args.path(), meta.content_length())
+            .await?;
+        let size = header.plaintext_size(args.path(), meta.content_length())?;
+
+        // content_md5 is calculated over the encrypted object, drop it.
+        let mut m = ObjectMetadata::default();
+        m.set_mode(ObjectMode::FILE).set_content_length(size);
+        if let Some(v) = meta.last_modified() {
+            m.set_last_modified(v);
+        }
+        if let Some(v) = meta.etag() {
+            m.set_etag(v);
+        }
+        Ok(m)
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+struct DecryptReader {
+    inner: BytesReader,
+    path: String,
+    cipher: Aes256Gcm,
+    chunk_size: u64,
+    /// Plaintext size of the whole object.
+    total: u64,
+
+    /// Index of the next chunk.
+    idx: u64,
+    /// Bytes to skip in the next chunk.
+    skip: usize,
+    /// Bytes to return to users.
+    remaining: u64,
+
+    cbuf: Vec<u8>,
+    filled: usize,
+    pbuf: Vec<u8>,
+    ppos: usize,
+}
+
+impl DecryptReader {
+    fn last_idx(&self) -> u64 {
+        (self.total - 1) / self.chunk_size
+    }
+
+    /// Encrypted size of chunk `idx`.
+    fn chunk_size(&self, idx: u64) -> usize {
+        if idx == self.last_idx() {
+            (self.total - idx * self.chunk_size + TAG_SIZE) as usize
+        } else {
+            (self.chunk_size + TAG_SIZE) as usize
+        }
+    }
+}
+
+impl AsyncRead for DecryptReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let this = &mut *self;
+
+        loop {
+            if this.ppos < this.pbuf.len() {
+                let n = min(
+                    min(buf.len(), this.pbuf.len() - this.ppos) as u64,
+                    this.remaining,
+                ) as usize;
+                buf[..n].copy_from_slice(&this.pbuf[this.ppos..this.ppos + n]);
+                this.ppos += n;
+                this.remaining -= n as u64;
+                return Poll::Ready(Ok(n));
+            }
+            if this.remaining == 0 {
+                return Poll::Ready(Ok(0));
+            }
+
+            let size = this.chunk_size(this.idx);
+            this.cbuf.resize(size, 0);
+            while this.filled < size {
+                let n =
+                    ready!(Pin::new(&mut this.inner).poll_read(cx, &mut this.cbuf[this.filled..]))?;
+                if n == 0 {
+                    return Poll::Ready(Err(invalid_data(
+                        "read",
+                        &this.path,
+                        anyhow!("chunk {} is truncated", this.idx),
+                    )));
+                }
+                this.filled += n;
+            }
+
+            let nonce = chunk_nonce(this.idx, this.idx == this.last_idx());
+            this.pbuf = this
+                .cipher
+                .decrypt(Nonce::from_slice(&nonce), this.cbuf.as_slice())
+                .map_err(|_| {
+                    invalid_data(
+                        "read",
+                        &this.path,
+                        anyhow!("chunk {} failed authentication", this.idx),
+                    )
+                })?;
+            this.ppos = this.skip;
+            this.skip = 0;
+            this.filled = 0;
+            this.idx += 1;
+        }
+    }
+}
+
+struct EncryptWriter {
+    inner: BytesWriter,
+    path: String,
+    cipher: Aes256Gcm,
+    chunk_size: u64,
+    /// Plaintext size of the whole object.
+    size: u64,
+    written: u64,
+    idx: u64,
+
+    plain: Vec<u8>,
+    /// Encrypted data that not sent to inner writer.
+    out: Vec<u8>,
+    out_pos: usize,
+}
+
+impl EncryptWriter {
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.out_pos < self.out.len() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.out[self.out_pos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(Error::new(
+                    ErrorKind::WriteZero,
+                    ObjectError::new("write", &self.path, anyhow!("inner writer wrote zero")),
+                )));
+            }
+            self.out_pos += n;
+        }
+
+        self.out.clear();
+        self.out_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn seal(&mut self) -> Result<()> {
+        let nonce = chunk_nonce(self.idx, self.written == self.size);
+        let bs = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), self.plain.as_slice())
+            .map_err(|e| {
+                other(ObjectError::new(
+                    "write",
+                    &self.path,
+                    anyhow!("encrypt: {e}"),
+                ))
+            })?;
+
+        self.out.extend_from_slice(&bs);
+        self.plain.clear();
+        self.idx += 1;
+        Ok(())
+    }
+}
+
+impl AsyncWrite for EncryptWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        ready!(self.poll_send(cx))?;
+
+        if self.written + buf.len() as u64 > self.size {
+            return Poll::Ready(Err(Error::new(
+                ErrorKind::InvalidInput,
+                ObjectError::new(
+                    "write",
+                    &self.path,
+                    anyhow!("write more than expected size {}", self.size),
+                ),
+            )));
+        }
+
+        let n = min(buf.len(), self.chunk_size as usize - self.plain.len());
+        self.plain.extend_from_slice(&buf[..n]);
+        self.written += n as u64;
+        if self.plain.len() as u64 == self.chunk_size {
+            self.seal()?;
+        }
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_send(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.written != self.size {
+            return Poll::Ready(Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                ObjectError::new(
+                    "write",
+                    &self.path,
+                    anyhow!("expected size {}, but got {}", self.size, self.written),
+                ),
+            )));
+        }
+        // Empty objects still need the last chunk.
+        if !self.plain.is_empty() || self.idx == 0 {
+            self.seal()?;
+        }
+
+        ready!(self.poll_send(cx))?;
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    async fn new_operator() -> anyhow::Result<(Operator, Operator)> {
+        let inner = Operator::new(memory::Backend::build().finish().await?);
+        let op = inner
+            .clone()
+            .layer(EncryptionLayer::new(AesKeyProvider::new(&[42; 32])).with_chunk_size(16));
+        Ok((inner, op))
+    }
+
+    #[tokio::test]
+    async fn test_encryption() -> anyhow::Result<()> {
+        let (inner, op) = new_operator().await?;
+        let content: Vec<u8> = (0..100).collect();
+
+        op.object("file").write(content.clone()).await?;
+        assert_eq!(op.object("file").metadata().await?.content_length(), 100);
+        assert_eq!(op.object("file").read().await?, content);
+
+        let raw = inner.object("file").read().await?;
+        assert_ne!(raw, content);
+        assert!(!raw.windows(16).any(|w| w == &content[..16]));
+
+        for (start, end) in [(0, 1), (10, 20), (16, 32), (15, 17), (90, 100), (99, 100)] {
+            assert_eq!(
+                op.object("file").range_read(start..end).await?,
+                &content[start as usize..end as usize],
+                "range {start}..{end}"
+            );
+        }
+        assert_eq!(op.object("file").range_read(50..).await?, &content[50..]);
+        assert!(op.object("file").range_read(100..).await?.is_empty());
+        let err = op.object("file").range_read(101..).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        op.object("empty").create().await?;
+        assert_eq!(op.object("empty").metadata().await?.content_length(), 0);
+        assert!(op.object("empty").read().await?.is_empty());
+
+        // Objects truncated to the header only must not be read as empty.
+        let mut raw = inner.object("file").read().await?;
+        raw.truncate(raw.len() - 100 - 7 * TAG_SIZE as usize);
+        inner.object("file").write(raw).await?;
+        let err = op.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let mut raw = inner.object("empty").read().await?;
+        raw.truncate(raw.len() - TAG_SIZE as usize);
+        inner.object("empty").write(raw).await?;
+        let err = op.object("empty").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encryption_tampered() -> anyhow::Result<()> {
+        let (inner, op) = new_operator().await?;
+        let content: Vec<u8> = (0..100).collect();
+        op.object("file").write(content).await?;
+
+        let mut raw = inner.object("file").read().await?;
+        let idx = raw.len() - 1;
+        raw[idx] ^= 1;
+        inner.object("file").write(raw.clone()).await?;
+        let err = op.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // Truncated objects must be detected too.
+        raw.truncate(raw.len() - 20);
+        inner.object("file").write(raw).await?;
+        let err = op.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // Objects encrypted by other keys can't be read.
+        op.object("file").write("Hello, World!").await?;
+        let other = inner
+            .clone()
+            .layer(EncryptionLayer::new(AesKeyProvider::new(&[7; 32])));
+        let err = other.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        Ok(())
+    }
+}
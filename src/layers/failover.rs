@@ -0,0 +1,339 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use log::debug;
+use log::warn;
+use parking_lot::Mutex;
+
+use super::fallback;
+use super::fallback::Target;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// Default consecutive failures before the circuit opens.
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+/// Default duration that an open circuit will reject requests.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// FailoverLayer will fail over reads from the underlying accessor (the
+/// primary) to secondaries that hold the same data.
+///
+/// # Behavior
+///
+/// - `read`, `stat` and `list` will be sent to the first healthy endpoint,
+///   and fail over to the next one if failed.
+/// - `write`, `create` and `delete` will only be sent to the primary.
+/// - Errors caused by the caller like `NotFound`, `PermissionDenied` and
+///   `InvalidInput` will be returned directly without failing over.
+/// - Every endpoint has a circuit breaker. After `failure_threshold`
+///   consecutive failures, the circuit opens and the endpoint will be
+///   skipped for `cooldown`. After that, one request is allowed to probe
+///   the endpoint, which closes the circuit if succeeded.
+/// - If circuits of all endpoints are open, requests fail with
+///   `Interrupted` at once.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use std::time::Duration;
+///
+/// use opendal::layers::FailoverLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let primary = memory::Backend::build().finish().await?;
+/// let secondary = memory::Backend::build().finish().await?;
+/// let op = Operator::new(primary).layer(
+///     FailoverLayer::new(vec![secondary])
+///         .with_failure_threshold(3)
+///         .with_cooldown(Duration::from_secs(10)),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FailoverLayer {
+    secondaries: Vec<Arc<dyn Accessor>>,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl FailoverLayer {
+    /// Create a new FailoverLayer which fails over to `secondaries`.
+    pub fn new(secondaries: Vec<Arc<dyn Accessor>>) -> Self {
+        FailoverLayer {
+            secondaries,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+
+    /// Set consecutive failures before the circuit of an endpoint opens.
+    ///
+    /// Default to 5.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threshold` is zero.
+    #[must_use]
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        assert!(threshold > 0, "failure threshold must be greater than 0");
+
+        self.failure_threshold = threshold;
+        self
+    }
+
+    /// Set the duration that an open circuit will reject requests.
+    ///
+    /// Default to 30s.
+    #[must_use]
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+}
+
+impl Layer for FailoverLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        let mut endpoints = vec![Endpoint::new("primary".to_string(), inner, self)];
+        for (idx, acc) in self.secondaries.iter().enumerate() {
+            let name = format!("secondary #{} ({})", idx + 1, acc.metadata().name());
+            endpoints.push(Endpoint::new(name, acc.clone(), self));
+        }
+
+        Arc::new(FailoverAccessor { endpoints })
+    }
+}
+
+/// Check if the error is caused by the caller, which should not be failed over.
+fn is_caller_error(err: &Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::NotFound
+            | ErrorKind::PermissionDenied
+            | ErrorKind::InvalidInput
+            | ErrorKind::AlreadyExists
+            | ErrorKind::Unsupported
+    )
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    /// The circuit is open until this instant.
+    open_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    name: String,
+    accessor: Arc<dyn Accessor>,
+    failure_threshold: u32,
+    cooldown: Duration,
+    health: Mutex<Health>,
+}
+
+impl Endpoint {
+    fn new(name: String, accessor: Arc<dyn Accessor>, layer: &FailoverLayer) -> Self {
+        Endpoint {
+            name,
+            accessor,
+            failure_threshold: layer.failure_threshold,
+            cooldown: layer.cooldown,
+            health: Mutex::new(Health::default()),
+        }
+    }
+}
+
+impl Target for Endpoint {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn accessor(&self) -> &Arc<dyn Accessor> {
+        &self.accessor
+    }
+
+    /// Check if the endpoint could accept requests.
+    ///
+    /// Once the cooldown has passed, only one request will be allowed to
+    /// probe the endpoint until it's marked as succeeded or failed.
+    fn acquire(&self) -> bool {
+        let mut health = self.health.lock();
+        match health.open_until {
+            None => true,
+            Some(until) if Instant::now() >= until => {
+                debug!("endpoint {} circuit half open, probing", self.name);
+                health.open_until = Some(Instant::now() + self.cooldown);
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    fn succeed(&self) {
+        let mut health = self.health.lock();
+        if health.open_until.is_some() {
+            debug!("endpoint {} recovered, circuit closed", self.name);
+        }
+        *health = Health::default();
+    }
+
+    fn fail(&self) {
+        let mut health = self.health.lock();
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= self.failure_threshold {
+            warn!(
+                "endpoint {} failed {} times, circuit opened for {:?}",
+                self.name, health.consecutive_failures, self.cooldown
+            );
+            health.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FailoverAccessor {
+    /// Endpoints in priority order, the first one is the primary.
+    endpoints: Vec<Endpoint>,
+}
+
+impl FailoverAccessor {
+    fn primary(&self) -> &Arc<dyn Accessor> {
+        &self.endpoints[0].accessor
+    }
+
+    async fn fail_over<'a, T, F, Fut>(&'a self, op: &'static str, path: &str, f: F) -> Result<T>
+    where
+        F: Fn(&'a Arc<dyn Accessor>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        fallback::fail_over(op, path, &self.endpoints, is_caller_error, f).await
+    }
+}
+
+#[async_trait]
+impl Accessor for FailoverAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.primary().metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.primary().create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.fail_over("read", args.path(), |acc| acc.read(args))
+            .await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.primary().write(args).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.fail_over("stat", args.path(), |acc| acc.stat(args))
+            .await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.primary().delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.fail_over("list", args.path(), |acc| acc.list(args))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::layers::test_util::BrokenService;
+    use crate::services::memory;
+    use crate::Operator;
+
+    #[tokio::test]
+    async fn test_failover() -> anyhow::Result<()> {
+        let secondary = memory::Backend::build().finish().await?;
+        Operator::new(secondary.clone())
+            .object("file")
+            .write("Hello, World!")
+            .await?;
+
+        let primary = BrokenService::new(ErrorKind::Interrupted);
+        let op = Operator::new(primary.clone()).layer(FailoverLayer::new(vec![secondary]));
+        assert_eq!(op.object("file").metadata().await?.content_length(), 13);
+        assert_eq!(primary.stats.load(Ordering::SeqCst), 1);
+
+        let primary = BrokenService::new(ErrorKind::NotFound);
+        let secondary = BrokenService::new(ErrorKind::NotFound);
+        let op = Operator::new(primary.clone()).layer(FailoverLayer::new(vec![secondary.clone()]));
+        let err = op.object("file").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(secondary.stats.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() -> anyhow::Result<()> {
+        let primary = BrokenService::new(ErrorKind::Interrupted);
+        let secondary = memory::Backend::build().finish().await?;
+        let op = Operator::new(primary.clone()).layer(
+            FailoverLayer::new(vec![secondary])
+                .with_failure_threshold(2)
+                .with_cooldown(Duration::from_millis(100)),
+        );
+
+        for _ in 0..5 {
+            op.object("dir/").metadata().await?;
+        }
+        assert_eq!(primary.stats.load(Ordering::SeqCst), 2);
+
+        // Only one request is allowed to probe after cooldown.
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        for _ in 0..5 {
+            op.object("dir/").metadata().await?;
+        }
+        assert_eq!(primary.stats.load(Ordering::SeqCst), 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers shared by layers that fall back between accessors.
+
+use std::future::Future;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use log::warn;
+
+use crate::error::ObjectError;
+use crate::Accessor;
+
+/// Target that requests could fail over to.
+pub(super) trait Target {
+    /// Name of the target used in logs.
+    fn name(&self) -> &str;
+
+    fn accessor(&self) -> &Arc<dyn Accessor>;
+
+    /// Check if the target could accept requests, default to always.
+    fn acquire(&self) -> bool {
+        true
+    }
+
+    /// Report that a request on the target succeeded.
+    fn succeed(&self) {}
+
+    /// Report that a request on the target failed.
+    fn fail(&self) {}
+}
+
+impl Target for (String, Arc<dyn Accessor>) {
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn accessor(&self) -> &Arc<dyn Accessor> {
+        &self.1
+    }
+}
+
+/// Run `f` on `targets` in order, and fail over to the next one if failed.
+///
+/// Errors that `is_final` returns true for will be returned directly. If
+/// all targets failed, the first error will be returned.
+pub(super) async fn fail_over<'a, E, T, F, Fut>(
+    op: &'static str,
+    path: &str,
+    targets: &'a [E],
+    is_final: fn(&Error) -> bool,
+    f: F,
+) -> Result<T>
+where
+    E: Target,
+    F: Fn(&'a Arc<dyn Accessor>) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut first_err = None;
+    for target in targets {
+        if !target.acquire() {
+            continue;
+        }
+
+        match f(target.accessor()).await {
+            Ok(v) => {
+                target.succeed();
+                return Ok(v);
+            }
+            Err(e) if is_final(&e) => {
+                target.succeed();
+                return Err(e);
+            }
+            Err(e) => {
+                warn!(
+                    "object {} {} failed on {}, failing over: {}",
+                    path,
+                    op,
+                    target.name(),
+                    e
+                );
+                target.fail();
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+
+    Err(first_err.unwrap_or_else(|| {
+        Error::new(
+            ErrorKind::Interrupted,
+            ObjectError::new(op, path, anyhow!("no target is available")),
+        )
+    }))
+}
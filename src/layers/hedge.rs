@@ -0,0 +1,370 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use futures::future::select;
+use futures::future::Either;
+use log::debug;
+use parking_lot::Mutex;
+
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// Default ratio of requests that could be hedged.
+const DEFAULT_BUDGET: f64 = 0.05;
+/// Max latency samples that kept for percentile.
+const MAX_SAMPLES: usize = 1024;
+/// Min latency samples before percentile takes effect.
+const MIN_SAMPLES: usize = 64;
+/// Recompute the percentile delay every `UPDATE_INTERVAL` samples.
+const UPDATE_INTERVAL: usize = 16;
+
+/// HedgeLayer will send a duplicate request if the first one doesn't
+/// respond in time, and take whichever responds first.
+///
+/// Only `read` and `stat` will be hedged. For `read`, the response means
+/// the reader has been created, the data transfer is not hedged.
+///
+/// # Behavior
+///
+/// - The duplicate request will be sent after the fixed delay, or the
+///   percentile of observed latency if [`HedgeLayer::with_percentile`] is set.
+/// - The request that loses will be dropped, which cancels it.
+/// - If the first response is a retryable error, we will wait for the other.
+/// - Hedged requests will never exceed the budget set by
+///   [`HedgeLayer::with_budget`], which is 5% of total requests by default.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use std::time::Duration;
+///
+/// use opendal::layers::HedgeLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(
+///     HedgeLayer::new(Duration::from_millis(100))
+///         .with_percentile(95.0)
+///         .with_budget(0.1),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HedgeLayer {
+    delay: Duration,
+    percentile: Option<f64>,
+    budget: f64,
+}
+
+impl HedgeLayer {
+    /// Create a new HedgeLayer which sends duplicate requests after `delay`.
+    pub fn new(delay: Duration) -> Self {
+        HedgeLayer {
+            delay,
+            percentile: None,
+            budget: DEFAULT_BUDGET,
+        }
+    }
+
+    /// Send duplicate requests after the `percentile` (in `0.0..=100.0`) of
+    /// observed latency.
+    ///
+    /// The delay set in [`HedgeLayer::new`] will be used until we have
+    /// observed enough requests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `percentile` is not in `0.0..=100.0`.
+    #[must_use]
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        assert!(
+            (0.0..=100.0).contains(&percentile),
+            "percentile must be in 0.0..=100.0"
+        );
+
+        self.percentile = Some(percentile);
+        self
+    }
+
+    /// Set the max ratio (in `0.0..=1.0`) of requests that could be hedged.
+    ///
+    /// Default to 0.05.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `budget` is not in `0.0..=1.0`.
+    #[must_use]
+    pub fn with_budget(mut self, budget: f64) -> Self {
+        assert!((0.0..=1.0).contains(&budget), "budget must be in 0.0..=1.0");
+
+        self.budget = budget;
+        self
+    }
+}
+
+impl Layer for HedgeLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(HedgeAccessor {
+            inner,
+            budget: self.budget,
+            total: AtomicU64::new(0),
+            hedged: AtomicU64::new(0),
+            read_latency: Mutex::new(LatencyWindow::new(self.delay, self.percentile)),
+            stat_latency: Mutex::new(LatencyWindow::new(self.delay, self.percentile)),
+        })
+    }
+}
+
+/// LatencyWindow keeps recent latency samples to decide the hedge delay.
+#[derive(Debug)]
+struct LatencyWindow {
+    percentile: Option<f64>,
+    samples: VecDeque<Duration>,
+    delay: Duration,
+    since_update: usize,
+}
+
+impl LatencyWindow {
+    fn new(delay: Duration, percentile: Option<f64>) -> Self {
+        LatencyWindow {
+            percentile,
+            samples: VecDeque::new(),
+            delay,
+            since_update: 0,
+        }
+    }
+
+    fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let percentile = match self.percentile {
+            Some(v) => v,
+            None => return,
+        };
+
+        if self.samples.len() >= MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency);
+
+        self.since_update += 1;
+        if self.samples.len() < MIN_SAMPLES || self.since_update < UPDATE_INTERVAL {
+            return;
+        }
+        self.since_update = 0;
+
+        let mut samples: Vec<_> = self.samples.iter().copied().collect();
+        samples.sort_unstable();
+        let idx = ((samples.len() - 1) as f64 * percentile / 100.0).round() as usize;
+        self.delay = samples[idx];
+    }
+}
+
+#[derive(Debug)]
+struct HedgeAccessor {
+    inner: Arc<dyn Accessor>,
+
+    budget: f64,
+    total: AtomicU64,
+    hedged: AtomicU64,
+
+    read_latency: Mutex<LatencyWindow>,
+    stat_latency: Mutex<LatencyWindow>,
+}
+
+impl HedgeAccessor {
+    /// Take a hedge from budget, returns `false` if budget has been used up.
+    fn acquire_budget(&self) -> bool {
+        let total = self.total.load(Ordering::Relaxed);
+        self.hedged
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |hedged| {
+                ((hedged + 1) as f64 <= total as f64 * self.budget).then(|| hedged + 1)
+            })
+            .is_ok()
+    }
+
+    async fn hedge<T, F, Fut>(&self, path: &str, latency: &Mutex<LatencyWindow>, f: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.total.fetch_add(1, Ordering::Relaxed);
+
+        let start = Instant::now();
+        let delay = latency.lock().delay();
+
+        let first = Box::pin(f());
+        let res = match select(first, Box::pin(tokio::time::sleep(delay))).await {
+            Either::Left((res, _)) => res,
+            Either::Right((_, first)) => {
+                if self.acquire_budget() {
+                    debug!("object {} not responded in {:?}, hedging", path, delay);
+                    race(first, Box::pin(f())).await
+                } else {
+                    first.await
+                }
+            }
+        };
+
+        if res.is_ok() {
+            latency.lock().record(start.elapsed());
+        }
+        res
+    }
+}
+
+/// Take whichever responds first, the other one will be dropped.
+///
+/// If the first response is a retryable error, wait for the other.
+async fn race<T, A, B>(a: A, b: B) -> Result<T>
+where
+    A: Future<Output = Result<T>> + Unpin,
+    B: Future<Output = Result<T>> + Unpin,
+{
+    match select(a, b).await {
+        Either::Left((Err(e), b)) if e.kind() == ErrorKind::Interrupted => b.await,
+        Either::Right((Err(e), a)) if e.kind() == ErrorKind::Interrupted => a.await,
+        Either::Left((res, _)) | Either::Right((res, _)) => res,
+    }
+}
+
+#[async_trait]
+impl Accessor for HedgeAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inner.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.hedge(args.path(), &self.read_latency, || self.inner.read(args))
+            .await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.inner.write(args).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.hedge(args.path(), &self.stat_latency, || self.inner.stat(args))
+            .await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::Operator;
+
+    /// SlowService will be slow for the first stat.
+    #[derive(Debug, Default)]
+    struct SlowService {
+        stats: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Accessor for SlowService {
+        async fn stat(&self, _: &OpStat) -> Result<ObjectMetadata> {
+            if self.stats.fetch_add(1, Ordering::SeqCst) == 0 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ObjectMetadata::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hedge() -> anyhow::Result<()> {
+        let srv = Arc::new(SlowService::default());
+        let op = Operator::new(srv.clone())
+            .layer(HedgeLayer::new(Duration::from_millis(10)).with_budget(1.0));
+
+        let now = Instant::now();
+        op.object("file").metadata().await?;
+        assert!(now.elapsed() < Duration::from_secs(1));
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hedge_budget() -> anyhow::Result<()> {
+        let srv = Arc::new(SlowService::default());
+        let op = Operator::new(srv.clone())
+            .layer(HedgeLayer::new(Duration::from_millis(10)).with_budget(0.0));
+
+        let now = Instant::now();
+        op.object("file").metadata().await?;
+        assert!(now.elapsed() >= Duration::from_secs(1));
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_latency_window() {
+        let mut window = LatencyWindow::new(Duration::from_secs(1), Some(90.0));
+
+        for i in 0..MIN_SAMPLES - 1 {
+            window.record(Duration::from_millis(i as u64));
+        }
+        assert_eq!(window.delay(), Duration::from_secs(1));
+
+        for i in 0..100 {
+            window.record(Duration::from_millis(i as u64));
+        }
+        assert!(window.delay() < Duration::from_millis(100));
+        assert!(window.delay() > Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,494 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::future::join_all;
+use futures::ready;
+use futures::AsyncWrite;
+use log::warn;
+
+use super::fallback;
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// MirrorLayer will mirror objects from the underlying accessor (the
+/// primary) to replicas.
+///
+/// # Behavior
+///
+/// - `write`, `create` and `delete` will be sent to the primary and all
+///   replicas, and succeed if at least quorum of them succeed. The quorum
+///   is the count of all accessors by default.
+/// - The writer will be teed to all accessors, so data will only be read
+///   from the caller once.
+/// - `read`, `stat` and `list` will be sent to the primary first, and fail
+///   over to replicas one by one if failed. `NotFound` is final and will
+///   not fail over, so that stale replicas won't serve deleted objects.
+///
+/// Failed replicas will be reported in the returned error if quorum is not
+/// reached, or logged as warnings otherwise.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::MirrorLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let primary = memory::Backend::build().finish().await?;
+/// let replica = memory::Backend::build().finish().await?;
+/// let op = Operator::new(primary).layer(MirrorLayer::new(vec![replica]).with_quorum(1));
+///
+/// op.object("file").write("Hello, World!").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MirrorLayer {
+    replicas: Vec<Arc<dyn Accessor>>,
+    quorum: Option<usize>,
+}
+
+impl MirrorLayer {
+    /// Create a new MirrorLayer which mirrors objects to `replicas`.
+    pub fn new(replicas: Vec<Arc<dyn Accessor>>) -> Self {
+        MirrorLayer {
+            replicas,
+            quorum: None,
+        }
+    }
+
+    /// Set the count of accessors (including the primary) that must succeed
+    /// for `write`, `create` and `delete`.
+    ///
+    /// Default to the count of all accessors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `quorum` is zero or greater than the count of all accessors.
+    #[must_use]
+    pub fn with_quorum(mut self, quorum: usize) -> Self {
+        assert!(quorum > 0, "quorum must be greater than 0");
+        assert!(
+            quorum <= self.replicas.len() + 1,
+            "quorum must not be greater than the count of accessors"
+        );
+
+        self.quorum = Some(quorum);
+        self
+    }
+}
+
+impl Layer for MirrorLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        let mut accessors = vec![("primary".to_string(), inner)];
+        for (idx, acc) in self.replicas.iter().enumerate() {
+            let name = format!("replica #{} ({})", idx + 1, acc.metadata().name());
+            accessors.push((name, acc.clone()));
+        }
+
+        let quorum = self.quorum.unwrap_or(accessors.len());
+        Arc::new(MirrorAccessor { accessors, quorum })
+    }
+}
+
+#[derive(Debug)]
+struct MirrorAccessor {
+    /// Accessors with their names, the first one is the primary.
+    accessors: Vec<(String, Arc<dyn Accessor>)>,
+    quorum: usize,
+}
+
+/// Build the error for not reaching quorum.
+fn quorum_error(
+    op: &'static str,
+    path: &str,
+    succeed: usize,
+    failures: &[(String, Error)],
+) -> Error {
+    let kind = failures
+        .first()
+        .map(|(_, e)| e.kind())
+        .unwrap_or(ErrorKind::Other);
+    let failed = failures
+        .iter()
+        .map(|(name, e)| format!("{name}: {e}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Error::new(
+        kind,
+        ObjectError::new(
+            op,
+            path,
+            anyhow!("quorum not reached with {succeed} succeed, failed replicas: [{failed}]"),
+        ),
+    )
+}
+
+/// Report failed replicas when quorum has been reached.
+fn report_failures(op: &'static str, path: &str, failures: &[(String, Error)]) {
+    for (name, e) in failures {
+        warn!("object {} {} failed on {}: {}", path, op, name, e);
+    }
+}
+
+impl MirrorAccessor {
+    /// Run `f` on all accessors concurrently and check quorum.
+    async fn fan_out<'a, F, Fut>(&'a self, op: &'static str, path: &str, f: F) -> Result<()>
+    where
+        F: Fn(&'a Arc<dyn Accessor>) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let results = join_all(self.accessors.iter().map(|(_, acc)| f(acc))).await;
+
+        let mut failures = Vec::new();
+        for ((name, _), res) in self.accessors.iter().zip(results) {
+            if let Err(e) = res {
+                failures.push((name.clone(), e));
+            }
+        }
+
+        let succeed = self.accessors.len() - failures.len();
+        if succeed < self.quorum {
+            return Err(quorum_error(op, path, succeed, &failures));
+        }
+        report_failures(op, path, &failures);
+        Ok(())
+    }
+
+    /// Run `f` on the primary, and fail over to replicas one by one.
+    ///
+    /// `NotFound` will be returned directly.
+    async fn fail_over<'a, T, F, Fut>(&'a self, op: &'static str, path: &str, f: F) -> Result<T>
+    where
+        F: Fn(&'a Arc<dyn Accessor>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        fallback::fail_over(
+            op,
+            path,
+            &self.accessors,
+            |e| e.kind() == ErrorKind::NotFound,
+            f,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl Accessor for MirrorAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.accessors[0].1.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.fan_out("create", args.path(), |acc| acc.create(args))
+            .await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.fail_over("read", args.path(), |acc| acc.read(args))
+            .await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        let results = join_all(self.accessors.iter().map(|(_, acc)| acc.write(args))).await;
+
+        let mut targets = Vec::new();
+        let mut failures = Vec::new();
+        for ((name, _), res) in self.accessors.iter().zip(results) {
+            match res {
+                Ok(writer) => targets.push(Target {
+                    name: name.clone(),
+                    writer,
+                    offset: 0,
+                    closed: false,
+                }),
+                Err(e) => failures.push((name.clone(), e)),
+            }
+        }
+
+        if targets.len() < self.quorum {
+            return Err(quorum_error("write", args.path(), targets.len(), &failures));
+        }
+
+        Ok(Box::new(MirrorWriter {
+            path: args.path().to_string(),
+            quorum: self.quorum,
+            targets,
+            failures,
+            buf: Vec::new(),
+        }))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.fail_over("stat", args.path(), |acc| acc.stat(args))
+            .await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.fan_out("delete", args.path(), |acc| acc.delete(args))
+            .await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.fail_over("list", args.path(), |acc| acc.list(args))
+            .await
+    }
+}
+
+struct Target {
+    name: String,
+    writer: BytesWriter,
+    /// Bytes of `buf` that have been written into this target.
+    offset: usize,
+    closed: bool,
+}
+
+/// MirrorWriter tees data into all targets.
+///
+/// Data will be copied into `buf` and accepted at once, the next
+/// `poll_write` will wait until `buf` has been written into all targets.
+struct MirrorWriter {
+    path: String,
+    quorum: usize,
+    targets: Vec<Target>,
+    failures: Vec<(String, Error)>,
+    buf: Vec<u8>,
+}
+
+impl MirrorWriter {
+    /// Remove failed targets and check quorum.
+    fn check_failures(&mut self, failed: Vec<(usize, Error)>) -> Result<()> {
+        for (idx, e) in failed.into_iter().rev() {
+            let target = self.targets.remove(idx);
+            self.failures.push((target.name, e));
+        }
+
+        if self.targets.len() < self.quorum {
+            return Err(quorum_error(
+                "write",
+                &self.path,
+                self.targets.len(),
+                &self.failures,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Write the pending `buf` into all targets.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let mut pending = false;
+        let mut failed = Vec::new();
+
+        for (idx, t) in self.targets.iter_mut().enumerate() {
+            while t.offset < self.buf.len() {
+                match Pin::new(&mut t.writer).poll_write(cx, &self.buf[t.offset..]) {
+                    Poll::Ready(Ok(0)) => {
+                        failed.push((idx, Error::from(ErrorKind::WriteZero)));
+                        break;
+                    }
+                    Poll::Ready(Ok(n)) => t.offset += n,
+                    Poll::Ready(Err(e)) => {
+                        failed.push((idx, e));
+                        break;
+                    }
+                    Poll::Pending => {
+                        pending = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.check_failures(failed)?;
+        if pending {
+            return Poll::Pending;
+        }
+
+        self.buf.clear();
+        for t in self.targets.iter_mut() {
+            t.offset = 0;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for MirrorWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        ready!(self.poll_drain(cx))?;
+
+        self.buf.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_drain(cx))?;
+
+        let mut pending = false;
+        let mut failed = Vec::new();
+        for (idx, t) in self.targets.iter_mut().enumerate() {
+            match Pin::new(&mut t.writer).poll_flush(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => failed.push((idx, e)),
+                Poll::Pending => pending = true,
+            }
+        }
+
+        self.check_failures(failed)?;
+        if pending {
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_drain(cx))?;
+
+        let mut pending = false;
+        let mut failed = Vec::new();
+        for (idx, t) in self.targets.iter_mut().enumerate() {
+            if t.closed {
+                continue;
+            }
+            match Pin::new(&mut t.writer).poll_close(cx) {
+                Poll::Ready(Ok(())) => t.closed = true,
+                Poll::Ready(Err(e)) => failed.push((idx, e)),
+                Poll::Pending => pending = true,
+            }
+        }
+
+        self.check_failures(failed)?;
+        if pending {
+            return Poll::Pending;
+        }
+
+        report_failures("write", &self.path, &self.failures);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::test_util::BrokenService;
+    use crate::services::memory;
+    use crate::Operator;
+
+    #[tokio::test]
+    async fn test_mirror() -> anyhow::Result<()> {
+        let primary = memory::Backend::build().finish().await?;
+        let replica = memory::Backend::build().finish().await?;
+        let op = Operator::new(primary.clone()).layer(MirrorLayer::new(vec![replica.clone()]));
+
+        let data = vec![1u8; 64 * 1024];
+        op.object("file").write(data.clone()).await?;
+
+        assert_eq!(Operator::new(primary).object("file").read().await?, data);
+        assert_eq!(
+            Operator::new(replica.clone()).object("file").read().await?,
+            data
+        );
+
+        op.object("file").delete().await?;
+        assert!(!Operator::new(replica).object("file").is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mirror_quorum() -> anyhow::Result<()> {
+        let primary = memory::Backend::build().finish().await?;
+        let broken: Arc<dyn Accessor> = BrokenService::new(ErrorKind::Interrupted);
+
+        let op = Operator::new(primary.clone()).layer(MirrorLayer::new(vec![broken.clone()]));
+        let err = op.object("file").write("Hello, World!").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+        assert!(err.to_string().contains("replica #1"), "{}", err);
+
+        let op = Operator::new(primary).layer(MirrorLayer::new(vec![broken]).with_quorum(1));
+        op.object("file").write("Hello, World!").await?;
+        op.object("file").delete().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mirror_fail_over() -> anyhow::Result<()> {
+        let replica = memory::Backend::build().finish().await?;
+        Operator::new(replica.clone())
+            .object("file")
+            .write("Hello, World!")
+            .await?;
+
+        let op = Operator::new(BrokenService::new(ErrorKind::Interrupted))
+            .layer(MirrorLayer::new(vec![replica]));
+        assert_eq!(op.object("file").read().await?, b"Hello, World!");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mirror_not_found() -> anyhow::Result<()> {
+        let primary = memory::Backend::build().finish().await?;
+        let replica = memory::Backend::build().finish().await?;
+        // The replica is stale, the object has been deleted from primary.
+        Operator::new(replica.clone())
+            .object("file")
+            .write("Hello, World!")
+            .await?;
+
+        let op = Operator::new(primary).layer(MirrorLayer::new(vec![replica]));
+        let err = op.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "quorum must not be greater than the count of accessors")]
+    fn test_mirror_invalid_quorum() {
+        let _ = MirrorLayer::new(vec![]).with_quorum(2);
+    }
+}
@@ -12,8 +12,108 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Providing Layer implementations.
+//!
+//! All layers in this module can be applied to an [`Operator`][crate::Operator]
+//! via [`Operator::layer()`][crate::Operator::layer].
+
 mod layer;
 pub use layer::Layer;
 
+mod cache;
+pub use cache::CacheLayer;
+
+mod block_cache;
+pub use block_cache::BlockCacheLayer;
+
+mod cache_index;
+
+mod stat_cache;
+pub use stat_cache::StatCacheLayer;
+
+mod timeout;
+pub use timeout::TimeoutLayer;
+
+mod hedge;
+pub use hedge::HedgeLayer;
+
+mod singleflight;
+pub use singleflight::SingleflightLayer;
+
+mod chunk;
+pub use chunk::ChunkLayer;
+
+mod prefix;
+pub use prefix::PrefixLayer;
+
+mod overlay;
+pub use overlay::OverlayLayer;
+
+mod mirror;
+pub use mirror::MirrorLayer;
+
+mod failover;
+pub use failover::FailoverLayer;
+
+mod fallback;
+
+mod policy;
+pub use policy::PolicyLayer;
+
+mod read_only;
+pub use read_only::ReadOnlyLayer;
+
+mod quota;
+pub use quota::QuotaExceeded;
+pub use quota::QuotaLayer;
+
+mod write_back;
+pub use write_back::WriteBackLayer;
+
+mod trash;
+pub use trash::Trash;
+pub use trash::TrashEntry;
+pub use trash::TrashLayer;
+
+#[cfg(feature = "audit")]
+mod audit;
+#[cfg(feature = "audit")]
+pub use audit::AuditLayer;
+#[cfg(feature = "audit")]
+pub use audit::AuditRecord;
+#[cfg(feature = "audit")]
+pub use audit::AuditSink;
+#[cfg(feature = "audit")]
+pub use audit::ObjectSink;
+
+#[cfg(feature = "chaos")]
+mod chaos;
+#[cfg(feature = "chaos")]
+pub use chaos::ChaosLayer;
+
+#[cfg(feature = "checksum")]
+mod checksum;
+#[cfg(feature = "checksum")]
+pub use checksum::ChecksumLayer;
+
+#[cfg(feature = "encryption")]
+mod encryption;
+#[cfg(feature = "encryption")]
+pub use encryption::AesKeyProvider;
+#[cfg(feature = "encryption")]
+pub use encryption::EncryptionLayer;
+#[cfg(feature = "encryption")]
+pub use encryption::KeyProvider;
+
+#[cfg(feature = "record")]
+mod record;
+#[cfg(feature = "record")]
+pub use record::RecordLayer;
+#[cfg(feature = "record")]
+pub use record::Replayer;
+
 #[cfg(feature = "retry")]
 mod retry;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,373 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::future;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use log::debug;
+
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+
+/// Prefix of whiteout objects of files in upper layer.
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// Prefix of whiteout objects of dirs in upper layer.
+const DIR_WHITEOUT_PREFIX: &str = ".whd.";
+
+/// OverlayLayer will stack a writable upper layer on top of the underlying
+/// accessor, which is used as a read-only lower layer.
+///
+/// # Behavior
+///
+/// - `read` and `stat` check the upper layer first, then the lower layer.
+/// - `write`, `create` and `delete` only happen on the upper layer, the
+///   lower layer will never be changed.
+/// - Deleting objects that exist in the lower layer will create a whiteout
+///   object named `.wh.<name>` for files or `.whd.<name>` for dirs in the
+///   same dir of the upper layer, which hides the object in the lower
+///   layer.
+/// - `list` merges both layers, objects in the upper layer win.
+///
+/// Whiteouts only hide the object itself, objects under a deleted dir in
+/// the lower layer are still visible.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::OverlayLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let base = memory::Backend::build().finish().await?;
+/// let upper = memory::Backend::build().finish().await?;
+/// let op = Operator::new(base).layer(OverlayLayer::new(upper));
+///
+/// op.object("file").write("Hello, World!").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OverlayLayer {
+    upper: Arc<dyn Accessor>,
+}
+
+impl OverlayLayer {
+    /// Create a new OverlayLayer with the writable `upper` layer.
+    pub fn new(upper: Arc<dyn Accessor>) -> Self {
+        OverlayLayer { upper }
+    }
+}
+
+impl Layer for OverlayLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(OverlayAccessor {
+            upper: self.upper.clone(),
+            lower: inner,
+        })
+    }
+}
+
+/// Build the whiteout path of the input path.
+fn whiteout_path(path: &str) -> String {
+    let prefix = if path.ends_with('/') {
+        DIR_WHITEOUT_PREFIX
+    } else {
+        WHITEOUT_PREFIX
+    };
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(idx) => format!("{}{}{}", &path[..=idx], prefix, &path[idx + 1..]),
+        None => format!("{}{}", prefix, path),
+    }
+}
+
+/// Get the name of the entry hidden by the whiteout entry, dirs end
+/// with `/`.
+fn whiteout_target(de: &DirEntry) -> Option<String> {
+    let name = entry_name(de);
+    if let Some(v) = name.strip_prefix(DIR_WHITEOUT_PREFIX) {
+        return Some(format!("{v}/"));
+    }
+    name.strip_prefix(WHITEOUT_PREFIX).map(|v| v.to_string())
+}
+
+/// Get the name of entry without the trailing `/`.
+fn entry_name(de: &DirEntry) -> &str {
+    de.name().trim_end_matches('/')
+}
+
+#[derive(Debug, Clone)]
+struct OverlayAccessor {
+    upper: Arc<dyn Accessor>,
+    lower: Arc<dyn Accessor>,
+}
+
+impl OverlayAccessor {
+    async fn is_whiteout(&self, path: &str) -> Result<bool> {
+        match self.upper.stat(&OpStat::new(&whiteout_path(path))?).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Build the error for objects that have been whiteout.
+    fn removed(&self, op: &'static str, path: &str) -> Error {
+        Error::new(
+            ErrorKind::NotFound,
+            ObjectError::new(op, path, anyhow!("object has been removed in upper layer")),
+        )
+    }
+
+    async fn remove_whiteout(&self, path: &str) -> Result<()> {
+        if path == "/" {
+            return Ok(());
+        }
+        self.upper
+            .delete(&OpDelete::new(&whiteout_path(path))?)
+            .await
+    }
+
+    /// List dir in the accessor, not existing dirs are treated as empty.
+    async fn list_or_empty(acc: &Arc<dyn Accessor>, args: &OpList) -> Result<Option<DirStreamer>> {
+        match acc.list(args).await {
+            Ok(s) => Ok(Some(s)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl Accessor for OverlayAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.lower.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.remove_whiteout(args.path()).await?;
+        self.upper.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        match self.upper.read(args).await {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if self.is_whiteout(args.path()).await? {
+                    return Err(self.removed("read", args.path()));
+                }
+                self.lower.read(args).await
+            }
+            v => v,
+        }
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.remove_whiteout(args.path()).await?;
+        self.upper.write(args).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        match self.upper.stat(args).await {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if self.is_whiteout(args.path()).await? {
+                    return Err(self.removed("stat", args.path()));
+                }
+                self.lower.stat(args).await
+            }
+            v => v,
+        }
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let path = args.path();
+        self.upper.delete(args).await?;
+
+        if path == "/" {
+            return Ok(());
+        }
+        match self.lower.stat(&OpStat::new(path)?).await {
+            Ok(_) => {
+                debug!("object {} exists in lower layer, creating whiteout", path);
+                self.upper
+                    .create(&OpCreate::new(&whiteout_path(path), ObjectMode::FILE)?)
+                    .await
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let upper = Self::list_or_empty(&self.upper, args).await?;
+        let lower = Self::list_or_empty(&self.lower, args).await?;
+
+        if upper.is_none() && lower.is_none() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                ObjectError::new("list", args.path(), anyhow!("dir not found in any layer")),
+            ));
+        }
+
+        // Upper layer is expected to be small, collect it to decide which
+        // entries in lower layer should be hidden.
+        let mut entries = Vec::new();
+        // Entries in upper layer hide lower ones with the same name in any
+        // mode, while whiteouts only hide the same mode.
+        let mut shadowed = HashSet::new();
+        let mut removed = HashSet::new();
+        if let Some(upper) = upper {
+            let upper: Vec<DirEntry> = upper.try_collect().await?;
+            for de in upper {
+                match whiteout_target(&de) {
+                    Some(name) => {
+                        removed.insert(name);
+                    }
+                    None => {
+                        shadowed.insert(entry_name(&de).to_string());
+                        entries.push(de);
+                    }
+                }
+            }
+        }
+
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        let upper = {
+            let acc = acc.clone();
+            stream::iter(
+                entries
+                    .into_iter()
+                    .map(move |de| Ok(DirEntry::new(acc.clone(), de.mode(), de.path()))),
+            )
+        };
+
+        match lower {
+            None => Ok(Box::new(upper)),
+            Some(lower) => {
+                let lower = lower
+                    .try_filter(move |de| {
+                        future::ready(
+                            !shadowed.contains(entry_name(de)) && !removed.contains(de.name()),
+                        )
+                    })
+                    .map_ok(move |de| DirEntry::new(acc.clone(), de.mode(), de.path()));
+                Ok(Box::new(upper.chain(lower)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    async fn list(op: &Operator, path: &str) -> anyhow::Result<Vec<String>> {
+        let entries: Vec<DirEntry> = op.object(path).list().await?.try_collect().await?;
+        let mut paths: Vec<String> = entries.iter().map(|de| de.path().to_string()).collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    #[test]
+    fn test_whiteout_path() {
+        assert_eq!(whiteout_path("file"), ".wh.file");
+        assert_eq!(whiteout_path("dir/file"), "dir/.wh.file");
+        assert_eq!(whiteout_path("dir/sub/"), "dir/.whd.sub");
+        assert_ne!(whiteout_path("dir/sub/"), whiteout_path("dir/sub"));
+    }
+
+    #[tokio::test]
+    async fn test_overlay() -> anyhow::Result<()> {
+        let lower = Operator::new(memory::Backend::build().finish().await?);
+        lower.object("base").write("Hello, Base!").await?;
+        lower.object("shared").write("Hello, Lower!").await?;
+
+        let upper = memory::Backend::build().finish().await?;
+        let op = lower.clone().layer(OverlayLayer::new(upper));
+
+        assert_eq!(op.object("base").read().await?, b"Hello, Base!");
+
+        op.object("shared").write("Hello, Upper!").await?;
+        op.object("new").write("Hello, New!").await?;
+        assert_eq!(op.object("shared").read().await?, b"Hello, Upper!");
+        assert_eq!(lower.object("shared").read().await?, b"Hello, Lower!");
+        assert!(!lower.object("new").is_exist().await?);
+
+        assert_eq!(list(&op, "/").await?, vec!["base", "new", "shared"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overlay_whiteout() -> anyhow::Result<()> {
+        let lower = Operator::new(memory::Backend::build().finish().await?);
+        lower.object("base").write("Hello, Base!").await?;
+        lower.object("other").write("Hello, Other!").await?;
+
+        let upper = memory::Backend::build().finish().await?;
+        let op = lower.clone().layer(OverlayLayer::new(upper.clone()));
+        let upper = Operator::new(upper);
+
+        op.object("base").delete().await?;
+        assert!(lower.object("base").is_exist().await?);
+        assert!(upper.object(".wh.base").is_exist().await?);
+
+        let err = op.object("base").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(!op.object("base").is_exist().await?);
+        assert_eq!(list(&op, "/").await?, vec!["other"]);
+
+        op.object("base").write("Hello, Again!").await?;
+        assert!(!upper.object(".wh.base").is_exist().await?);
+        assert_eq!(op.object("base").read().await?, b"Hello, Again!");
+        assert_eq!(list(&op, "/").await?, vec!["base", "other"]);
+
+        // Deleting a dir doesn't hide the file with the same name.
+        lower.object("sub").write("Hello, Sub!").await?;
+        lower.object("sub/").create().await?;
+        op.object("sub/").delete().await?;
+        assert!(upper.object(".whd.sub").is_exist().await?);
+        assert_eq!(op.object("sub").read().await?, b"Hello, Sub!");
+        assert_eq!(list(&op, "/").await?, vec!["base", "other", "sub"]);
+
+        Ok(())
+    }
+}
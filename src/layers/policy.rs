@@ -0,0 +1,394 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::future;
+use futures::StreamExt;
+use log::debug;
+
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::Operation;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+
+/// PolicyLayer will only allow operations on paths that match the rules.
+///
+/// # Rules
+///
+/// Rules are glob patterns of paths (relative to the root) per operation:
+///
+/// - `?` matches any single character except `/`.
+/// - `*` matches any characters except `/`.
+/// - `**` matches any characters including `/`.
+///
+/// An operation is allowed if the path matches any `allow` rule and doesn't
+/// match any `deny` rule of the operation. All others will be rejected with
+/// `PermissionDenied` without touching the underlying accessor.
+///
+/// Paths that contain `..` will always be rejected, as services may resolve
+/// them into paths that don't match the rules.
+///
+/// # Listing
+///
+/// Entries returned by `list` will be filtered, only entries that allowed
+/// by any operation, and dirs that may contain allowed entries will be kept.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::PolicyLayer;
+/// use opendal::ops::Operation;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(
+///     PolicyLayer::new()
+///         .allow(&[Operation::Read, Operation::Stat, Operation::List], "datasets/**")
+///         .allow(&[Operation::Write, Operation::Delete], "scratch/**")
+///         .deny(&[Operation::Read], "datasets/private/**"),
+/// );
+///
+/// op.object("scratch/file").write("Hello, World!").await?;
+/// assert!(op.object("datasets/file").write("Hello, World!").await.is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PolicyLayer {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    ops: Vec<Operation>,
+    pattern: String,
+}
+
+impl PolicyLayer {
+    /// Create a new PolicyLayer which denies everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow `ops` on paths that match `pattern`.
+    #[must_use]
+    pub fn allow(mut self, ops: &[Operation], pattern: &str) -> Self {
+        self.rules.push(Rule {
+            allow: true,
+            ops: ops.to_vec(),
+            pattern: pattern.trim_start_matches('/').to_string(),
+        });
+        self
+    }
+
+    /// Deny `ops` on paths that match `pattern`, deny rules take precedence
+    /// over allow rules.
+    #[must_use]
+    pub fn deny(mut self, ops: &[Operation], pattern: &str) -> Self {
+        self.rules.push(Rule {
+            allow: false,
+            ops: ops.to_vec(),
+            pattern: pattern.trim_start_matches('/').to_string(),
+        });
+        self
+    }
+}
+
+impl Layer for PolicyLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(PolicyAccessor {
+            inner,
+            rules: Arc::new(self.rules.clone()),
+        })
+    }
+}
+
+/// Match `path` against the glob `pattern`.
+fn glob_match(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| glob_match(rest, &path[i..])),
+        ['*', rest @ ..] => {
+            for i in 0..=path.len() {
+                if glob_match(rest, &path[i..]) {
+                    return true;
+                }
+                if i < path.len() && path[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => match path {
+            [c, remain @ ..] if *c != '/' => glob_match(rest, remain),
+            _ => false,
+        },
+        [p, rest @ ..] => match path {
+            [c, remain @ ..] if c == p => glob_match(rest, remain),
+            _ => false,
+        },
+    }
+}
+
+/// Get the path used to match rules, the root will be an empty string.
+fn rule_path(path: &str) -> &str {
+    if path == "/" {
+        ""
+    } else {
+        path
+    }
+}
+
+/// Check if the dir may contain paths that match `pattern`, which means
+/// the literal prefix of pattern and the dir are prefix of each other.
+///
+/// Patterns without wildcards can only match paths under the dir.
+fn may_contain(pattern: &str, dir: &str) -> bool {
+    match pattern.find(['*', '?']) {
+        Some(idx) => {
+            let literal = &pattern[..idx];
+            literal.starts_with(dir) || dir.starts_with(literal)
+        }
+        None => pattern.starts_with(dir),
+    }
+}
+
+fn is_allowed(rules: &[Rule], op: Operation, path: &str) -> bool {
+    let path: Vec<char> = rule_path(path).chars().collect();
+
+    let mut allowed = false;
+    for rule in rules.iter().filter(|r| r.ops.contains(&op)) {
+        let pattern: Vec<char> = rule.pattern.chars().collect();
+        if !glob_match(&pattern, &path) {
+            continue;
+        }
+        if !rule.allow {
+            return false;
+        }
+        allowed = true;
+    }
+    allowed
+}
+
+/// Check if the path contains `..` which may escape the matched rules.
+fn is_escaping(path: &str) -> bool {
+    path.split('/').any(|v| v.trim() == "..")
+}
+
+/// Check if the entry should be visible in listing.
+fn is_visible(rules: &[Rule], de: &DirEntry) -> bool {
+    let ops = [
+        Operation::Create,
+        Operation::Read,
+        Operation::Write,
+        Operation::Stat,
+        Operation::Delete,
+        Operation::List,
+    ];
+    if ops.iter().any(|op| is_allowed(rules, *op, de.path())) {
+        return true;
+    }
+
+    de.mode() == ObjectMode::DIR
+        && rules
+            .iter()
+            .any(|r| r.allow && may_contain(&r.pattern, rule_path(de.path())))
+}
+
+#[derive(Debug, Clone)]
+struct PolicyAccessor {
+    inner: Arc<dyn Accessor>,
+    rules: Arc<Vec<Rule>>,
+}
+
+impl PolicyAccessor {
+    fn check(&self, op: Operation, path: &str) -> Result<()> {
+        if is_escaping(path) {
+            debug!("object {} {} denied by policy: escaping path", path, op);
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                ObjectError::new(op.as_str(), path, anyhow!("path must not contain `..`")),
+            ));
+        }
+
+        if is_allowed(&self.rules, op, path) {
+            return Ok(());
+        }
+
+        debug!("object {} {} denied by policy", path, op);
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            ObjectError::new(op.as_str(), path, anyhow!("denied by policy")),
+        ))
+    }
+}
+
+#[async_trait]
+impl Accessor for PolicyAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.check(Operation::Create, args.path())?;
+        self.inner.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.check(Operation::Read, args.path())?;
+        self.inner.read(args).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.check(Operation::Write, args.path())?;
+        self.inner.write(args).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.check(Operation::Stat, args.path())?;
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.check(Operation::Delete, args.path())?;
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.check(Operation::List, args.path())?;
+
+        let s = self.inner.list(args).await?;
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        let rules = self.rules.clone();
+        Ok(Box::new(
+            s.filter(move |de| {
+                future::ready(match de {
+                    Ok(de) => is_visible(&rules, de),
+                    Err(_) => true,
+                })
+            })
+            .map(move |de| de.map(|de| DirEntry::new(acc.clone(), de.mode(), de.path()))),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    fn glob(pattern: &str, path: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let path: Vec<char> = path.chars().collect();
+        glob_match(&pattern, &path)
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob("datasets/**", "datasets/"));
+        assert!(glob("datasets/**", "datasets/a/b/c"));
+        assert!(!glob("datasets/**", "datasets"));
+        assert!(!glob("datasets/**", "scratch/a"));
+        assert!(glob("datasets/*.csv", "datasets/a.csv"));
+        assert!(!glob("datasets/*.csv", "datasets/a/b.csv"));
+        assert!(glob("**/*.csv", "datasets/a/b.csv"));
+        assert!(glob("file-?", "file-1"));
+        assert!(!glob("file-?", "file-12"));
+    }
+
+    #[tokio::test]
+    async fn test_policy() -> anyhow::Result<()> {
+        let root = Operator::new(memory::Backend::build().finish().await?);
+        root.object("datasets/a").write("Hello, A!").await?;
+        root.object("datasets/private/b").write("Hello, B!").await?;
+
+        let op = root.clone().layer(
+            PolicyLayer::new()
+                .allow(&[Operation::Read, Operation::List], "datasets/**")
+                .allow(&[Operation::Write], "scratch/**")
+                .deny(&[Operation::Read], "datasets/private/**"),
+        );
+
+        assert_eq!(op.object("datasets/a").read().await?, b"Hello, A!");
+        op.object("scratch/c").write("Hello, C!").await?;
+
+        let err = op.object("datasets/private/b").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        let err = op.object("datasets/a").write("Hello").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        let err = op.object("datasets/a").delete().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(root.object("datasets/a").read().await?, b"Hello, A!");
+
+        // Paths with `..` should be denied even if they match rules.
+        let err = op.object("scratch/../x").write("Hello").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(!root.object("x").is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_policy_list() -> anyhow::Result<()> {
+        let root = Operator::new(memory::Backend::build().finish().await?);
+        root.object("datasets/").create().await?;
+        root.object("models/").create().await?;
+        root.object("scratch/").create().await?;
+        root.object("secret").write("Hello, World!").await?;
+
+        let op = root.clone().layer(
+            PolicyLayer::new()
+                .allow(&[Operation::List], "/")
+                .allow(&[Operation::Read], "datasets/**")
+                .allow(&[Operation::Write], "scratch/tmp/**"),
+        );
+
+        let entries: Vec<DirEntry> = op.object("/").list().await?.try_collect().await?;
+        let mut paths: Vec<&str> = entries.iter().map(|de| de.path()).collect();
+        paths.sort_unstable();
+        assert_eq!(paths, vec!["datasets/", "scratch/"]);
+
+        let err = op.object("models/").list().await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::StreamExt;
+
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::path::normalize_path;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// PrefixLayer will rebase all paths under the given prefix.
+///
+/// Operators with this layer behave like operators whose root is the
+/// prefix, without building a new backend.
+///
+/// # Behavior
+///
+/// - Paths that contain `..` will be rejected with `PermissionDenied` so
+///   that they can't escape the prefix.
+/// - [`AccessorMetadata::root`] will be the new root.
+/// - Paths of [`DirEntry`] will be relative to the new root.
+///
+/// [`Operator::subdir`][crate::Operator::subdir] is a shortcut of this layer.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::PrefixLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor);
+/// let tenant = op.clone().layer(PrefixLayer::new("tenant-a/")?);
+///
+/// tenant.object("file").write("Hello, World!").await?;
+/// assert!(op.object("tenant-a/file").is_exist().await?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrefixLayer {
+    prefix: String,
+}
+
+impl PrefixLayer {
+    /// Create a new PrefixLayer.
+    ///
+    /// `prefix` will be normalized as a dir path, an error will be returned
+    /// if it contains `..`.
+    pub fn new(prefix: &str) -> Result<Self> {
+        if is_escaping(prefix) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                anyhow!("prefix {prefix} must not contain `..`"),
+            ));
+        }
+
+        let mut prefix = normalize_path(prefix);
+        if prefix == "/" {
+            prefix.clear();
+        } else if !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+
+        Ok(PrefixLayer { prefix })
+    }
+}
+
+impl Layer for PrefixLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(PrefixAccessor {
+            inner,
+            prefix: self.prefix.clone(),
+        })
+    }
+}
+
+/// Check if the path contains `..` which may escape the prefix.
+fn is_escaping(path: &str) -> bool {
+    path.split('/').any(|v| v.trim() == "..")
+}
+
+#[derive(Debug, Clone)]
+struct PrefixAccessor {
+    inner: Arc<dyn Accessor>,
+    prefix: String,
+}
+
+impl PrefixAccessor {
+    /// Build the path in underlying accessor.
+    fn abs_path(&self, op: &'static str, path: &str) -> Result<String> {
+        if is_escaping(path) {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                ObjectError::new(op, path, anyhow!("path escapes the prefix")),
+            ));
+        }
+
+        if path == "/" && !self.prefix.is_empty() {
+            Ok(self.prefix.clone())
+        } else {
+            Ok(format!("{}{}", self.prefix, path))
+        }
+    }
+}
+
+/// Build the path relative to the prefix.
+fn rel_path(prefix: &str, path: &str) -> String {
+    match path.strip_prefix(prefix) {
+        Some("") => "/".to_string(),
+        Some(v) => v.to_string(),
+        None => path.to_string(),
+    }
+}
+
+#[async_trait]
+impl Accessor for PrefixAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        let mut meta = self.inner.metadata();
+        let root = format!("{}{}", meta.root(), self.prefix);
+        meta.set_root(&root);
+        meta
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        let p = self.abs_path("create", args.path())?;
+        self.inner.create(&args.clone().with_path(&p)).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let p = self.abs_path("read", args.path())?;
+        self.inner.read(&args.clone().with_path(&p)).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        let p = self.abs_path("write", args.path())?;
+        self.inner.write(&args.clone().with_path(&p)).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        let p = self.abs_path("stat", args.path())?;
+        self.inner.stat(&args.clone().with_path(&p)).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let p = self.abs_path("delete", args.path())?;
+        self.inner.delete(&args.clone().with_path(&p)).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let p = self.abs_path("list", args.path())?;
+        let s = self.inner.list(&args.clone().with_path(&p)).await?;
+
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        let prefix = self.prefix.clone();
+        Ok(Box::new(s.map(move |de| {
+            de.map(|de| DirEntry::new(acc.clone(), de.mode(), &rel_path(&prefix, de.path())))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    #[tokio::test]
+    async fn test_prefix() -> anyhow::Result<()> {
+        let op = Operator::new(memory::Backend::build().finish().await?);
+        let sub = op.subdir("/tenant-a")?;
+
+        sub.object("dir/").create().await?;
+        sub.object("dir/file").write("Hello, World!").await?;
+        assert_eq!(
+            op.object("tenant-a/dir/file").read().await?,
+            b"Hello, World!"
+        );
+        assert!(!op.object("dir/file").is_exist().await?);
+
+        assert_eq!(
+            sub.metadata().root(),
+            format!("{}tenant-a/", op.metadata().root())
+        );
+
+        let entries: Vec<DirEntry> = sub.object("/").list().await?.try_collect().await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path(), "dir/");
+
+        let entries: Vec<DirEntry> = entries[0]
+            .clone()
+            .into_object()
+            .list()
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(entries[0].path(), "dir/file");
+        assert_eq!(
+            entries[0].clone().into_object().read().await?,
+            b"Hello, World!"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefix_escape() -> anyhow::Result<()> {
+        let op = Operator::new(memory::Backend::build().finish().await?);
+        op.object("secret").write("Hello, World!").await?;
+
+        let sub = op.subdir("tenant-a/")?;
+        let err = sub.object("../secret").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        let err = sub.object("dir/../../secret").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        assert!(op.subdir("tenant-a/../").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,622 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use async_trait::async_trait;
+use futures::ready;
+use futures::AsyncWrite;
+use futures::TryStreamExt;
+use log::debug;
+use parking_lot::Mutex;
+use thiserror::Error;
+use tokio::sync::OwnedMutexGuard;
+
+use crate::error::other;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+use crate::Operator;
+
+/// QuotaExceeded will be returned (wrapped in [`std::io::Error`]) while
+/// writing objects that exceed the quota.
+///
+/// # Examples
+///
+/// ```
+/// # use std::io::Error;
+/// use opendal::layers::QuotaExceeded;
+///
+/// fn is_quota_exceeded(err: &Error) -> bool {
+///     err.get_ref()
+///         .map(|e| e.is::<QuotaExceeded>())
+///         .unwrap_or_default()
+/// }
+/// ```
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("quota exceeded: (path: {path}, prefix: {prefix}, {resource} limit: {limit})")]
+pub struct QuotaExceeded {
+    path: String,
+    prefix: String,
+    resource: &'static str,
+    limit: u64,
+}
+
+impl QuotaExceeded {
+    /// Path of the object that exceeds the quota.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Prefix of the exceeded quota, root will be `/`.
+    pub fn prefix(&self) -> &str {
+        if self.prefix.is_empty() {
+            "/"
+        } else {
+            &self.prefix
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    prefix: String,
+    max_bytes: Option<u64>,
+    max_objects: Option<u64>,
+    bytes: u64,
+    objects: u64,
+}
+
+/// Changes of usage caused by an operation.
+#[derive(Debug, Clone, Copy, Default)]
+struct Delta {
+    bytes: i64,
+    objects: i64,
+}
+
+fn apply(v: u64, delta: i64) -> u64 {
+    if delta >= 0 {
+        v.saturating_add(delta as u64)
+    } else {
+        v.saturating_sub(delta.unsigned_abs())
+    }
+}
+
+#[derive(Debug, Default)]
+struct QuotaState {
+    usages: Mutex<Vec<Usage>>,
+    /// Locks of paths that are being changed, so that the size of existing
+    /// objects won't change until the usage has been updated.
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl QuotaState {
+    /// Lock the path until the returned guard is dropped.
+    async fn lock(&self, path: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock();
+            // Drop locks that are not held by anyone.
+            locks.retain(|_, v| Arc::strong_count(v) > 1);
+            locks.entry(path.to_string()).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+
+    /// Check quotas of all prefixes that path belongs to, and apply the
+    /// delta if none of them exceeded.
+    fn reserve(&self, path: &str, delta: Delta) -> Result<()> {
+        let mut usages = self.usages.lock();
+
+        for u in usages.iter().filter(|u| path.starts_with(&u.prefix)) {
+            let exceeded = |resource, limit| QuotaExceeded {
+                path: path.to_string(),
+                prefix: u.prefix.clone(),
+                resource,
+                limit,
+            };
+
+            match u.max_bytes {
+                Some(limit) if delta.bytes > 0 && apply(u.bytes, delta.bytes) > limit => {
+                    return Err(other(exceeded("bytes", limit)));
+                }
+                _ => {}
+            }
+            match u.max_objects {
+                Some(limit) if delta.objects > 0 && apply(u.objects, delta.objects) > limit => {
+                    return Err(other(exceeded("objects", limit)));
+                }
+                _ => {}
+            }
+        }
+
+        apply_all(&mut usages, path, delta);
+        Ok(())
+    }
+
+    /// Apply the delta without checking quotas.
+    fn apply(&self, path: &str, delta: Delta) {
+        apply_all(&mut self.usages.lock(), path, delta)
+    }
+}
+
+fn apply_all(usages: &mut [Usage], path: &str, delta: Delta) {
+    for u in usages.iter_mut().filter(|u| path.starts_with(&u.prefix)) {
+        u.bytes = apply(u.bytes, delta.bytes);
+        u.objects = apply(u.objects, delta.objects);
+    }
+}
+
+/// QuotaLayer will limit bytes and objects stored under prefixes.
+///
+/// # Behavior
+///
+/// - `write` and `create` will fail with [`QuotaExceeded`] if the quota of
+///   any prefix the path belongs to will be exceeded.
+/// - Usage will be reserved by the size of `write` before writing, and
+///   released if the writer failed or dropped without closing.
+/// - Overwriting and `delete` will `stat` the object first to adjust the
+///   usage by its size.
+/// - Operations on the same path will be serialized, a `write` holds the
+///   path until the writer is closed or dropped.
+/// - Usage starts from zero, call [`QuotaLayer::scan`] to seed it from the
+///   existing objects.
+///
+/// Usage is tracked inside the process only, writes from other processes
+/// will not be counted until next scan.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::QuotaLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor);
+///
+/// let quota = QuotaLayer::new()
+///     .with_max_bytes("/", 1024 * 1024 * 1024)
+///     .with_max_objects("tenant-a/", 1000);
+/// quota.scan(&op).await?;
+///
+/// let op = op.layer(quota);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QuotaLayer {
+    state: Arc<QuotaState>,
+}
+
+impl QuotaLayer {
+    /// Create a new QuotaLayer without any quota.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update(self, prefix: &str, f: impl FnOnce(&mut Usage)) -> Self {
+        let prefix = prefix.trim_start_matches('/').to_string();
+        {
+            let mut usages = self.state.usages.lock();
+            match usages.iter_mut().find(|u| u.prefix == prefix) {
+                Some(u) => f(u),
+                None => {
+                    let mut u = Usage {
+                        prefix,
+                        ..Default::default()
+                    };
+                    f(&mut u);
+                    usages.push(u);
+                }
+            }
+        }
+        self
+    }
+
+    /// Limit total bytes of objects under `prefix`, use `/` for all objects.
+    #[must_use]
+    pub fn with_max_bytes(self, prefix: &str, max_bytes: u64) -> Self {
+        self.update(prefix, |u| u.max_bytes = Some(max_bytes))
+    }
+
+    /// Limit the count of objects under `prefix`, use `/` for all objects.
+    #[must_use]
+    pub fn with_max_objects(self, prefix: &str, max_objects: u64) -> Self {
+        self.update(prefix, |u| u.max_objects = Some(max_objects))
+    }
+
+    /// Get the usage of `prefix` in `(bytes, objects)`.
+    ///
+    /// Returns `None` if there is no quota on this prefix.
+    pub fn usage(&self, prefix: &str) -> Option<(u64, u64)> {
+        let prefix = prefix.trim_start_matches('/');
+        self.state
+            .usages
+            .lock()
+            .iter()
+            .find(|u| u.prefix == prefix)
+            .map(|u| (u.bytes, u.objects))
+    }
+
+    /// Scan objects under all prefixes of quotas to seed the usage.
+    ///
+    /// `op` should be the operator without this layer.
+    pub async fn scan(&self, op: &Operator) -> Result<()> {
+        let prefixes: Vec<String> = self
+            .state
+            .usages
+            .lock()
+            .iter()
+            .map(|u| u.prefix.clone())
+            .collect();
+
+        for prefix in prefixes {
+            let dir = if prefix.is_empty() { "/" } else { &prefix };
+            let (mut bytes, mut objects) = (0, 0);
+
+            if prefix.is_empty() || prefix.ends_with('/') {
+                let mut s = op.batch().walk_top_down(dir)?;
+                while let Some(de) = s.try_next().await? {
+                    if de.mode() != ObjectMode::FILE {
+                        continue;
+                    }
+                    bytes += de.metadata().await?.content_length();
+                    objects += 1;
+                }
+            } else {
+                match op.object(&prefix).metadata().await {
+                    Ok(meta) => {
+                        bytes = meta.content_length();
+                        objects = 1;
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            debug!(
+                "quota prefix {:?} scanned: bytes {}, objects {}",
+                prefix, bytes, objects
+            );
+            let mut usages = self.state.usages.lock();
+            if let Some(u) = usages.iter_mut().find(|u| u.prefix == prefix) {
+                u.bytes = bytes;
+                u.objects = objects;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Layer for QuotaLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(QuotaAccessor {
+            inner,
+            state: self.state.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct QuotaAccessor {
+    inner: Arc<dyn Accessor>,
+    state: Arc<QuotaState>,
+}
+
+impl QuotaAccessor {
+    /// Get the size of existing file, returns `None` if not exist.
+    async fn existing_size(&self, path: &str) -> Result<Option<u64>> {
+        match self.inner.stat(&OpStat::new(path)?).await {
+            Ok(meta) if meta.mode() == ObjectMode::FILE => Ok(Some(meta.content_length())),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl Accessor for QuotaAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        if args.mode() != ObjectMode::FILE {
+            return self.inner.create(args).await;
+        }
+
+        let _guard = self.state.lock(args.path()).await;
+        let delta = match self.existing_size(args.path()).await? {
+            Some(size) => Delta {
+                bytes: -(size as i64),
+                objects: 0,
+            },
+            None => Delta {
+                bytes: 0,
+                objects: 1,
+            },
+        };
+        self.state.reserve(args.path(), delta)?;
+
+        let res = self.inner.create(args).await;
+        if res.is_err() {
+            self.state.apply(
+                args.path(),
+                Delta {
+                    bytes: -delta.bytes,
+                    objects: -delta.objects,
+                },
+            );
+        }
+        res
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.inner.read(args).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        let guard = self.state.lock(args.path()).await;
+        let delta = match self.existing_size(args.path()).await? {
+            Some(size) => Delta {
+                bytes: args.size() as i64 - size as i64,
+                objects: 0,
+            },
+            None => Delta {
+                bytes: args.size() as i64,
+                objects: 1,
+            },
+        };
+        self.state.reserve(args.path(), delta)?;
+
+        match self.inner.write(args).await {
+            Ok(w) => Ok(Box::new(QuotaWriter {
+                inner: w,
+                state: self.state.clone(),
+                path: args.path().to_string(),
+                delta: Some(delta),
+                guard: Some(guard),
+            })),
+            Err(e) => {
+                self.state.apply(
+                    args.path(),
+                    Delta {
+                        bytes: -delta.bytes,
+                        objects: -delta.objects,
+                    },
+                );
+                Err(e)
+            }
+        }
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let _guard = self.state.lock(args.path()).await;
+        let size = self.existing_size(args.path()).await?;
+        self.inner.delete(args).await?;
+
+        if let Some(size) = size {
+            self.state.apply(
+                args.path(),
+                Delta {
+                    bytes: -(size as i64),
+                    objects: -1,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+/// QuotaWriter will release the reserved usage if writing failed.
+struct QuotaWriter {
+    inner: BytesWriter,
+    state: Arc<QuotaState>,
+    path: String,
+    /// Reserved delta, will be taken after the writer closed.
+    delta: Option<Delta>,
+    /// Lock of the path, will be released after the writer closed.
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl QuotaWriter {
+    fn release(&mut self) {
+        if let Some(delta) = self.delta.take() {
+            debug!("object {} write failed, releasing quota", self.path);
+            self.state.apply(
+                &self.path,
+                Delta {
+                    bytes: -delta.bytes,
+                    objects: -delta.objects,
+                },
+            );
+        }
+    }
+}
+
+impl AsyncWrite for QuotaWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        let res = ready!(Pin::new(&mut self.inner).poll_write(cx, buf));
+        if res.is_err() {
+            self.release();
+        }
+        Poll::Ready(res)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let res = ready!(Pin::new(&mut self.inner).poll_close(cx));
+        match res {
+            Ok(_) => self.delta = None,
+            Err(_) => self.release(),
+        }
+        self.guard = None;
+        Poll::Ready(res)
+    }
+}
+
+impl Drop for QuotaWriter {
+    fn drop(&mut self) {
+        self.release()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory;
+
+    fn is_quota_exceeded(err: &std::io::Error) -> bool {
+        err.get_ref()
+            .map(|e| e.is::<QuotaExceeded>())
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_quota() -> anyhow::Result<()> {
+        let quota = QuotaLayer::new()
+            .with_max_bytes("/", 20)
+            .with_max_objects("tenant-a/", 1);
+        let op = Operator::new(memory::Backend::build().finish().await?).layer(quota.clone());
+
+        op.object("tenant-a/a").write("Hello, World!").await?;
+        assert_eq!(quota.usage("/"), Some((13, 1)));
+        assert_eq!(quota.usage("tenant-a/"), Some((13, 1)));
+
+        let err = op.object("tenant-a/b").write("Hello").await.unwrap_err();
+        assert!(is_quota_exceeded(&err), "{}", err);
+        let err = op.object("b").write("Hello, World!").await.unwrap_err();
+        assert!(is_quota_exceeded(&err), "{}", err);
+        assert_eq!(quota.usage("/"), Some((13, 1)));
+
+        // Overwriting only counts the difference.
+        op.object("tenant-a/a")
+            .write("Hello, World! Hello!")
+            .await?;
+        assert_eq!(quota.usage("/"), Some((20, 1)));
+
+        op.object("tenant-a/a").delete().await?;
+        assert_eq!(quota.usage("/"), Some((0, 0)));
+        op.object("b").write("Hello, World!").await?;
+
+        Ok(())
+    }
+
+    /// SlowStat yields in `stat` so that concurrent operations interleave.
+    #[derive(Debug)]
+    struct SlowStat {
+        inner: Arc<dyn Accessor>,
+    }
+
+    #[async_trait]
+    impl Accessor for SlowStat {
+        async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+            self.inner.write(args).await
+        }
+        async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+            let meta = self.inner.stat(args).await;
+            tokio::task::yield_now().await;
+            meta
+        }
+        async fn delete(&self, args: &OpDelete) -> Result<()> {
+            self.inner.delete(args).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quota_concurrent() -> anyhow::Result<()> {
+        let quota = QuotaLayer::new().with_max_objects("/", 10);
+        let op = Operator::new(Arc::new(SlowStat {
+            inner: memory::Backend::build().finish().await?,
+        }))
+        .layer(quota.clone());
+
+        // Concurrent writes to the same new path should only count once.
+        let objects: Vec<_> = (0..8).map(|_| op.object("file")).collect();
+        let writes = objects.iter().map(|o| o.write("Hello"));
+        for res in futures::future::join_all(writes).await {
+            res?;
+        }
+        assert_eq!(quota.usage("/"), Some((5, 1)));
+
+        // Concurrent deletes and overwrites should never double subtract.
+        let o = op.object("file");
+        let (del, write, del2) = futures::join!(o.delete(), o.write("World!"), o.delete());
+        del?;
+        write?;
+        del2?;
+        let usage = quota.usage("/").unwrap();
+        assert!(usage == (0, 0) || usage == (6, 1), "{:?}", usage);
+        match o.metadata().await {
+            Ok(meta) => assert_eq!(usage, (meta.content_length(), 1)),
+            Err(_) => assert_eq!(usage, (0, 0)),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quota_scan() -> anyhow::Result<()> {
+        let op = Operator::new(memory::Backend::build().finish().await?);
+        op.object("dir/").create().await?;
+        op.object("dir/a").write("Hello").await?;
+        op.object("b").write("World!").await?;
+
+        let quota = QuotaLayer::new()
+            .with_max_bytes("/", 1024)
+            .with_max_objects("dir/", 10);
+        quota.scan(&op).await?;
+        assert_eq!(quota.usage("/"), Some((11, 2)));
+        assert_eq!(quota.usage("dir/"), Some((5, 1)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::StreamExt;
+
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::Operation;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// ReadOnlyLayer will reject all mutating operations with `PermissionDenied`.
+///
+/// Only `read`, `stat` and `list` will be sent to the underlying accessor,
+/// and [`AccessorMetadata::read_only`] will be `true`.
+///
+/// [`Operator::read_only`][crate::Operator::read_only] is a shortcut of this layer.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::ReadOnlyLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(ReadOnlyLayer);
+///
+/// assert!(op.metadata().read_only());
+/// assert!(op.object("file").write("Hello, World!").await.is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadOnlyLayer;
+
+impl Layer for ReadOnlyLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ReadOnlyAccessor { inner })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ReadOnlyAccessor {
+    inner: Arc<dyn Accessor>,
+}
+
+/// Build the error for mutating operations.
+fn read_only_error(op: Operation, path: &str) -> Error {
+    Error::new(
+        ErrorKind::PermissionDenied,
+        ObjectError::new(op.as_str(), path, anyhow!("operator is read-only")),
+    )
+}
+
+#[async_trait]
+impl Accessor for ReadOnlyAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        let mut meta = self.inner.metadata();
+        meta.set_read_only(true);
+        meta
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        Err(read_only_error(Operation::Create, args.path()))
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.inner.read(args).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        Err(read_only_error(Operation::Write, args.path()))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        Err(read_only_error(Operation::Delete, args.path()))
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let s = self.inner.list(args).await?;
+
+        // Entries must be rebuilt so that operations on them are read-only too.
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        Ok(Box::new(s.map(move |de| {
+            de.map(|de| DirEntry::new(acc.clone(), de.mode(), de.path()))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    #[tokio::test]
+    async fn test_read_only() -> anyhow::Result<()> {
+        let root = Operator::new(memory::Backend::build().finish().await?);
+        root.object("file").write("Hello, World!").await?;
+
+        let op = root.read_only();
+        assert!(op.metadata().read_only());
+        assert!(!root.metadata().read_only());
+
+        assert_eq!(op.object("file").read().await?, b"Hello, World!");
+
+        let err = op.object("file").write("Hello").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        let err = op.object("file").delete().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        let err = op.object("dir/").create().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(root.object("file").read().await?, b"Hello, World!");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_only_list() -> anyhow::Result<()> {
+        let root = Operator::new(memory::Backend::build().finish().await?);
+        root.object("file").write("Hello, World!").await?;
+
+        let op = root.read_only();
+        let entries: Vec<DirEntry> = op.object("/").list().await?.try_collect().await?;
+        assert_eq!(entries.len(), 1);
+
+        let err = entries[0].clone().into_object().delete().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,696 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::io::Write;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::io::Cursor;
+use futures::ready;
+use futures::stream;
+use futures::AsyncReadExt;
+use futures::AsyncWrite;
+use futures::TryStreamExt;
+use log::warn;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::error::other;
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+
+/// A line in the recording file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Metadata {
+        root: String,
+        name: String,
+        read_only: bool,
+    },
+    Call {
+        call: Call,
+        outcome: Outcome,
+    },
+}
+
+/// Call on the accessor, replay will match calls by all fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Call {
+    Create {
+        path: String,
+        mode: String,
+    },
+    Read {
+        path: String,
+        offset: Option<u64>,
+        size: Option<u64>,
+    },
+    Write {
+        path: String,
+        size: u64,
+    },
+    Stat {
+        path: String,
+    },
+    Delete {
+        path: String,
+    },
+    List {
+        path: String,
+    },
+}
+
+impl Call {
+    fn op(&self) -> &'static str {
+        match self {
+            Call::Create { .. } => "create",
+            Call::Read { .. } => "read",
+            Call::Write { .. } => "write",
+            Call::Stat { .. } => "stat",
+            Call::Delete { .. } => "delete",
+            Call::List { .. } => "list",
+        }
+    }
+
+    fn path(&self) -> &str {
+        match self {
+            Call::Create { path, .. }
+            | Call::Read { path, .. }
+            | Call::Write { path, .. }
+            | Call::Stat { path }
+            | Call::Delete { path }
+            | Call::List { path } => path,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Outcome {
+    Ok,
+    /// Read payload in base64.
+    Data {
+        data: String,
+    },
+    Metadata {
+        mode: String,
+        content_length: u64,
+        content_md5: Option<String>,
+        /// Unix timestamp in nanoseconds.
+        last_modified: Option<i128>,
+        etag: Option<String>,
+    },
+    Entries {
+        entries: Vec<(String, String)>,
+    },
+    Error {
+        kind: String,
+        message: String,
+    },
+}
+
+impl Outcome {
+    fn error(err: &Error) -> Self {
+        Outcome::Error {
+            kind: format!("{:?}", err.kind()),
+            message: err.to_string(),
+        }
+    }
+
+    fn metadata(meta: &ObjectMetadata) -> Self {
+        Outcome::Metadata {
+            mode: meta.mode().to_string(),
+            content_length: meta.content_length(),
+            content_md5: meta.content_md5().map(|v| v.to_string()),
+            last_modified: meta.last_modified().map(|v| v.unix_timestamp_nanos()),
+            etag: meta.etag().map(|v| v.to_string()),
+        }
+    }
+}
+
+fn parse_mode(s: &str) -> ObjectMode {
+    match s {
+        "file" => ObjectMode::FILE,
+        "dir" => ObjectMode::DIR,
+        _ => ObjectMode::Unknown,
+    }
+}
+
+fn parse_error_kind(s: &str) -> ErrorKind {
+    match s {
+        "NotFound" => ErrorKind::NotFound,
+        "PermissionDenied" => ErrorKind::PermissionDenied,
+        "AlreadyExists" => ErrorKind::AlreadyExists,
+        "InvalidInput" => ErrorKind::InvalidInput,
+        "InvalidData" => ErrorKind::InvalidData,
+        "TimedOut" => ErrorKind::TimedOut,
+        "Interrupted" => ErrorKind::Interrupted,
+        "Unsupported" => ErrorKind::Unsupported,
+        "UnexpectedEof" => ErrorKind::UnexpectedEof,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// RecordLayer will record all calls on the accessor and their results
+/// into a file, which could be served by [`Replayer`] later.
+///
+/// # Recording
+///
+/// The recording is a [JSON Lines](https://jsonlines.org/) file, every line
+/// is a call with its outcome:
+///
+/// - `read` will be recorded with the whole payload, so readers are fully
+///   buffered while recording.
+/// - `list` will be recorded with all entries, so listing is fully buffered
+///   while recording.
+/// - `write` will be recorded while the writer closed, the written content
+///   will not be recorded.
+///
+/// Recording is written synchronously and is expected to be used in tests.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::RecordLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// # let path = std::env::temp_dir().join(format!("opendal-record-doc-{}", std::process::id()));
+/// let op = Operator::new(accessor).layer(RecordLayer::new(&path)?);
+///
+/// op.object("file").write("Hello, World!").await?;
+/// let _ = op.object("file").read().await?;
+/// # std::fs::remove_file(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecordLayer {
+    file: Arc<Mutex<File>>,
+}
+
+impl RecordLayer {
+    /// Create a new RecordLayer which records into `path`.
+    ///
+    /// Existing file will be truncated.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            file: Arc::new(Mutex::new(File::create(path)?)),
+        })
+    }
+}
+
+impl Layer for RecordLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        let meta = inner.metadata();
+        let acc = RecordAccessor {
+            inner,
+            file: self.file.clone(),
+        };
+
+        if let Err(e) = acc.append(&Record::Metadata {
+            root: meta.root().to_string(),
+            name: meta.name().to_string(),
+            read_only: meta.read_only(),
+        }) {
+            warn!("record accessor metadata failed: {}", e);
+        }
+
+        Arc::new(acc)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RecordAccessor {
+    inner: Arc<dyn Accessor>,
+    file: Arc<Mutex<File>>,
+}
+
+impl RecordAccessor {
+    fn append(&self, record: &Record) -> Result<()> {
+        let mut line = serde_json::to_vec(record).map_err(other)?;
+        line.push(b'\n');
+        self.file.lock().write_all(&line)
+    }
+
+    /// Record the call and pass through the result.
+    fn record<T>(&self, call: Call, res: Result<T>, f: impl FnOnce(&T) -> Outcome) -> Result<T> {
+        let outcome = match &res {
+            Ok(v) => f(v),
+            Err(e) => Outcome::error(e),
+        };
+        self.append(&Record::Call { call, outcome })?;
+        res
+    }
+}
+
+#[async_trait]
+impl Accessor for RecordAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        let call = Call::Create {
+            path: args.path().to_string(),
+            mode: args.mode().to_string(),
+        };
+        let res = self.inner.create(args).await;
+        self.record(call, res, |_| Outcome::Ok)
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let call = Call::Read {
+            path: args.path().to_string(),
+            offset: args.offset(),
+            size: args.size(),
+        };
+
+        let res = match self.inner.read(args).await {
+            Ok(mut r) => {
+                let mut bs = Vec::new();
+                r.read_to_end(&mut bs).await.map(|_| bs)
+            }
+            Err(e) => Err(e),
+        };
+        let bs = self.record(call, res, |bs| Outcome::Data {
+            data: base64::encode(bs),
+        })?;
+        Ok(Box::new(Cursor::new(bs)))
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        let call = Call::Write {
+            path: args.path().to_string(),
+            size: args.size(),
+        };
+
+        match self.inner.write(args).await {
+            Ok(w) => Ok(Box::new(RecordWriter {
+                inner: w,
+                acc: self.clone(),
+                call: Some(call),
+            })),
+            Err(e) => self.record(call, Err(e), |_| Outcome::Ok),
+        }
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        let call = Call::Stat {
+            path: args.path().to_string(),
+        };
+        let res = self.inner.stat(args).await;
+        self.record(call, res, Outcome::metadata)
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let call = Call::Delete {
+            path: args.path().to_string(),
+        };
+        let res = self.inner.delete(args).await;
+        self.record(call, res, |_| Outcome::Ok)
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let call = Call::List {
+            path: args.path().to_string(),
+        };
+
+        let res = match self.inner.list(args).await {
+            Ok(s) => s.try_collect::<Vec<DirEntry>>().await,
+            Err(e) => Err(e),
+        };
+        let entries = self.record(call, res, |entries| Outcome::Entries {
+            entries: entries
+                .iter()
+                .map(|de| (de.path().to_string(), de.mode().to_string()))
+                .collect(),
+        })?;
+
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        Ok(Box::new(stream::iter(entries.into_iter().map(move |de| {
+            Ok(DirEntry::new(acc.clone(), de.mode(), de.path()))
+        }))))
+    }
+}
+
+/// RecordWriter will record the write call while closing.
+struct RecordWriter {
+    inner: BytesWriter,
+    acc: RecordAccessor,
+    /// Call to record, will be taken after recorded.
+    call: Option<Call>,
+}
+
+impl AsyncWrite for RecordWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let res = ready!(Pin::new(&mut self.inner).poll_close(cx));
+        match self.call.take() {
+            Some(call) => Poll::Ready(self.acc.record(call, res, |_| Outcome::Ok)),
+            None => Poll::Ready(res),
+        }
+    }
+}
+
+/// Replayer serves calls from the recording of [`RecordLayer`] without
+/// any backend.
+///
+/// # Behavior
+///
+/// - Calls are matched by operation and all arguments, outcomes of the same
+///   call will be returned in the recorded order, and the last one will be
+///   repeated after all consumed.
+/// - Calls not in the recording will fail with `ErrorKind::Other`.
+/// - Written content will be discarded, the recorded error of `write` will be
+///   returned while creating the writer.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::RecordLayer;
+/// use opendal::layers::Replayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// # let path = std::env::temp_dir().join(format!("opendal-replay-doc-{}", std::process::id()));
+/// // Capture the session once.
+/// let op = Operator::new(accessor).layer(RecordLayer::new(&path)?);
+/// op.object("file").write("Hello, World!").await?;
+/// let _ = op.object("file").read().await?;
+///
+/// // Replay it without the backend.
+/// let op = Operator::new(Replayer::load(&path)?);
+/// assert_eq!(op.object("file").read().await?, b"Hello, World!");
+/// # std::fs::remove_file(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    meta: AccessorMetadata,
+    calls: Arc<Mutex<HashMap<Call, VecDeque<Outcome>>>>,
+}
+
+impl Replayer {
+    /// Load the recording from `path` and build an accessor to serve it.
+    pub fn load(path: impl AsRef<Path>) -> Result<Arc<dyn Accessor>> {
+        let content = fs::read_to_string(path)?;
+
+        let mut meta = AccessorMetadata::default();
+        let mut calls: HashMap<Call, VecDeque<Outcome>> = HashMap::new();
+        for (idx, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: Record = serde_json::from_str(line).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    anyhow!("parse recording at line {} failed: {}", idx + 1, e),
+                )
+            })?;
+            match record {
+                Record::Metadata {
+                    root,
+                    name,
+                    read_only,
+                } => {
+                    meta.set_root(&root)
+                        .set_name(&name)
+                        .set_read_only(read_only);
+                }
+                Record::Call { call, outcome } => {
+                    calls.entry(call).or_default().push_back(outcome);
+                }
+            }
+        }
+
+        Ok(Arc::new(Replayer {
+            meta,
+            calls: Arc::new(Mutex::new(calls)),
+        }))
+    }
+
+    fn replay(&self, call: Call) -> Result<Outcome> {
+        let outcome = {
+            let mut calls = self.calls.lock();
+            calls.get_mut(&call).and_then(|q| {
+                if q.len() > 1 {
+                    q.pop_front()
+                } else {
+                    q.front().cloned()
+                }
+            })
+        };
+
+        match outcome {
+            Some(Outcome::Error { kind, message }) => Err(Error::new(
+                parse_error_kind(&kind),
+                ObjectError::new(call.op(), call.path(), anyhow!("{}", message)),
+            )),
+            Some(outcome) => Ok(outcome),
+            None => Err(other(ObjectError::new(
+                call.op(),
+                call.path(),
+                anyhow!("call is not recorded"),
+            ))),
+        }
+    }
+
+    fn unexpected(call: &Call, outcome: Outcome) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            ObjectError::new(
+                call.op(),
+                call.path(),
+                anyhow!("unexpected recorded outcome: {:?}", outcome),
+            ),
+        )
+    }
+}
+
+#[async_trait]
+impl Accessor for Replayer {
+    fn metadata(&self) -> AccessorMetadata {
+        self.meta.clone()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.replay(Call::Create {
+            path: args.path().to_string(),
+            mode: args.mode().to_string(),
+        })
+        .map(|_| ())
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let call = Call::Read {
+            path: args.path().to_string(),
+            offset: args.offset(),
+            size: args.size(),
+        };
+
+        match self.replay(call.clone())? {
+            Outcome::Data { data } => {
+                let bs = base64::decode(data).map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        ObjectError::new("read", args.path(), anyhow!("invalid payload: {e}")),
+                    )
+                })?;
+                Ok(Box::new(Cursor::new(bs)))
+            }
+            outcome => Err(Self::unexpected(&call, outcome)),
+        }
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.replay(Call::Write {
+            path: args.path().to_string(),
+            size: args.size(),
+        })?;
+        Ok(Box::new(futures::io::sink()))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        let call = Call::Stat {
+            path: args.path().to_string(),
+        };
+
+        match self.replay(call.clone())? {
+            Outcome::Metadata {
+                mode,
+                content_length,
+                content_md5,
+                last_modified,
+                etag,
+            } => {
+                let mut meta = ObjectMetadata::default();
+                meta.set_mode(parse_mode(&mode))
+                    .set_content_length(content_length);
+                if let Some(v) = content_md5 {
+                    meta.set_content_md5(&v);
+                }
+                if let Some(v) = last_modified {
+                    meta.set_last_modified(
+                        OffsetDateTime::from_unix_timestamp_nanos(v).map_err(other)?,
+                    );
+                }
+                if let Some(v) = etag {
+                    meta.set_etag(&v);
+                }
+                Ok(meta)
+            }
+            outcome => Err(Self::unexpected(&call, outcome)),
+        }
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.replay(Call::Delete {
+            path: args.path().to_string(),
+        })
+        .map(|_| ())
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let call = Call::List {
+            path: args.path().to_string(),
+        };
+
+        match self.replay(call.clone())? {
+            Outcome::Entries { entries } => {
+                let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+                Ok(Box::new(stream::iter(entries.into_iter().map(
+                    move |(path, mode)| Ok(DirEntry::new(acc.clone(), parse_mode(&mode), &path)),
+                ))))
+            }
+            outcome => Err(Self::unexpected(&call, outcome)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    fn recording(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("opendal-record-{}-{}", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn test_record_replay() -> anyhow::Result<()> {
+        let path = recording("replay");
+
+        let op =
+            Operator::new(memory::Backend::build().finish().await?).layer(RecordLayer::new(&path)?);
+        op.object("dir/").create().await?;
+        op.object("dir/file").write("Hello, World!").await?;
+        assert_eq!(op.object("dir/file").read().await?, b"Hello, World!");
+        assert_eq!(op.object("dir/file").range_read(7..).await?, b"World!");
+        let meta = op.object("dir/file").metadata().await?;
+        let entries: Vec<DirEntry> = op.object("dir/").list().await?.try_collect().await?;
+        assert_eq!(entries.len(), 1);
+        op.object("dir/file").delete().await?;
+        let err = op.object("dir/file").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        let op = Operator::new(Replayer::load(&path)?);
+        op.object("dir/").create().await?;
+        op.object("dir/file").write("Hello, World!").await?;
+        assert_eq!(op.object("dir/file").read().await?, b"Hello, World!");
+        assert_eq!(op.object("dir/file").range_read(7..).await?, b"World!");
+        let replayed = op.object("dir/file").metadata().await?;
+        assert_eq!(replayed.mode(), meta.mode());
+        assert_eq!(replayed.content_length(), meta.content_length());
+        let entries: Vec<DirEntry> = op.object("dir/").list().await?.try_collect().await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path(), "dir/file");
+        assert_eq!(entries[0].mode(), ObjectMode::FILE);
+        op.object("dir/file").delete().await?;
+        let err = op.object("dir/file").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_not_recorded() -> anyhow::Result<()> {
+        let path = recording("not-recorded");
+
+        let op =
+            Operator::new(memory::Backend::build().finish().await?).layer(RecordLayer::new(&path)?);
+        op.object("file").write("Hello, World!").await?;
+
+        let op = Operator::new(Replayer::load(&path)?);
+        let err = op.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert!(err.to_string().contains("not recorded"), "{}", err);
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -15,13 +15,28 @@
 //! Provide backoff retry support via implement [`Layer`] for [`backon::Backoff`](https://docs.rs/backon/latest/backon/trait.Backoff.html)
 
 use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::future::Future;
+use std::io::Error;
 use std::io::ErrorKind;
 use std::io::Result;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
 
+use anyhow::anyhow;
 use async_trait::async_trait;
-use backon::Retryable;
+use futures::future::BoxFuture;
+use futures::ready;
+use futures::AsyncRead;
+use futures::AsyncWrite;
+use log::debug;
+use log::warn;
 
+use crate::error::retry_after;
+use crate::error::ObjectError;
 use crate::ops::OpCreate;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
@@ -38,6 +53,23 @@ use crate::ObjectMetadata;
 
 /// Implement [`Layer`] for [`backon::Backoff`](https://docs.rs/backon/latest/backon/trait.Backoff.html) so that all backoff can be used as a layer
 ///
+/// # Behavior
+///
+/// - Operations will be retried if the error kind is `Interrupted`. Services
+///   classify errors via status code and service error code, for example,
+///   S3's `SlowDown` and Azblob's `ServerBusy` are retryable.
+/// - The delay of backoff will be jittered, and the `Retry-After` returned by
+///   services will be respected.
+/// - Readers will be resumed from the current offset if they meet an
+///   `Interrupted` error in the middle of the stream. Reads are pinned to
+///   the `ETag` so that a changed object will not be spliced silently. The
+///   `ETag` is taken from `if_match` of the read, or fetched by `stat`
+///   before the read. Reads of objects without `ETag` or that can't be
+///   `stat` will not be resumed.
+/// - Writers can't be resumed since the written data has been consumed, an
+///   `Interrupted` error in the middle of the stream will be reported with
+///   the written size so that users can restart the write.
+///
 /// # Example
 ///
 ///
@@ -68,64 +100,294 @@ where
 }
 
 #[derive(Debug)]
-struct RetryableAccessor<B: backon::Backoff + Debug + Send + Sync> {
+struct RetryableAccessor<B: backon::Backoff + Debug + Send + Sync + 'static> {
     inner: Arc<dyn Accessor>,
     backoff: B,
 }
 
 impl<B> RetryableAccessor<B>
 where
-    B: backon::Backoff + Debug + Send + Sync,
+    B: backon::Backoff + Debug + Send + Sync + 'static,
 {
     fn create(inner: Arc<dyn Accessor>, backoff: B) -> Self {
         Self { inner, backoff }
     }
+
+    async fn retry<T, F, Fut>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut backoff = self.backoff.clone();
+
+        loop {
+            match f().await {
+                Ok(v) => return Ok(v),
+                Err(e) if e.kind() == ErrorKind::Interrupted => match backoff.next() {
+                    Some(dur) => {
+                        let dur = retry_delay(&e, dur);
+                        warn!("operation failed, retry after {:?}: {}", dur, e);
+                        tokio::time::sleep(dur).await;
+                    }
+                    None => return Err(e),
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Decide the delay before next retry.
+///
+/// The delay of backoff will be jittered into `[delay / 2, delay]` to avoid
+/// retrying at the same time, and the delay that services asked for via
+/// `Retry-After` will be respected.
+fn retry_delay(err: &Error, dur: Duration) -> Duration {
+    let half = dur / 2;
+    let dur = half + half.mul_f64(rand::random::<f64>());
+
+    match retry_after(err) {
+        Some(after) => after.max(dur),
+        None => dur,
+    }
 }
 
 #[async_trait]
 impl<B> Accessor for RetryableAccessor<B>
 where
-    B: backon::Backoff + Debug + Send + Sync,
+    B: backon::Backoff + Debug + Send + Sync + 'static,
 {
     fn metadata(&self) -> AccessorMetadata {
         self.inner.metadata()
     }
 
     async fn create(&self, args: &OpCreate) -> Result<()> {
-        { || self.inner.create(args) }
-            .retry(self.backoff.clone())
-            .with_error_fn(|e| e.kind() == ErrorKind::Interrupted)
-            .await
+        self.retry(|| self.inner.create(args)).await
     }
     async fn read(&self, args: &OpRead) -> Result<BytesReader> {
-        { || self.inner.read(args) }
-            .retry(self.backoff.clone())
-            .with_error_fn(|e| e.kind() == ErrorKind::Interrupted)
-            .await
+        let etag = match args.if_match() {
+            Some(etag) => Some(etag.to_string()),
+            None => {
+                let stat = OpStat::new(args.path())?;
+                match self.retry(|| self.inner.stat(&stat)).await {
+                    Ok(meta) => meta.etag().map(|v| v.to_string()),
+                    Err(e) if e.kind() == ErrorKind::NotFound => return Err(e),
+                    Err(e) => {
+                        warn!("object {} stat for pinning read: {}", args.path(), e);
+                        None
+                    }
+                }
+            }
+        };
+
+        let op = match &etag {
+            Some(etag) => args.clone().with_if_match(etag),
+            None => args.clone(),
+        };
+        let r = self.retry(|| self.inner.read(&op)).await?;
+
+        Ok(Box::new(RetryableReader {
+            inner: self.inner.clone(),
+            path: args.path().to_string(),
+            offset: args.offset(),
+            size: args.size(),
+            etag,
+            pos: 0,
+            backoff: Box::new(self.backoff.clone()),
+            retries: None,
+            state: ReadState::Reading(r),
+        }))
     }
     async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
-        { || self.inner.write(args) }
-            .retry(self.backoff.clone())
-            .with_error_fn(|e| e.kind() == ErrorKind::Interrupted)
-            .await
+        let w = self.retry(|| self.inner.write(args)).await?;
+
+        Ok(Box::new(RetryableWriter {
+            inner: w,
+            path: args.path().to_string(),
+            written: 0,
+        }))
     }
     async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
-        { || self.inner.stat(args) }
-            .retry(self.backoff.clone())
-            .with_error_fn(|e| e.kind() == ErrorKind::Interrupted)
-            .await
+        self.retry(|| self.inner.stat(args)).await
     }
     async fn delete(&self, args: &OpDelete) -> Result<()> {
-        { || self.inner.delete(args) }
-            .retry(self.backoff.clone())
-            .with_error_fn(|e| e.kind() == ErrorKind::Interrupted)
-            .await
+        self.retry(|| self.inner.delete(args)).await
     }
     async fn list(&self, args: &OpList) -> Result<DirStreamer> {
-        { || self.inner.list(args) }
-            .retry(self.backoff.clone())
-            .with_error_fn(|e| e.kind() == ErrorKind::Interrupted)
-            .await
+        self.retry(|| self.inner.list(args)).await
+    }
+}
+
+enum ReadState {
+    Reading(BytesReader),
+    Sleeping(Pin<Box<tokio::time::Sleep>>),
+    Sending(BoxFuture<'static, Result<BytesReader>>),
+}
+
+/// RetryableReader will resume the read from current offset while meeting
+/// retryable errors.
+struct RetryableReader<B: backon::Backoff> {
+    inner: Arc<dyn Accessor>,
+    path: String,
+    offset: Option<u64>,
+    size: Option<u64>,
+    /// The `ETag` that reads are pinned to, reads without `ETag` can't be
+    /// resumed.
+    etag: Option<String>,
+
+    /// The bytes that have been read.
+    pos: u64,
+    backoff: Box<B>,
+    /// Backoff of current retries, will be reset after reading succeeds.
+    retries: Option<Box<B>>,
+    state: ReadState,
+}
+
+impl<B: backon::Backoff> Debug for RetryableReader<B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryableReader")
+            .field("path", &self.path)
+            .field("offset", &self.offset)
+            .field("size", &self.size)
+            .field("etag", &self.etag)
+            .field("pos", &self.pos)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B: backon::Backoff> RetryableReader<B> {
+    /// Get the next backoff delay, returns `None` if retries are exhausted.
+    fn next_backoff(&mut self) -> Option<Duration> {
+        let backoff = &self.backoff;
+        self.retries.get_or_insert_with(|| backoff.clone()).next()
+    }
+
+    fn resume(&self, etag: &str) -> Result<BoxFuture<'static, Result<BytesReader>>> {
+        let offset = self.offset.unwrap_or_default() + self.pos;
+        let size = self.size.map(|v| v - self.pos);
+        let op = OpRead::new_with_offset(&self.path, Some(offset), size)?.with_if_match(etag);
+
+        let acc = self.inner.clone();
+        Ok(Box::pin(async move { acc.read(&op).await }))
+    }
+
+    /// Decide what to do after meeting an error.
+    fn handle_error(&mut self, err: Error) -> Result<()> {
+        if err.kind() != ErrorKind::Interrupted {
+            return Err(err);
+        }
+        if self.etag.is_none() {
+            warn!(
+                "object {} read interrupted at {}, can't resume without etag",
+                self.path, self.pos
+            );
+            return Err(err);
+        }
+
+        match self.next_backoff() {
+            Some(dur) => {
+                let dur = retry_delay(&err, dur);
+                warn!(
+                    "object {} read interrupted at {}, retry after {:?}: {}",
+                    self.path, self.pos, dur, err
+                );
+                self.state = ReadState::Sleeping(Box::pin(tokio::time::sleep(dur)));
+                Ok(())
+            }
+            None => Err(err),
+        }
+    }
+}
+
+impl<B> AsyncRead for RetryableReader<B>
+where
+    B: backon::Backoff + Send + Sync,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        loop {
+            match &mut self.state {
+                ReadState::Reading(r) => match ready!(Pin::new(r).poll_read(cx, buf)) {
+                    Ok(n) => {
+                        self.pos += n as u64;
+                        self.retries = None;
+                        return Poll::Ready(Ok(n));
+                    }
+                    Err(e) => self.handle_error(e)?,
+                },
+                ReadState::Sleeping(fut) => {
+                    ready!(fut.as_mut().poll(cx));
+                    let etag = self.etag.clone().expect("etag must be set for resuming");
+                    let fut = self.resume(&etag)?;
+                    self.state = ReadState::Sending(fut);
+                }
+                ReadState::Sending(fut) => match ready!(fut.as_mut().poll(cx)) {
+                    Ok(r) => {
+                        debug!("object {} read resumed at {}", self.path, self.pos);
+                        self.state = ReadState::Reading(r);
+                    }
+                    Err(e) => self.handle_error(e)?,
+                },
+            }
+        }
+    }
+}
+
+/// RetryableWriter will report retryable errors clearly since writers
+/// can't be resumed.
+struct RetryableWriter {
+    inner: BytesWriter,
+    path: String,
+    written: u64,
+}
+
+impl RetryableWriter {
+    fn map_err(&self, err: Error) -> Error {
+        if err.kind() != ErrorKind::Interrupted {
+            return err;
+        }
+
+        Error::new(
+            ErrorKind::Interrupted,
+            ObjectError::new(
+                "write",
+                &self.path,
+                anyhow!(
+                    "write interrupted after {} bytes written and can't be resumed, please restart the write: {}",
+                    self.written,
+                    err
+                ),
+            ),
+        )
+    }
+}
+
+impl AsyncWrite for RetryableWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        match ready!(Pin::new(&mut self.inner).poll_write(cx, buf)) {
+            Ok(n) => {
+                self.written += n as u64;
+                Poll::Ready(Ok(n))
+            }
+            Err(e) => Poll::Ready(Err(self.map_err(e))),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let res = ready!(Pin::new(&mut self.inner).poll_flush(cx));
+        Poll::Ready(res.map_err(|e| self.map_err(e)))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let res = ready!(Pin::new(&mut self.inner).poll_close(cx));
+        Poll::Ready(res.map_err(|e| self.map_err(e)))
     }
 }
 
@@ -138,12 +400,22 @@ mod tests {
     use anyhow::anyhow;
     use async_trait::async_trait;
     use backon::ConstantBackoff;
+    use futures::AsyncReadExt;
+    use futures::AsyncWriteExt;
+    use futures::StreamExt;
     use tokio::sync::Mutex;
 
     use crate::error::other;
+    use crate::error::ObjectError;
+    use crate::io_util::into_reader;
+    use crate::ops::OpDelete;
     use crate::ops::OpRead;
+    use crate::ops::OpStat;
+    use crate::ops::OpWrite;
     use crate::Accessor;
     use crate::BytesReader;
+    use crate::BytesWriter;
+    use crate::ObjectMetadata;
     use crate::Operator;
 
     #[derive(Debug, Clone, Default)]
@@ -165,6 +437,112 @@ mod tests {
                 _ => Err(other(anyhow!("not_retryable_error"))),
             }
         }
+        async fn stat(&self, _: &OpStat) -> std::io::Result<ObjectMetadata> {
+            Ok(ObjectMetadata::default())
+        }
+    }
+
+    /// BusyService asks clients to retry after 50ms for the first request.
+    #[derive(Debug, Default)]
+    struct BusyService {
+        attempt: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl Accessor for BusyService {
+        async fn delete(&self, args: &OpDelete) -> std::io::Result<()> {
+            let mut attempt = self.attempt.lock().await;
+            *attempt += 1;
+
+            if *attempt == 1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    ObjectError::new("delete", args.path(), anyhow!("ServerBusy"))
+                        .with_retry_after(Some(Duration::from_millis(50))),
+                ));
+            }
+            Ok(())
+        }
+    }
+
+    const CONTENT: &[u8] = b"Hello, World! Hello, OpenDAL!";
+
+    /// FlakyService returns readers that break after every 8 bytes.
+    #[derive(Debug, Default)]
+    struct FlakyService {
+        etag: std::sync::Mutex<String>,
+        reads: Mutex<Vec<(Option<u64>, Option<u64>)>>,
+        stat_unsupported: bool,
+    }
+
+    #[async_trait]
+    impl Accessor for FlakyService {
+        async fn read(&self, args: &OpRead) -> std::io::Result<BytesReader> {
+            self.reads.lock().await.push((args.offset(), args.size()));
+
+            let etag = self.etag.lock().unwrap().clone();
+            if matches!(args.if_match(), Some(v) if v != etag) {
+                return Err(other(anyhow!("etag mismatch")));
+            }
+
+            let start = args.offset().unwrap_or_default() as usize;
+            let end = match args.size() {
+                Some(size) => start + size as usize,
+                None => CONTENT.len(),
+            };
+            let chunk_end = end.min(start + 8);
+            let mut chunks: Vec<std::io::Result<bytes::Bytes>> =
+                vec![Ok(bytes::Bytes::from_static(&CONTENT[start..chunk_end]))];
+            if chunk_end < end {
+                chunks.push(Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    anyhow!("connection reset"),
+                )));
+            }
+
+            Ok(Box::new(into_reader(futures::stream::iter(chunks).boxed())))
+        }
+        async fn write(&self, _: &OpWrite) -> std::io::Result<BytesWriter> {
+            Ok(Box::new(BrokenWriter))
+        }
+        async fn stat(&self, _: &OpStat) -> std::io::Result<ObjectMetadata> {
+            if self.stat_unsupported {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    anyhow!("stat is not supported"),
+                ));
+            }
+            let mut meta = ObjectMetadata::default();
+            meta.set_etag(&self.etag.lock().unwrap());
+            Ok(meta)
+        }
+    }
+
+    struct BrokenWriter;
+
+    impl futures::AsyncWrite for BrokenWriter {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                anyhow!("connection reset"),
+            )))
+        }
     }
 
     #[tokio::test]
@@ -200,4 +578,143 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_retry_resume_read() -> anyhow::Result<()> {
+        let srv = Arc::new(FlakyService {
+            etag: std::sync::Mutex::new("\"v1\"".to_string()),
+            ..Default::default()
+        });
+
+        let backoff = ConstantBackoff::default().with_delay(Duration::from_micros(1));
+        let op = Operator::new(srv.clone()).layer(backoff);
+
+        let bs = op.object("file").read().await?;
+        assert_eq!(bs, CONTENT);
+        assert_eq!(
+            *srv.reads.lock().await,
+            vec![
+                (None, None),
+                (Some(8), None),
+                (Some(16), None),
+                (Some(24), None)
+            ]
+        );
+
+        srv.reads.lock().await.clear();
+        let bs = op.object("file").range_read(4..20).await?;
+        assert_eq!(bs, &CONTENT[4..20]);
+        assert_eq!(
+            *srv.reads.lock().await,
+            vec![(Some(4), Some(16)), (Some(12), Some(8))]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_resume_read_without_stat() -> anyhow::Result<()> {
+        let srv = Arc::new(FlakyService {
+            stat_unsupported: true,
+            ..Default::default()
+        });
+
+        let backoff = ConstantBackoff::default().with_delay(Duration::from_micros(1));
+        let op = Operator::new(srv.clone()).layer(backoff);
+
+        // Reads without etag can't be resumed.
+        let err = op.object("file").read().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(srv.reads.lock().await.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_resume_read_changed_before_resume() -> anyhow::Result<()> {
+        let srv = Arc::new(FlakyService {
+            etag: std::sync::Mutex::new("\"v1\"".to_string()),
+            ..Default::default()
+        });
+
+        let backoff = ConstantBackoff::default().with_delay(Duration::from_micros(1));
+        let op = Operator::new(srv.clone()).layer(backoff);
+
+        // The etag is pinned before the first interruption.
+        let mut r = op.object("file").reader().await?;
+        *srv.etag.lock().unwrap() = "\"v2\"".to_string();
+
+        let mut buf = Vec::new();
+        let err = r.read_to_end(&mut buf).await.unwrap_err();
+        assert_eq!(err.to_string(), "etag mismatch");
+        assert_eq!(buf, &CONTENT[..8]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_resume_read_etag_changed() -> anyhow::Result<()> {
+        let srv = Arc::new(FlakyService {
+            etag: std::sync::Mutex::new("\"v1\"".to_string()),
+            ..Default::default()
+        });
+
+        let backoff = ConstantBackoff::default().with_delay(Duration::from_micros(1));
+        let op = Operator::new(srv.clone()).layer(backoff);
+
+        let mut r = op.object("file").reader().await?;
+        let mut buf = vec![0; 16];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(buf, &CONTENT[..16]);
+
+        // The object has been changed, resumed read must fail.
+        *srv.etag.lock().unwrap() = "\"v2\"".to_string();
+        let mut buf = Vec::new();
+        let err = r.read_to_end(&mut buf).await.unwrap_err();
+        assert_eq!(err.to_string(), "etag mismatch");
+        assert!(buf.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_write_error() -> anyhow::Result<()> {
+        let srv = Arc::new(FlakyService::default());
+
+        let op = Operator::new(srv.clone()).layer(ConstantBackoff::default());
+
+        let mut w = op.object("file").writer(13).await?;
+        w.write_all(b"Hello, World!").await?;
+        let err = w.close().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert!(err.to_string().contains("after 13 bytes written"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_after() -> anyhow::Result<()> {
+        let srv = Arc::new(BusyService::default());
+
+        let backoff = ConstantBackoff::default().with_delay(Duration::from_micros(1));
+        let op = Operator::new(srv.clone()).layer(backoff);
+
+        let now = std::time::Instant::now();
+        op.object("file").delete().await?;
+        assert!(now.elapsed() >= Duration::from_millis(50));
+        assert_eq!(*srv.attempt.lock().await, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_delay_jitter() {
+        let err = io::Error::new(io::ErrorKind::Interrupted, anyhow!("retryable_error"));
+
+        for _ in 0..100 {
+            let dur = super::retry_delay(&err, Duration::from_secs(1));
+            assert!(dur >= Duration::from_millis(500));
+            assert!(dur <= Duration::from_secs(1));
+        }
+    }
 }
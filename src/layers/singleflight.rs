@@ -0,0 +1,365 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::future::Future;
+use std::hash::Hash;
+use std::io::Error;
+use std::io::Result;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::future::Shared;
+use futures::io::Cursor;
+use futures::AsyncReadExt;
+use futures::FutureExt;
+use log::debug;
+use parking_lot::Mutex;
+
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// SingleflightLayer will coalesce concurrent identical `read` and `stat`
+/// calls into one request to the underlying accessor.
+///
+/// # Behavior
+///
+/// - `stat` calls on the same path will share the same result.
+/// - `read` calls on the same path, range and `if_match` will share the
+///   same payload, which is buffered in memory and fanned out to every
+///   waiter.
+/// - Reads larger than the max buffer size won't be coalesced: reads with
+///   known larger size will be sent directly, and if the payload turns out
+///   to be larger while buffering, every waiter will fall back to read
+///   by itself.
+/// - The caller that started the call will get the original error, other
+///   waiters will get a copy with the same kind and message, which can't
+///   be downcast into its source anymore.
+///
+/// Only in-flight calls will be coalesced, results are not cached after the
+/// call finished.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::SingleflightLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor)
+///     .layer(SingleflightLayer::new().with_max_buffer_size(4 * 1024 * 1024));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SingleflightLayer {
+    max_buffer_size: u64,
+}
+
+impl Default for SingleflightLayer {
+    fn default() -> Self {
+        Self {
+            max_buffer_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+impl SingleflightLayer {
+    /// Create a new SingleflightLayer with default max buffer size 8 MiB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the max size of payload that could be buffered for coalesced reads.
+    #[must_use]
+    pub fn with_max_buffer_size(mut self, size: u64) -> Self {
+        self.max_buffer_size = size;
+        self
+    }
+}
+
+impl Layer for SingleflightLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(SingleflightAccessor {
+            inner,
+            max_buffer_size: self.max_buffer_size,
+            stats: Arc::new(Group::default()),
+            reads: Arc::new(Group::default()),
+        })
+    }
+}
+
+type SharedFuture<T> = Shared<BoxFuture<'static, std::result::Result<T, Arc<Error>>>>;
+
+/// Group holds in-flight calls by key.
+pub(super) struct Group<K, T> {
+    calls: Mutex<HashMap<K, SharedFuture<T>>>,
+}
+
+impl<K, T> Default for Group<K, T> {
+    fn default() -> Self {
+        Self {
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K, T> Group<K, T>
+where
+    K: Hash + Eq + Clone + Debug + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    /// Join the in-flight call of `key`, or start a new one by `f`.
+    pub(super) async fn work<F, Fut>(self: &Arc<Self>, key: K, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        let (fut, origin) = {
+            let mut calls = self.calls.lock();
+            match calls.get(&key) {
+                Some(fut) => {
+                    debug!("call {:?} joined in-flight one", key);
+                    (fut.clone(), None)
+                }
+                None => {
+                    let group = self.clone();
+                    let k = key.clone();
+                    let call = f();
+                    // Keep the original error for the caller that started the call.
+                    let origin = Arc::new(Mutex::new(None));
+                    let slot = origin.clone();
+                    let fut = async move {
+                        let res = call.await;
+                        group.calls.lock().remove(&k);
+                        res.map_err(|e| {
+                            let flattened = Arc::new(flatten(&e));
+                            *slot.lock() = Some(e);
+                            flattened
+                        })
+                    }
+                    .boxed()
+                    .shared();
+                    calls.insert(key, fut.clone());
+                    (fut, Some(origin))
+                }
+            }
+        };
+
+        fut.await.map_err(|e| {
+            origin
+                .and_then(|v| v.lock().take())
+                .unwrap_or_else(|| flatten(&e))
+        })
+    }
+}
+
+/// Copy the error with its kind and message, so that it can be shared
+/// between waiters.
+fn flatten(e: &Error) -> Error {
+    Error::new(e.kind(), e.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ReadKey {
+    path: String,
+    offset: Option<u64>,
+    size: Option<u64>,
+    if_match: Option<String>,
+}
+
+/// Payload of the coalesced read, `None` means it's too large to buffer.
+type Payload = Option<Bytes>;
+
+#[derive(Clone)]
+struct SingleflightAccessor {
+    inner: Arc<dyn Accessor>,
+    max_buffer_size: u64,
+    stats: Arc<Group<String, ObjectMetadata>>,
+    reads: Arc<Group<ReadKey, Payload>>,
+}
+
+impl Debug for SingleflightAccessor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SingleflightAccessor")
+            .field("inner", &self.inner)
+            .field("max_buffer_size", &self.max_buffer_size)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Accessor for SingleflightAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inner.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        if matches!(args.size(), Some(size) if size > self.max_buffer_size) {
+            return self.inner.read(args).await;
+        }
+
+        let key = ReadKey {
+            path: args.path().to_string(),
+            offset: args.offset(),
+            size: args.size(),
+            if_match: args.if_match().map(|v| v.to_string()),
+        };
+        let payload = self
+            .reads
+            .work(key, || {
+                let inner = self.inner.clone();
+                let args = args.clone();
+                let limit = self.max_buffer_size;
+                async move {
+                    let r = inner.read(&args).await?;
+
+                    // Read one more byte to know whether it exceeds the limit.
+                    let mut bs = Vec::new();
+                    r.take(limit + 1).read_to_end(&mut bs).await?;
+                    if bs.len() as u64 > limit {
+                        debug!("object {} is too large to buffer", args.path());
+                        return Ok(None);
+                    }
+                    Ok(Some(Bytes::from(bs)))
+                }
+            })
+            .await?;
+
+        match payload {
+            Some(bs) => Ok(Box::new(Cursor::new(bs))),
+            None => self.inner.read(args).await,
+        }
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.inner.write(args).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.stats
+            .work(args.path().to_string(), || {
+                let inner = self.inner.clone();
+                let args = args.clone();
+                async move { inner.stat(&args).await }
+            })
+            .await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use futures::future::join_all;
+
+    use super::*;
+    use crate::layers::test_util::CountingAccessor;
+    use crate::Operator;
+
+    async fn counting() -> anyhow::Result<Arc<CountingAccessor>> {
+        CountingAccessor::new(Duration::from_millis(50)).await
+    }
+
+    #[tokio::test]
+    async fn test_singleflight() -> anyhow::Result<()> {
+        let acc = counting().await?;
+        let op = Operator::new(acc.clone()).layer(SingleflightLayer::new());
+        op.object("manifest").write("Hello, World!").await?;
+
+        let o = op.object("manifest");
+        let reads = join_all((0..8).map(|_| o.read())).await;
+        for bs in reads {
+            assert_eq!(bs?, b"Hello, World!");
+        }
+        assert_eq!(acc.reads.load(Ordering::SeqCst), 1);
+
+        let metas = join_all((0..8).map(|_| o.metadata())).await;
+        for meta in metas {
+            assert_eq!(meta?.content_length(), 13);
+        }
+        assert_eq!(acc.stats.load(Ordering::SeqCst), 1);
+
+        // Different ranges won't be coalesced.
+        let (a, b) = futures::join!(o.range_read(..5), o.range_read(7..));
+        assert_eq!(a?, b"Hello");
+        assert_eq!(b?, b"World!");
+        assert_eq!(acc.reads.load(Ordering::SeqCst), 3);
+
+        // Errors are shared too.
+        let o = op.object("not_exist");
+        let metas = join_all((0..4).map(|_| o.metadata())).await;
+        for meta in metas {
+            assert_eq!(meta.unwrap_err().kind(), ErrorKind::NotFound);
+        }
+        assert_eq!(acc.stats.load(Ordering::SeqCst), 2);
+
+        // The caller that started the call gets the original error.
+        let err = o.metadata().await.unwrap_err();
+        assert!(err.get_ref().unwrap().is::<crate::error::ObjectError>());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_singleflight_too_large() -> anyhow::Result<()> {
+        let acc = counting().await?;
+        let op = Operator::new(acc.clone()).layer(SingleflightLayer::new().with_max_buffer_size(4));
+        op.object("manifest").write("Hello, World!").await?;
+
+        let o = op.object("manifest");
+        let reads = join_all((0..4).map(|_| o.read())).await;
+        for bs in reads {
+            assert_eq!(bs?, b"Hello, World!");
+        }
+        // One coalesced read to find out it's too large, then every waiter
+        // reads by itself.
+        assert_eq!(acc.reads.load(Ordering::SeqCst), 5);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,346 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use log::debug;
+use parking_lot::Mutex;
+
+use crate::error::ObjectError;
+use crate::io_util::observe_write;
+use crate::io_util::WriteEvent;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::path::normalize_path;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// Default max entries that could be cached.
+const DEFAULT_MAX_ENTRIES: usize = 64 * 1024;
+
+/// StatCacheLayer will cache the results of `stat` for a configurable TTL.
+///
+/// Both [`Object::metadata`][crate::Object::metadata] and
+/// [`Object::is_exist`][crate::Object::is_exist] benefit from this layer.
+///
+/// # Behavior
+///
+/// - Successful `stat` results will be cached for `ttl`.
+/// - `NotFound` errors will be cached for `not_found_ttl`, which is the same
+///   as `ttl` by default.
+/// - `write`, `create` and `delete` will invalidate the cached results of
+///   the path and all its parents.
+///
+/// # Notes
+///
+/// [`DirEntry`][crate::DirEntry] only carries object mode for now, so `list`
+/// results will not be used to pre-populate the cache.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use std::time::Duration;
+///
+/// use opendal::layers::StatCacheLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(
+///     StatCacheLayer::new(Duration::from_secs(60)).with_not_found_ttl(Duration::from_secs(5)),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatCacheLayer {
+    ttl: Duration,
+    not_found_ttl: Duration,
+    max_entries: usize,
+}
+
+impl StatCacheLayer {
+    /// Create a new StatCacheLayer which caches `stat` results for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        StatCacheLayer {
+            ttl,
+            not_found_ttl: ttl,
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    /// Set the TTL of cached `NotFound` results.
+    ///
+    /// Set to `Duration::ZERO` to disable negative caching.
+    #[must_use]
+    pub fn with_not_found_ttl(mut self, ttl: Duration) -> Self {
+        self.not_found_ttl = ttl;
+        self
+    }
+
+    /// Set the max entries that could be cached.
+    ///
+    /// Default to 65536.
+    #[must_use]
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+}
+
+impl Layer for StatCacheLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(StatCacheAccessor {
+            inner,
+            ttl: self.ttl,
+            not_found_ttl: self.not_found_ttl,
+            max_entries: self.max_entries,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Cached {
+    Found(ObjectMetadata),
+    NotFound,
+}
+
+#[derive(Debug)]
+struct StatCacheAccessor {
+    inner: Arc<dyn Accessor>,
+    ttl: Duration,
+    not_found_ttl: Duration,
+    max_entries: usize,
+    entries: Arc<Mutex<HashMap<String, (Cached, Instant)>>>,
+}
+
+impl StatCacheAccessor {
+    fn get(&self, path: &str) -> Option<Result<ObjectMetadata>> {
+        let mut entries = self.entries.lock();
+
+        match entries.get(path) {
+            Some((_, expire)) if *expire <= Instant::now() => {
+                entries.remove(path);
+                None
+            }
+            Some((Cached::Found(meta), _)) => Some(Ok(meta.clone())),
+            Some((Cached::NotFound, _)) => Some(Err(Error::new(
+                ErrorKind::NotFound,
+                ObjectError::new("stat", path, anyhow!("object not found (cached)")),
+            ))),
+            None => None,
+        }
+    }
+
+    fn insert(&self, path: &str, cached: Cached) {
+        let ttl = match cached {
+            Cached::Found(_) => self.ttl,
+            Cached::NotFound => self.not_found_ttl,
+        };
+        if ttl.is_zero() {
+            return;
+        }
+
+        let mut entries = self.entries.lock();
+        if entries.len() >= self.max_entries {
+            let now = Instant::now();
+            entries.retain(|_, (_, expire)| *expire > now);
+        }
+        if entries.len() >= self.max_entries {
+            debug!("stat cache is full, skip caching {}", path);
+            return;
+        }
+
+        entries.insert(path.to_string(), (cached, Instant::now() + ttl));
+    }
+}
+
+/// Drop cached results of the path and all its parents.
+fn invalidate(entries: &Mutex<HashMap<String, (Cached, Instant)>>, path: &str) {
+    let path = normalize_path(path);
+    let mut entries = entries.lock();
+
+    entries.remove(&path);
+    // Root is always normalized as `/`.
+    entries.remove("/");
+    for (idx, _) in path.trim_end_matches('/').match_indices('/') {
+        entries.remove(&path[..idx + 1]);
+    }
+}
+
+#[async_trait]
+impl Accessor for StatCacheAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        let res = self.inner.create(args).await;
+        invalidate(&self.entries, args.path());
+        res
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.inner.read(args).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        invalidate(&self.entries, args.path());
+
+        let w = self.inner.write(args).await?;
+
+        let entries = self.entries.clone();
+        let path = args.path().to_string();
+        Ok(Box::new(observe_write(w, move |e| {
+            if let WriteEvent::Closed = e {
+                invalidate(&entries, &path);
+            }
+        })))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        let key = normalize_path(args.path());
+        if let Some(res) = self.get(&key) {
+            debug!("object {} stat served from cache", args.path());
+            return res;
+        }
+
+        match self.inner.stat(args).await {
+            Ok(meta) => {
+                self.insert(&key, Cached::Found(meta.clone()));
+                Ok(meta)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                self.insert(&key, Cached::NotFound);
+                Err(e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let res = self.inner.delete(args).await;
+        invalidate(&self.entries, args.path());
+        res
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::layers::test_util::CountingAccessor;
+    use crate::Operator;
+
+    async fn new_operator(
+        layer: StatCacheLayer,
+    ) -> anyhow::Result<(Arc<CountingAccessor>, Operator)> {
+        let srv = CountingAccessor::new(Duration::ZERO).await?;
+
+        Ok((srv.clone(), Operator::new(srv).layer(layer)))
+    }
+
+    #[tokio::test]
+    async fn test_stat_cache() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(StatCacheLayer::new(Duration::from_secs(60))).await?;
+        let o = op.object("dir/file");
+
+        // NotFound should be cached too.
+        assert!(!o.is_exist().await?);
+        assert!(!o.is_exist().await?);
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 1);
+
+        // Write should invalidate the cached result.
+        o.write("Hello, World!").await?;
+        assert_eq!(o.metadata().await?.content_length(), 13);
+        assert_eq!(o.metadata().await?.content_length(), 13);
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 2);
+
+        // Delete should invalidate the cached result.
+        o.delete().await?;
+        assert!(!o.is_exist().await?);
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_cache_root() -> anyhow::Result<()> {
+        let srv = CountingAccessor::new(Duration::ZERO).await?;
+        let acc = StatCacheLayer::new(Duration::from_secs(60)).layer(srv.clone());
+
+        acc.stat(&OpStat::new("/")?).await?;
+        acc.stat(&OpStat::new("")?).await?;
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 1);
+
+        // Writing a child should invalidate the root entry.
+        Operator::new(acc.clone())
+            .object("file")
+            .write("Hello")
+            .await?;
+        acc.stat(&OpStat::new("/")?).await?;
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_cache_expire() -> anyhow::Result<()> {
+        let (srv, op) = new_operator(
+            StatCacheLayer::new(Duration::from_millis(50)).with_not_found_ttl(Duration::ZERO),
+        )
+        .await?;
+
+        assert!(!op.object("file").is_exist().await?);
+        assert!(!op.object("file").is_exist().await?);
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 2);
+
+        op.object("file").write("Hello, World!").await?;
+        op.object("file").metadata().await?;
+        op.object("file").metadata().await?;
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 3);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        op.object("file").metadata().await?;
+        assert_eq!(srv.stats.load(Ordering::SeqCst), 4);
+
+        Ok(())
+    }
+}
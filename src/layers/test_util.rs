@@ -0,0 +1,178 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers shared by tests of layers.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::AsyncWrite;
+
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::services::memory;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirStreamer;
+use crate::ObjectMetadata;
+
+/// CountingAccessor counts `read` and `stat` calls on a memory backend,
+/// and delays them to make concurrent calls overlap.
+#[derive(Debug)]
+pub(super) struct CountingAccessor {
+    inner: Arc<dyn Accessor>,
+    delay: Duration,
+    pub(super) reads: AtomicUsize,
+    pub(super) stats: AtomicUsize,
+}
+
+impl CountingAccessor {
+    /// Create a new CountingAccessor which delays `read` and `stat` calls
+    /// for `delay`.
+    pub(super) async fn new(delay: Duration) -> anyhow::Result<Arc<Self>> {
+        Ok(Arc::new(CountingAccessor {
+            inner: memory::Backend::build().finish().await?,
+            delay,
+            reads: AtomicUsize::new(0),
+            stats: AtomicUsize::new(0),
+        }))
+    }
+
+    async fn delay(&self) {
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Accessor for CountingAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inner.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.delay().await;
+        self.inner.read(args).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.inner.write(args).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.stats.fetch_add(1, Ordering::SeqCst);
+        self.delay().await;
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        self.inner.list(args).await
+    }
+}
+
+/// BrokenService fails all operations with `kind`, and counts `stat` calls.
+///
+/// `write` returns a writer that fails all writes.
+#[derive(Debug)]
+pub(super) struct BrokenService {
+    kind: ErrorKind,
+    pub(super) stats: AtomicUsize,
+}
+
+impl BrokenService {
+    pub(super) fn new(kind: ErrorKind) -> Arc<Self> {
+        Arc::new(BrokenService {
+            kind,
+            stats: AtomicUsize::new(0),
+        })
+    }
+
+    fn error(&self) -> Error {
+        Error::new(self.kind, anyhow!("broken"))
+    }
+}
+
+#[async_trait]
+impl Accessor for BrokenService {
+    fn metadata(&self) -> AccessorMetadata {
+        AccessorMetadata::default()
+    }
+
+    async fn create(&self, _: &OpCreate) -> Result<()> {
+        Err(self.error())
+    }
+
+    async fn read(&self, _: &OpRead) -> Result<BytesReader> {
+        Err(self.error())
+    }
+
+    async fn write(&self, _: &OpWrite) -> Result<BytesWriter> {
+        Ok(Box::new(BrokenWriter(self.kind)))
+    }
+
+    async fn stat(&self, _: &OpStat) -> Result<ObjectMetadata> {
+        self.stats.fetch_add(1, Ordering::SeqCst);
+        Err(self.error())
+    }
+
+    async fn delete(&self, _: &OpDelete) -> Result<()> {
+        Err(self.error())
+    }
+
+    async fn list(&self, _: &OpList) -> Result<DirStreamer> {
+        Err(self.error())
+    }
+}
+
+struct BrokenWriter(ErrorKind);
+
+impl AsyncWrite for BrokenWriter {
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, _: &[u8]) -> Poll<Result<usize>> {
+        Poll::Ready(Err(Error::new(self.0, anyhow!("broken"))))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
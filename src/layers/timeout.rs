@@ -0,0 +1,446 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::ready;
+use futures::AsyncRead;
+use futures::AsyncWrite;
+use futures::Stream;
+use log::warn;
+use tokio::time::Instant;
+use tokio::time::Sleep;
+
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+
+/// TimeoutLayer will fail operations that take too long.
+///
+/// # Behavior
+///
+/// - Every operation has a deadline, `read`, `write` and `list` only count
+///   the time to create the reader, writer or dir streamer.
+/// - With [`TimeoutLayer::with_idle_timeout`], readers, writers and dir
+///   streamers will fail if no progress is made for the given duration.
+///
+/// Both of them will fail with `ErrorKind::Interrupted` so that they can be
+/// retried. To make retry work, please apply `TimeoutLayer` before the
+/// retry layer.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use std::time::Duration;
+///
+/// use opendal::layers::TimeoutLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(
+///     TimeoutLayer::new(Duration::from_secs(10))
+///         .with_list_timeout(Duration::from_secs(30))
+///         .with_idle_timeout(Duration::from_secs(5)),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimeoutLayer {
+    create: Duration,
+    read: Duration,
+    write: Duration,
+    stat: Duration,
+    delete: Duration,
+    list: Duration,
+    idle: Option<Duration>,
+}
+
+impl TimeoutLayer {
+    /// Create a new TimeoutLayer which uses `timeout` for all operations.
+    ///
+    /// Idle timeout is disabled by default.
+    pub fn new(timeout: Duration) -> Self {
+        TimeoutLayer {
+            create: timeout,
+            read: timeout,
+            write: timeout,
+            stat: timeout,
+            delete: timeout,
+            list: timeout,
+            idle: None,
+        }
+    }
+
+    /// Set the timeout of `create` operation.
+    #[must_use]
+    pub fn with_create_timeout(mut self, timeout: Duration) -> Self {
+        self.create = timeout;
+        self
+    }
+
+    /// Set the timeout of creating readers.
+    #[must_use]
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read = timeout;
+        self
+    }
+
+    /// Set the timeout of creating writers.
+    #[must_use]
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write = timeout;
+        self
+    }
+
+    /// Set the timeout of `stat` operation.
+    #[must_use]
+    pub fn with_stat_timeout(mut self, timeout: Duration) -> Self {
+        self.stat = timeout;
+        self
+    }
+
+    /// Set the timeout of `delete` operation.
+    #[must_use]
+    pub fn with_delete_timeout(mut self, timeout: Duration) -> Self {
+        self.delete = timeout;
+        self
+    }
+
+    /// Set the timeout of creating dir streamers.
+    #[must_use]
+    pub fn with_list_timeout(mut self, timeout: Duration) -> Self {
+        self.list = timeout;
+        self
+    }
+
+    /// Set the idle timeout of readers, writers and dir streamers.
+    ///
+    /// Streams will fail if they make no progress for `timeout`. The time
+    /// users spent between two polls will not be counted.
+    #[must_use]
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle = Some(timeout);
+        self
+    }
+}
+
+impl Layer for TimeoutLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(TimeoutAccessor {
+            inner,
+            layer: self.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct TimeoutAccessor {
+    inner: Arc<dyn Accessor>,
+    layer: TimeoutLayer,
+}
+
+fn timeout_error(op: &'static str, path: &str, timeout: Duration) -> Error {
+    warn!("object {} {} timeout after {:?}", path, op, timeout);
+
+    Error::new(
+        ErrorKind::Interrupted,
+        ObjectError::new(op, path, anyhow!("operation timeout after {:?}", timeout)),
+    )
+}
+
+async fn timeout<T>(
+    op: &'static str,
+    path: &str,
+    timeout: Duration,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    match tokio::time::timeout(timeout, fut).await {
+        Ok(res) => res,
+        Err(_) => Err(timeout_error(op, path, timeout)),
+    }
+}
+
+#[async_trait]
+impl Accessor for TimeoutAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        timeout(
+            "create",
+            args.path(),
+            self.layer.create,
+            self.inner.create(args),
+        )
+        .await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let r = timeout("read", args.path(), self.layer.read, self.inner.read(args)).await?;
+
+        match self.layer.idle {
+            Some(idle) => Ok(Box::new(TimeoutStream {
+                inner: r,
+                op: "read",
+                path: args.path().to_string(),
+                timer: IdleTimer::new(idle),
+            })),
+            None => Ok(r),
+        }
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        let w = timeout(
+            "write",
+            args.path(),
+            self.layer.write,
+            self.inner.write(args),
+        )
+        .await?;
+
+        match self.layer.idle {
+            Some(idle) => Ok(Box::new(TimeoutStream {
+                inner: w,
+                op: "write",
+                path: args.path().to_string(),
+                timer: IdleTimer::new(idle),
+            })),
+            None => Ok(w),
+        }
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        timeout("stat", args.path(), self.layer.stat, self.inner.stat(args)).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        timeout(
+            "delete",
+            args.path(),
+            self.layer.delete,
+            self.inner.delete(args),
+        )
+        .await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let s = timeout("list", args.path(), self.layer.list, self.inner.list(args)).await?;
+
+        match self.layer.idle {
+            Some(idle) => Ok(Box::new(TimeoutStream {
+                inner: s,
+                op: "list",
+                path: args.path().to_string(),
+                timer: IdleTimer::new(idle),
+            })),
+            None => Ok(s),
+        }
+    }
+}
+
+/// IdleTimer fails the stream if the inner stream keeps pending for too long.
+struct IdleTimer {
+    timeout: Duration,
+    sleep: Pin<Box<Sleep>>,
+    /// Whether the inner stream returned `Pending` last time.
+    ///
+    /// The deadline will only be reset while starting a new poll so that
+    /// the time spent by users will not be counted.
+    waiting: bool,
+}
+
+impl IdleTimer {
+    fn new(timeout: Duration) -> Self {
+        IdleTimer {
+            timeout,
+            sleep: Box::pin(tokio::time::sleep(timeout)),
+            waiting: false,
+        }
+    }
+
+    /// Call before polling the inner stream.
+    fn start(&mut self) {
+        if !self.waiting {
+            self.sleep.as_mut().reset(Instant::now() + self.timeout);
+        }
+    }
+
+    /// Call after polling the inner stream, returns `Ready(true)` if timeout.
+    fn poll_timeout<T>(&mut self, cx: &mut Context<'_>, res: &Poll<T>) -> Poll<bool> {
+        if res.is_ready() {
+            self.waiting = false;
+            return Poll::Ready(false);
+        }
+
+        self.waiting = true;
+        ready!(self.sleep.as_mut().poll(cx));
+        self.waiting = false;
+        Poll::Ready(true)
+    }
+}
+
+struct TimeoutStream<S> {
+    inner: S,
+    op: &'static str,
+    path: String,
+    timer: IdleTimer,
+}
+
+impl<S> TimeoutStream<S> {
+    /// Poll the inner stream via `f` with idle timeout applied.
+    fn poll_with<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        f: impl FnOnce(&mut S, &mut Context<'_>) -> Poll<Result<T>>,
+    ) -> Poll<Result<T>> {
+        self.timer.start();
+        let res = f(&mut self.inner, cx);
+        if ready!(self.timer.poll_timeout(cx, &res)) {
+            return Poll::Ready(Err(timeout_error(self.op, &self.path, self.timer.timeout)));
+        }
+        res
+    }
+}
+
+impl AsyncRead for TimeoutStream<BytesReader> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        self.poll_with(cx, |r, cx| Pin::new(r).poll_read(cx, buf))
+    }
+}
+
+impl AsyncWrite for TimeoutStream<BytesWriter> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        self.poll_with(cx, |w, cx| Pin::new(w).poll_write(cx, buf))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_with(cx, |w, cx| Pin::new(w).poll_flush(cx))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_with(cx, |w, cx| Pin::new(w).poll_close(cx))
+    }
+}
+
+impl Stream for TimeoutStream<DirStreamer> {
+    type Item = Result<DirEntry>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let res = self.poll_with(cx, |s, cx| match Pin::new(s).poll_next(cx) {
+            Poll::Ready(Some(v)) => Poll::Ready(v.map(Some)),
+            Poll::Ready(None) => Poll::Ready(Ok(None)),
+            Poll::Pending => Poll::Pending,
+        });
+        res.map(|v| v.transpose())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::stream;
+    use futures::AsyncReadExt;
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::io_util::into_reader;
+    use crate::Operator;
+
+    /// StallService hangs on `stat`, and its readers stall after the
+    /// first chunk.
+    #[derive(Debug)]
+    struct StallService;
+
+    #[async_trait]
+    impl Accessor for StallService {
+        async fn read(&self, _: &OpRead) -> Result<BytesReader> {
+            let s = stream::iter(vec![Ok(Bytes::from("Hello, "))])
+                .chain(stream::pending())
+                .boxed();
+            Ok(Box::new(into_reader(s)))
+        }
+        async fn stat(&self, _: &OpStat) -> Result<ObjectMetadata> {
+            futures::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_operation_timeout() -> anyhow::Result<()> {
+        let op = Operator::new(Arc::new(StallService))
+            .layer(TimeoutLayer::new(Duration::from_millis(50)));
+
+        let err = op.object("file").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() -> anyhow::Result<()> {
+        let op = Operator::new(Arc::new(StallService)).layer(
+            TimeoutLayer::new(Duration::from_secs(10)).with_idle_timeout(Duration::from_millis(50)),
+        );
+
+        let mut r = op.object("file").reader().await?;
+        let mut buf = vec![0; 7];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(buf, b"Hello, ");
+
+        // Time spent by users should not be counted.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let now = std::time::Instant::now();
+        let err = r.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+        assert!(now.elapsed() >= Duration::from_millis(50));
+
+        Ok(())
+    }
+}
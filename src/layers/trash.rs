@@ -0,0 +1,403 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::future;
+use futures::TryStreamExt;
+use log::debug;
+use time::OffsetDateTime;
+
+use crate::error::other;
+use crate::error::ObjectError;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+use crate::Operator;
+
+/// Default dir that trashed objects will be moved into.
+const DEFAULT_TRASH_DIR: &str = ".trash/";
+
+/// Normalize the trash dir into `path/to/dir/`.
+fn normalize_dir(dir: &str) -> String {
+    let mut dir = dir.trim_start_matches('/').to_string();
+    assert!(!dir.is_empty(), "trash dir must not be root");
+    if !dir.ends_with('/') {
+        dir.push('/');
+    }
+    dir
+}
+
+/// TrashLayer will move objects into the trash dir instead of deleting them.
+///
+/// # Behavior
+///
+/// - Deleting a file will copy it into `.trash/<unix_timestamp_nanos>/<path>`
+///   and then delete the original one.
+/// - Deleting a dir or objects inside the trash dir will delete them
+///   directly.
+/// - The trash dir will be hidden while listing the root, so that
+///   [`BatchOperator::remove_all`][crate::BatchOperator::remove_all] on root
+///   will not purge the trash.
+///
+/// Objects are moved via `copy` of the inner accessor, which streams them
+/// through the client unless the service supports copy natively.
+///
+/// Use [`Trash`] to list, restore and purge trashed objects.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::Trash;
+/// use opendal::layers::TrashLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let op = Operator::new(accessor).layer(TrashLayer::new());
+///
+/// op.object("file").write("Hello, World!").await?;
+/// op.object("file").delete().await?;
+///
+/// let trash = Trash::new(op.clone());
+/// for entry in trash.list().await? {
+///     trash.restore(&entry).await?;
+/// }
+/// assert!(op.object("file").is_exist().await?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TrashLayer {
+    dir: String,
+}
+
+impl Default for TrashLayer {
+    fn default() -> Self {
+        TrashLayer {
+            dir: DEFAULT_TRASH_DIR.to_string(),
+        }
+    }
+}
+
+impl TrashLayer {
+    /// Create a new TrashLayer with default trash dir `.trash/`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the dir that trashed objects will be moved into.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dir` is root.
+    #[must_use]
+    pub fn with_dir(mut self, dir: &str) -> Self {
+        self.dir = normalize_dir(dir);
+        self
+    }
+}
+
+impl Layer for TrashLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(TrashAccessor {
+            inner,
+            dir: self.dir.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TrashAccessor {
+    inner: Arc<dyn Accessor>,
+    dir: String,
+}
+
+impl TrashAccessor {
+    /// Create all parent dirs of the trashed object, so that the trash can
+    /// be walked on services without implicit dirs.
+    async fn create_parents(&self, path: &str) -> Result<()> {
+        let mut idx = self.dir.len();
+        while let Some(pos) = path[idx..].find('/') {
+            idx += pos + 1;
+            self.inner
+                .create(&OpCreate::new(&path[..idx], ObjectMode::DIR)?)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Accessor for TrashAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        self.inner.create(args).await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        self.inner.read(args).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        self.inner.write(args).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let path = args.path();
+        if path.ends_with('/') || path.starts_with(&self.dir) {
+            return self.inner.delete(args).await;
+        }
+
+        match self.inner.stat(&OpStat::new(path)?).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let nanos = OffsetDateTime::now_utc().unix_timestamp_nanos();
+        let trash_path = format!("{}{}/{}", self.dir, nanos, path);
+        debug!("object {} moving into trash {}", path, trash_path);
+
+        self.create_parents(&trash_path).await?;
+        self.inner.copy(&OpCopy::new(path, &trash_path)?).await?;
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let s = self.inner.list(args).await?;
+
+        // Entries must be rebuilt so that deleting them will go into trash.
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        let dir = self.dir.clone();
+        Ok(Box::new(
+            s.try_filter(move |de| future::ready(de.path() != dir))
+                .map_ok(move |de| DirEntry::new(acc.clone(), de.mode(), de.path())),
+        ))
+    }
+}
+
+/// TrashEntry is an object that has been moved into the trash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashEntry {
+    path: String,
+    trash_path: String,
+    deleted_at: OffsetDateTime,
+}
+
+impl TrashEntry {
+    /// Original path of the object.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Path of the object inside the trash.
+    pub fn trash_path(&self) -> &str {
+        &self.trash_path
+    }
+
+    /// Time that the object has been deleted.
+    pub fn deleted_at(&self) -> OffsetDateTime {
+        self.deleted_at
+    }
+}
+
+/// Trash provides APIs to manage objects trashed by [`TrashLayer`].
+///
+/// The operator could be the one with or without [`TrashLayer`], but the
+/// trash dir must be the same.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    op: Operator,
+    dir: String,
+}
+
+impl Trash {
+    /// Create a new Trash on the operator with default trash dir `.trash/`.
+    pub fn new(op: Operator) -> Self {
+        Trash {
+            op,
+            dir: DEFAULT_TRASH_DIR.to_string(),
+        }
+    }
+
+    /// Set the trash dir, must be the same with [`TrashLayer::with_dir`].
+    #[must_use]
+    pub fn with_dir(mut self, dir: &str) -> Self {
+        self.dir = normalize_dir(dir);
+        self
+    }
+
+    /// Parse the timestamp of the dir in format `.trash/<unix_timestamp_nanos>/`.
+    fn parse_timestamp(&self, path: &str) -> Option<OffsetDateTime> {
+        let ts = path.strip_prefix(&self.dir)?.strip_suffix('/')?;
+        OffsetDateTime::from_unix_timestamp_nanos(ts.parse().ok()?).ok()
+    }
+
+    /// List timestamp dirs in the trash.
+    async fn list_dirs(&self) -> Result<Vec<(String, OffsetDateTime)>> {
+        let entries: Vec<DirEntry> = match self.op.object(&self.dir).list().await {
+            Ok(s) => s.try_collect().await?,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|de| {
+                let ts = self.parse_timestamp(de.path())?;
+                Some((de.path().to_string(), ts))
+            })
+            .collect())
+    }
+
+    /// List all objects in the trash, sorted by deleted time.
+    pub async fn list(&self) -> Result<Vec<TrashEntry>> {
+        let mut entries = Vec::new();
+        for (dir, deleted_at) in self.list_dirs().await? {
+            let mut s = self.op.batch().walk_top_down(&dir)?;
+            while let Some(de) = s.try_next().await? {
+                if de.mode() != ObjectMode::FILE {
+                    continue;
+                }
+                entries.push(TrashEntry {
+                    path: de.path()[dir.len()..].to_string(),
+                    trash_path: de.path().to_string(),
+                    deleted_at,
+                });
+            }
+        }
+
+        entries.sort_by_key(|v| v.deleted_at);
+        Ok(entries)
+    }
+
+    /// Restore the object to its original path, and remove it from trash.
+    ///
+    /// Restoring will fail with `AlreadyExists` if the original path exists.
+    pub async fn restore(&self, entry: &TrashEntry) -> Result<()> {
+        let target = self.op.object(entry.path());
+        if target.is_exist().await? {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                ObjectError::new("restore", entry.path(), anyhow!("object already exists")),
+            ));
+        }
+
+        let source = self.op.object(entry.trash_path());
+        source.copy(entry.path()).await?;
+        source.delete().await
+    }
+
+    /// Purge all deletions older than `retention`, returns the count of
+    /// purged deletions.
+    pub async fn purge(&self, retention: Duration) -> Result<usize> {
+        let retention = time::Duration::try_from(retention).map_err(other)?;
+        let deadline = OffsetDateTime::now_utc() - retention;
+
+        let mut purged = 0;
+        for (dir, deleted_at) in self.list_dirs().await? {
+            if deleted_at > deadline {
+                continue;
+            }
+
+            debug!("purging trash {} deleted at {}", dir, deleted_at);
+            self.op.batch().remove_all(&dir).await?;
+            purged += 1;
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory;
+
+    #[tokio::test]
+    async fn test_trash() -> anyhow::Result<()> {
+        let op = Operator::new(memory::Backend::build().finish().await?).layer(TrashLayer::new());
+        op.object("dir/").create().await?;
+        op.object("dir/file").write("Hello, World!").await?;
+        op.object("other").write("Hello, Other!").await?;
+
+        op.batch().remove_all("/").await?;
+        assert!(!op.object("dir/file").is_exist().await?);
+        assert!(!op.object("other").is_exist().await?);
+
+        let trash = Trash::new(op.clone());
+        let entries = trash.list().await?;
+        let mut paths: Vec<&str> = entries.iter().map(|v| v.path()).collect();
+        paths.sort_unstable();
+        assert_eq!(paths, vec!["dir/file", "other"]);
+
+        let entry = entries.iter().find(|v| v.path() == "dir/file").unwrap();
+        trash.restore(entry).await?;
+        assert_eq!(op.object("dir/file").read().await?, b"Hello, World!");
+        assert_eq!(trash.list().await?.len(), 1);
+
+        op.object("other").write("Hello, Again!").await?;
+        let entry = &trash.list().await?[0];
+        let err = trash.restore(entry).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trash_purge() -> anyhow::Result<()> {
+        let op = Operator::new(memory::Backend::build().finish().await?).layer(TrashLayer::new());
+        op.object("file").write("Hello, World!").await?;
+        op.object("file").delete().await?;
+
+        let trash = Trash::new(op.clone());
+        assert_eq!(trash.purge(Duration::from_secs(3600)).await?, 0);
+        assert_eq!(trash.list().await?.len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(trash.purge(Duration::from_millis(1)).await?, 1);
+        assert!(trash.list().await?.is_empty());
+
+        Ok(())
+    }
+}
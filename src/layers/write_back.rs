@@ -0,0 +1,824 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::max;
+use std::cmp::min;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::io;
+use futures::ready;
+use futures::stream;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use futures::FutureExt;
+use futures::TryStreamExt;
+use log::debug;
+use log::warn;
+use parking_lot::Mutex;
+use time::OffsetDateTime;
+use tokio::sync::Notify;
+
+use crate::error::other;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::Layer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+use crate::Operator;
+
+/// WriteBackLayer will make writes return once the data is durable in a
+/// local staging accessor, and upload them to the underlying accessor in
+/// background.
+///
+/// # Behavior
+///
+/// - `write` (and `create` of files) will be staged into the staging
+///   accessor, and uploaded by a background task with exponential backoff.
+/// - `read`, `stat` and `list` will see staged but not uploaded objects.
+/// - `delete` will drop the staged object and delete it from the underlying
+///   accessor.
+/// - [`WriteBackLayer::flush`] waits until all staged objects have been
+///   uploaded.
+/// - Objects left in the staging accessor (for example, after a restart)
+///   will be recovered and uploaded while applying the layer.
+///
+/// Objects that still failed after all retries will be kept in staging,
+/// and uploaded again by next write on the same path or next recovery.
+///
+/// # Staging Layout
+///
+/// Every write is staged at `<generation>/<path>`, and marked as committed
+/// by an empty `<generation>.committed` object after the write closed.
+/// Staged objects without the mark are incomplete and will be dropped
+/// while recovering.
+///
+/// The staging accessor should be dedicated to one WriteBackLayer, and the
+/// layer must be applied within a tokio runtime.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::layers::WriteBackLayer;
+/// use opendal::services::fs;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// # let accessor = memory::Backend::build().finish().await?;
+/// let staging = fs::Backend::build().root("/tmp/opendal/staging").finish().await?;
+/// let layer = WriteBackLayer::new(staging);
+/// let op = Operator::new(accessor).layer(layer.clone());
+///
+/// op.object("file").write("Hello, World!").await?;
+/// layer.flush().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct WriteBackLayer {
+    staging: Arc<dyn Accessor>,
+    min_delay: Duration,
+    max_delay: Duration,
+    max_retries: usize,
+    state: Arc<WriteBackState>,
+}
+
+impl WriteBackLayer {
+    /// Create a new WriteBackLayer which stages writes into `staging`.
+    pub fn new(staging: Arc<dyn Accessor>) -> Self {
+        Self {
+            staging,
+            min_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            max_retries: 10,
+            state: Arc::new(WriteBackState::default()),
+        }
+    }
+
+    /// Set the delay before the first retry of uploading, default to 100ms.
+    #[must_use]
+    pub fn with_min_delay(mut self, delay: Duration) -> Self {
+        self.min_delay = delay;
+        self
+    }
+
+    /// Set the max delay between retries of uploading, default to 10s.
+    #[must_use]
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set the max retries of uploading, default to 10.
+    #[must_use]
+    pub fn with_max_retries(mut self, retries: usize) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Wait until all staged objects have been uploaded.
+    ///
+    /// Returns an error if some objects failed to upload after all retries.
+    pub async fn flush(&self) -> Result<()> {
+        loop {
+            // Create the future before checking so that no notification
+            // will be missed.
+            let notified = self.state.idle.notified();
+            {
+                let pending = self.state.pending.lock();
+                if pending.busy == 0 {
+                    let mut failed: Vec<&str> = pending
+                        .entries
+                        .iter()
+                        .filter(|(_, e)| e.failed)
+                        .map(|(path, _)| path.as_str())
+                        .collect();
+                    if failed.is_empty() {
+                        return Ok(());
+                    }
+
+                    failed.sort_unstable();
+                    return Err(other(anyhow!(
+                        "objects failed to upload: {}",
+                        failed.join(", ")
+                    )));
+                }
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Layer for WriteBackLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        let acc = WriteBackAccessor {
+            inner,
+            staging: self.staging.clone(),
+            min_delay: self.min_delay,
+            max_delay: self.max_delay,
+            max_retries: self.max_retries,
+            state: self.state.clone(),
+        };
+
+        // Generations after this are written by this layer, and will not be
+        // touched by recovery.
+        let boundary = acc.state.next_gen();
+        acc.state.pending.lock().busy += 1;
+        tokio::spawn(acc.clone().recover(boundary));
+
+        Arc::new(acc)
+    }
+}
+
+/// Entry of a staged object.
+#[derive(Debug)]
+struct Entry {
+    /// Generation of the latest staged write.
+    gen: u64,
+    size: u64,
+    /// Generation that is being uploaded.
+    uploading: Option<u64>,
+    /// Whether there is a task uploading this object.
+    running: bool,
+    /// Whether the latest upload failed after all retries.
+    failed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    entries: HashMap<String, Entry>,
+    /// Count of running upload and recovery tasks.
+    busy: usize,
+}
+
+#[derive(Debug, Default)]
+struct WriteBackState {
+    pending: Mutex<Pending>,
+    idle: Notify,
+    last_gen: AtomicU64,
+}
+
+impl WriteBackState {
+    /// Generate a new generation which is larger than all existing ones.
+    fn next_gen(&self) -> u64 {
+        let now = OffsetDateTime::now_utc().unix_timestamp_nanos() as u64;
+        let prev = self
+            .last_gen
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| {
+                Some(max(now, v + 1))
+            })
+            .expect("closure always returns Some");
+        max(now, prev + 1)
+    }
+
+    fn done(&self) {
+        let mut pending = self.pending.lock();
+        pending.busy -= 1;
+        if pending.busy == 0 {
+            self.idle.notify_waiters();
+        }
+    }
+}
+
+fn staged_path(gen: u64, path: &str) -> String {
+    format!("{:020}/{}", gen, path)
+}
+
+fn marker_path(gen: u64) -> String {
+    format!("{:020}.committed", gen)
+}
+
+/// Get the parent dir of the path, root will be `/`.
+fn parent(path: &str) -> &str {
+    match path.trim_end_matches('/').rfind('/') {
+        Some(idx) => &path[..=idx],
+        None => "/",
+    }
+}
+
+#[derive(Debug, Clone)]
+struct WriteBackAccessor {
+    inner: Arc<dyn Accessor>,
+    staging: Arc<dyn Accessor>,
+    min_delay: Duration,
+    max_delay: Duration,
+    max_retries: usize,
+    state: Arc<WriteBackState>,
+}
+
+impl WriteBackAccessor {
+    /// Get the staged generation of the object.
+    fn staged(&self, path: &str) -> Option<u64> {
+        self.state.pending.lock().entries.get(path).map(|e| e.gen)
+    }
+
+    /// Create all parent dirs of the staged object, so that the staging can
+    /// be walked on services without implicit dirs.
+    async fn create_parents(&self, path: &str) -> Result<()> {
+        let mut idx = 0;
+        while let Some(pos) = path[idx..].find('/') {
+            idx += pos + 1;
+            self.staging
+                .create(&OpCreate::new(&path[..idx], ObjectMode::DIR)?)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Remove the staged object with its parent dirs and committed mark.
+    async fn remove_staged(&self, gen: u64, path: &str) {
+        let staged = staged_path(gen, path);
+        let root = format!("{:020}/", gen);
+
+        let mut paths = vec![staged.clone()];
+        let mut dir = parent(&staged);
+        while dir != root {
+            paths.push(dir.to_string());
+            dir = parent(dir);
+        }
+        paths.push(root.clone());
+        paths.push(marker_path(gen));
+
+        for p in paths {
+            let res = match OpDelete::new(&p) {
+                Ok(op) => self.staging.delete(&op).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                warn!("staged object {} remove: {:?}", p, e);
+            }
+        }
+    }
+
+    /// Mark the staged write as committed and schedule the uploading.
+    async fn commit(&self, path: &str, gen: u64, size: u64) -> Result<()> {
+        self.staging
+            .create(&OpCreate::new(&marker_path(gen), ObjectMode::FILE)?)
+            .await?;
+        debug!("object {} staged: generation {}", path, gen);
+
+        self.schedule(path, gen, size).await;
+        Ok(())
+    }
+
+    /// Register the staged object and spawn the uploading task if needed.
+    async fn schedule(&self, path: &str, gen: u64, size: u64) {
+        let (spawn, obsolete) = {
+            let mut pending = self.state.pending.lock();
+            let (spawn, obsolete) = match pending.entries.get_mut(path) {
+                // Already scheduled.
+                Some(e) if e.gen == gen => (false, None),
+                Some(e) if e.gen > gen => (false, Some(gen)),
+                Some(e) => {
+                    let obsolete = (e.uploading != Some(e.gen)).then_some(e.gen);
+                    let spawn = !e.running;
+                    e.gen = gen;
+                    e.size = size;
+                    e.failed = false;
+                    e.running = true;
+                    (spawn, obsolete)
+                }
+                None => {
+                    pending.entries.insert(
+                        path.to_string(),
+                        Entry {
+                            gen,
+                            size,
+                            uploading: None,
+                            running: true,
+                            failed: false,
+                        },
+                    );
+                    (true, None)
+                }
+            };
+            if spawn {
+                pending.busy += 1;
+            }
+            (spawn, obsolete)
+        };
+
+        if let Some(gen) = obsolete {
+            self.remove_staged(gen, path).await;
+        }
+        if spawn {
+            tokio::spawn(self.clone().upload(path.to_string()));
+        }
+    }
+
+    /// Upload the object until the latest generation has been uploaded.
+    async fn upload(self, path: String) {
+        loop {
+            let (gen, size) = {
+                let mut pending = self.state.pending.lock();
+                match pending.entries.get_mut(&path) {
+                    Some(e) => {
+                        e.uploading = Some(e.gen);
+                        (e.gen, e.size)
+                    }
+                    None => break,
+                }
+            };
+
+            let res = self.upload_with_retry(&path, gen, size).await;
+
+            let (retry, keep_staged) = {
+                let mut pending = self.state.pending.lock();
+                match pending.entries.get_mut(&path) {
+                    // Latest generation has been handled.
+                    Some(e) if e.gen == gen => {
+                        if res.is_ok() {
+                            debug!("object {} uploaded: generation {}", path, gen);
+                            pending.entries.remove(&path);
+                        } else {
+                            e.uploading = None;
+                            e.running = false;
+                            e.failed = true;
+                        }
+                        (false, res.is_err())
+                    }
+                    // Object has been written again during uploading.
+                    Some(e) => {
+                        e.uploading = None;
+                        (true, false)
+                    }
+                    // Object has been deleted during uploading.
+                    None => (false, false),
+                }
+            };
+
+            if !keep_staged {
+                self.remove_staged(gen, &path).await;
+            }
+            if !retry {
+                break;
+            }
+        }
+
+        self.state.done()
+    }
+
+    async fn upload_with_retry(&self, path: &str, gen: u64, size: u64) -> Result<()> {
+        let mut delay = self.min_delay;
+        let mut retries = 0;
+        loop {
+            let err = match self.upload_once(path, gen, size).await {
+                Ok(_) => break,
+                Err(e) => e,
+            };
+
+            // Stop retrying if the object has been deleted or written again.
+            if self.staged(path) != Some(gen) {
+                return Err(err);
+            }
+            if retries >= self.max_retries {
+                warn!(
+                    "object {} upload failed after {} retries: {:?}",
+                    path, retries, err
+                );
+                return Err(err);
+            }
+
+            warn!(
+                "object {} upload failed, retry after {:?}: {:?}",
+                path, delay, err
+            );
+            tokio::time::sleep(delay).await;
+            delay = min(delay * 2, self.max_delay);
+            retries += 1;
+        }
+
+        // Object could be deleted during uploading, delete it again.
+        if self.staged(path).is_none() {
+            self.inner.delete(&OpDelete::new(path)?).await?;
+        }
+        Ok(())
+    }
+
+    async fn upload_once(&self, path: &str, gen: u64, size: u64) -> Result<()> {
+        let r = self
+            .staging
+            .read(&OpRead::new(&staged_path(gen, path), ..)?)
+            .await?;
+        let mut w = self.inner.write(&OpWrite::new(path, size)?).await?;
+        io::copy(r, &mut w).await?;
+        w.close().await
+    }
+
+    /// Recover committed objects left in staging before `boundary`.
+    async fn recover(self, boundary: u64) {
+        if let Err(e) = self.recover_staged(boundary).await {
+            warn!("staged objects recover: {:?}", e);
+        }
+        self.state.done()
+    }
+
+    async fn recover_staged(&self, boundary: u64) -> Result<()> {
+        let op = Operator::new(self.staging.clone());
+
+        let mut committed = Vec::new();
+        let mut dirs = Vec::new();
+        let mut s = op.object("/").list().await?;
+        while let Some(de) = s.try_next().await? {
+            let path = de.path();
+            let (gen, is_marker) = match path.strip_suffix(".committed") {
+                Some(gen) => (gen, true),
+                None => (path.trim_end_matches('/'), false),
+            };
+            match gen.parse::<u64>() {
+                Ok(gen) if gen < boundary && is_marker => committed.push(gen),
+                Ok(gen) if gen < boundary => dirs.push(gen),
+                _ => {}
+            }
+        }
+
+        // Drop incomplete writes and marks without objects.
+        for gen in dirs.iter().filter(|gen| !committed.contains(gen)) {
+            debug!("staged generation {} is incomplete, dropping", gen);
+            op.batch().remove_all(&format!("{:020}/", gen)).await?;
+        }
+        for gen in committed.iter().filter(|gen| !dirs.contains(gen)) {
+            op.object(&marker_path(*gen)).delete().await?;
+        }
+
+        let mut objects = BTreeMap::new();
+        for gen in committed.into_iter().filter(|gen| dirs.contains(gen)) {
+            let dir = format!("{:020}/", gen);
+            let mut s = op.batch().walk_top_down(&dir)?;
+            while let Some(de) = s.try_next().await? {
+                if de.mode() != ObjectMode::FILE {
+                    continue;
+                }
+                let size = de.metadata().await?.content_length();
+                objects.insert(gen, (de.path()[dir.len()..].to_string(), size));
+            }
+        }
+
+        // Generations are recovered in order so that the latest one wins.
+        for (gen, (path, size)) in objects {
+            self.state.last_gen.fetch_max(gen, Ordering::SeqCst);
+            debug!("object {} recovered: generation {}", path, gen);
+            self.schedule(&path, gen, size).await;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Accessor for WriteBackAccessor {
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        if args.mode() != ObjectMode::FILE {
+            return self.inner.create(args).await;
+        }
+
+        // Stage an empty object so that it's ordered with other writes.
+        let mut w = self.write(&OpWrite::new(args.path(), 0)?).await?;
+        w.close().await
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        if let Some(gen) = self.staged(args.path()) {
+            let staged = staged_path(gen, args.path());
+            match self.staging.read(&args.clone().with_path(&staged)).await {
+                Ok(r) => return Ok(r),
+                // Staged object has been uploaded and removed.
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.inner.read(args).await
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        let gen = self.state.next_gen();
+        let staged = staged_path(gen, args.path());
+
+        self.create_parents(&staged).await?;
+        let w = self.staging.write(&args.clone().with_path(&staged)).await?;
+
+        Ok(Box::new(WriteBackWriter {
+            inner: w,
+            acc: self.clone(),
+            path: args.path().to_string(),
+            gen,
+            size: args.size(),
+            state: WriterState::Writing,
+        }))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        if let Some(gen) = self.staged(args.path()) {
+            let staged = staged_path(gen, args.path());
+            match self.staging.stat(&args.clone().with_path(&staged)).await {
+                Ok(meta) => return Ok(meta),
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.inner.stat(args).await
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let removed = self.state.pending.lock().entries.remove(args.path());
+        if let Some(e) = removed {
+            // Generation that is uploading will be removed by the task.
+            if e.uploading != Some(e.gen) {
+                self.remove_staged(e.gen, args.path()).await;
+            }
+        }
+
+        self.inner.delete(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let mut entries: Vec<DirEntry> = self.inner.list(args).await?.try_collect().await?;
+
+        // Append staged objects which are not uploaded yet.
+        let mut staged: Vec<String> = self
+            .state
+            .pending
+            .lock()
+            .entries
+            .keys()
+            .filter(|path| parent(path) == args.path())
+            .filter(|path| !entries.iter().any(|de| de.path() == path.as_str()))
+            .cloned()
+            .collect();
+        staged.sort_unstable();
+
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        for path in staged {
+            entries.push(DirEntry::new(acc.clone(), ObjectMode::FILE, &path));
+        }
+
+        // Entries must be rebuilt so that operations on them go through
+        // the staging.
+        Ok(Box::new(stream::iter(entries.into_iter().map(move |de| {
+            Ok(DirEntry::new(acc.clone(), de.mode(), de.path()))
+        }))))
+    }
+}
+
+enum WriterState {
+    Writing,
+    Committing(BoxFuture<'static, Result<()>>),
+    Done,
+}
+
+/// WriteBackWriter will commit the staged object after closed.
+struct WriteBackWriter {
+    inner: BytesWriter,
+    acc: WriteBackAccessor,
+    path: String,
+    gen: u64,
+    size: u64,
+    state: WriterState,
+}
+
+impl AsyncWrite for WriteBackWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        loop {
+            match &mut self.state {
+                WriterState::Writing => {
+                    ready!(Pin::new(&mut self.inner).poll_close(cx))?;
+
+                    let acc = self.acc.clone();
+                    let path = self.path.clone();
+                    let (gen, size) = (self.gen, self.size);
+                    let fut = async move { acc.commit(&path, gen, size).await };
+                    self.state = WriterState::Committing(fut.boxed());
+                }
+                WriterState::Committing(fut) => {
+                    let res = ready!(fut.poll_unpin(cx));
+                    self.state = WriterState::Done;
+                    return Poll::Ready(res);
+                }
+                WriterState::Done => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::services::memory;
+
+    /// FlakyAccessor fails the first `failures` writes.
+    #[derive(Debug)]
+    struct FlakyAccessor {
+        inner: Arc<dyn Accessor>,
+        failures: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Accessor for FlakyAccessor {
+        fn metadata(&self) -> AccessorMetadata {
+            self.inner.metadata()
+        }
+
+        async fn create(&self, args: &OpCreate) -> Result<()> {
+            self.inner.create(args).await
+        }
+
+        async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+            self.inner.read(args).await
+        }
+
+        async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+            let failed = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| v.checked_sub(1))
+                .is_ok();
+            if failed {
+                return Err(Error::new(ErrorKind::Interrupted, "injected failure"));
+            }
+            self.inner.write(args).await
+        }
+
+        async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+            self.inner.stat(args).await
+        }
+
+        async fn delete(&self, args: &OpDelete) -> Result<()> {
+            self.inner.delete(args).await
+        }
+
+        async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+            self.inner.list(args).await
+        }
+    }
+
+    async fn flaky(failures: usize) -> anyhow::Result<(Operator, Arc<FlakyAccessor>)> {
+        let remote = memory::Backend::build().finish().await?;
+        let acc = Arc::new(FlakyAccessor {
+            inner: remote.clone(),
+            failures: AtomicUsize::new(failures),
+        });
+        Ok((Operator::new(remote), acc))
+    }
+
+    async fn staged_objects(staging: &Operator) -> anyhow::Result<Vec<String>> {
+        let entries: Vec<DirEntry> = staging.object("/").list().await?.try_collect().await?;
+        Ok(entries.iter().map(|de| de.path().to_string()).collect())
+    }
+
+    #[tokio::test]
+    async fn test_write_back() -> anyhow::Result<()> {
+        let (remote, acc) = flaky(2).await?;
+        let staging_acc = memory::Backend::build().finish().await?;
+        let staging = Operator::new(staging_acc.clone());
+        let layer =
+            WriteBackLayer::new(staging_acc.clone()).with_min_delay(Duration::from_millis(10));
+        let op = Operator::new(acc).layer(layer.clone());
+
+        op.object("dir/").create().await?;
+        op.object("dir/file").write("Hello, World!").await?;
+
+        // Staged object is visible before uploaded.
+        assert_eq!(op.object("dir/file").read().await?, b"Hello, World!");
+        assert_eq!(op.object("dir/file").metadata().await?.content_length(), 13);
+        let entries: Vec<DirEntry> = op.object("dir/").list().await?.try_collect().await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path(), "dir/file");
+
+        layer.flush().await?;
+        assert_eq!(remote.object("dir/file").read().await?, b"Hello, World!");
+        assert!(staged_objects(&staging).await?.is_empty());
+
+        op.object("dir/file").delete().await?;
+        assert!(!remote.object("dir/file").is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_back_recover() -> anyhow::Result<()> {
+        let staging_acc = memory::Backend::build().finish().await?;
+        let staging = Operator::new(staging_acc.clone());
+
+        // Uploading keeps failing, objects are left in staging.
+        let (_, acc) = flaky(usize::MAX).await?;
+        let layer = WriteBackLayer::new(staging_acc.clone()).with_max_retries(0);
+        let op = Operator::new(acc).layer(layer.clone());
+        op.object("file").write("Hello").await?;
+        op.object("file").write("Hello, World!").await?;
+        assert!(layer.flush().await.is_err());
+
+        // Incomplete write without committed mark.
+        staging.object("00000000000000000001/").create().await?;
+        staging
+            .object("00000000000000000001/partial")
+            .write("Hello")
+            .await?;
+
+        // Restart with a healthy remote.
+        let (remote, acc) = flaky(0).await?;
+        let layer = WriteBackLayer::new(staging_acc.clone());
+        let _op = Operator::new(acc).layer(layer.clone());
+        layer.flush().await?;
+
+        assert_eq!(remote.object("file").read().await?, b"Hello, World!");
+        assert!(!remote.object("partial").is_exist().await?);
+        assert!(staged_objects(&staging).await?.is_empty());
+
+        Ok(())
+    }
+}
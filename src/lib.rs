@@ -19,16 +19,24 @@
 //! | Services | Description |
 //! | -------- | ----------- |
 //! | [azblob][crate::services::azblob] | Azure Storage Blob services. |
+//! | [erasure][crate::services::erasure] | Erasure coded storage across multiple backends. |
 //! | [fs][crate::services::fs] | POSIX alike file system. |
 //! | [hdfs][crate::services::hdfs] | Hadoop Distributed File System(HDFS). |
 //! | [http][crate::services::http] | HTTP read-only backend. |
 //! | [memory][crate::services::memory] | In memory backend support. |
+//! | [mount][crate::services::mount] | Mount table which routes paths to multiple backends. |
 //! | [s3][crate::services::s3] | AWS S3 alike services. |
 //!
 //! # Optional features
 //!
+//! - `audit`: Enable audit log layer support.
+//! - `chaos`: Enable fault injection layer support.
+//! - `checksum`: Enable checksum verification layer support.
 //! - `compress`: Enable object decompress read support.
+//! - `encryption`: Enable client-side encryption layer support.
+//! - `record`: Enable record/replay layer support.
 //! - `retry`: Enable operator retry support.
+//! - `services-erasure`: Enable erasure coded service support.
 //! - `services-hdfs`: Enable hdfs service support.
 //! - `services-http`: Enable http service support.
 //!
@@ -101,7 +109,6 @@ pub use io::BytesStream;
 pub use io::BytesWrite;
 pub use io::BytesWriter;
 
-mod layers;
 pub use layers::Layer;
 
 mod operator;
@@ -123,6 +130,7 @@ pub use scheme::Scheme;
 
 // Public modules, they will be accessed via `opendal::io_util::Xxxx`
 pub mod io_util;
+pub mod layers;
 pub mod ops;
 pub mod services;
 
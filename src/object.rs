@@ -32,10 +32,12 @@ use crate::io_util::CompressAlgorithm;
 #[cfg(feature = "compress")]
 use crate::io_util::DecompressReader;
 use crate::io_util::SeekableReader;
+use crate::ops::OpCopy;
 use crate::ops::OpCreate;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
 use crate::ops::OpRead;
+use crate::ops::OpRename;
 use crate::ops::OpStat;
 use crate::ops::OpWrite;
 use crate::path::get_basename;
@@ -559,6 +561,60 @@ impl Object {
         self.acc.delete(op).await
     }
 
+    /// Copy object to `to`.
+    ///
+    /// # Notes
+    ///
+    /// - Services without native copy will stream the content through the
+    ///   client.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use opendal::services::memory;
+    /// # use anyhow::Result;
+    /// # use opendal::Operator;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// # let op = Operator::new(memory::Backend::build().finish().await?);
+    /// op.object("test").write("Hello, World!").await?;
+    /// op.object("test").copy("copied").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn copy(&self, to: &str) -> Result<()> {
+        let op = &OpCopy::new(self.path(), &normalize_path(to))?;
+
+        self.acc.copy(op).await
+    }
+
+    /// Rename object to `to`.
+    ///
+    /// # Notes
+    ///
+    /// - Services without native rename will copy the object and then
+    ///   delete it, which is not atomic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use opendal::services::memory;
+    /// # use anyhow::Result;
+    /// # use opendal::Operator;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// # let op = Operator::new(memory::Backend::build().finish().await?);
+    /// op.object("test").write("Hello, World!").await?;
+    /// op.object("test").rename("renamed").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn rename(&self, to: &str) -> Result<()> {
+        let op = &OpRename::new(self.path(), &normalize_path(to))?;
+
+        self.acc.rename(op).await
+    }
+
     /// List current dir object.
     ///
     /// This function will create a new [`DirStreamer`] handle to list objects.
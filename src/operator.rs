@@ -24,6 +24,8 @@ use log::debug;
 
 use crate::io_util::BottomUpWalker;
 use crate::io_util::TopDownWalker;
+use crate::layers::PrefixLayer;
+use crate::layers::ReadOnlyLayer;
 use crate::Accessor;
 use crate::AccessorMetadata;
 use crate::DirStreamer;
@@ -87,6 +89,38 @@ impl Operator {
         }
     }
 
+    /// Create a new operator whose root is `path` under current root.
+    ///
+    /// Paths of the new operator can't escape `path`, see
+    /// [`PrefixLayer`][crate::layers::PrefixLayer] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use anyhow::Result;
+    /// # use opendal::services::memory;
+    /// # use opendal::Operator;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// # let accessor = memory::Backend::build().finish().await?;
+    /// let op = Operator::new(accessor);
+    /// let tenant = op.subdir("tenant-a/")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subdir(&self, path: &str) -> Result<Self> {
+        Ok(self.clone().layer(PrefixLayer::new(path)?))
+    }
+
+    /// Create a new read-only operator, all mutating operations will fail
+    /// with `PermissionDenied`.
+    ///
+    /// See [`ReadOnlyLayer`] for more details.
+    pub fn read_only(&self) -> Self {
+        self.clone().layer(ReadOnlyLayer)
+    }
+
     /// Configure backoff for operators
     ///
     /// This function only provided if feature `retry` is enabled.
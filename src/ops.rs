@@ -17,6 +17,9 @@
 //! Users should not use struct or functions here, use [`Operator`][crate::Operator] instead
 
 use std::collections::Bound;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
 use std::io::Result;
 use std::ops::RangeBounds;
 
@@ -26,6 +29,43 @@ use crate::error::other;
 use crate::error::ObjectError;
 use crate::ObjectMode;
 
+/// Operations that provided by [`Accessor`][crate::Accessor].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Operation {
+    /// `create` operation.
+    Create,
+    /// `read` operation.
+    Read,
+    /// `write` operation.
+    Write,
+    /// `stat` operation.
+    Stat,
+    /// `delete` operation.
+    Delete,
+    /// `list` operation.
+    List,
+}
+
+impl Operation {
+    /// Get the name of operation.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Create => "create",
+            Operation::Read => "read",
+            Operation::Write => "write",
+            Operation::Stat => "stat",
+            Operation::Delete => "delete",
+            Operation::List => "list",
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// Args for `create` operation.
 ///
 /// The path must be normalized.
@@ -81,6 +121,12 @@ impl OpCreate {
         &self.path
     }
 
+    /// Replace path of this operation, used by layers that rebase paths.
+    pub(crate) fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
     /// Get object mode from option.
     pub fn mode(&self) -> ObjectMode {
         self.mode
@@ -95,6 +141,7 @@ pub struct OpRead {
     path: String,
     offset: Option<u64>,
     size: Option<u64>,
+    if_match: Option<String>,
 }
 
 impl OpRead {
@@ -116,6 +163,7 @@ impl OpRead {
             path: path.to_string(),
             offset: br.offset(),
             size: br.size(),
+            if_match: None,
         })
     }
 
@@ -136,14 +184,31 @@ impl OpRead {
             path: path.to_string(),
             offset,
             size,
+            if_match: None,
         })
     }
 
+    /// Only read the object if its ETag matches the input `etag`.
+    ///
+    /// Services that support conditional read SHOULD fail the read if
+    /// the ETag doesn't match.
+    #[must_use]
+    pub fn with_if_match(mut self, etag: &str) -> Self {
+        self.if_match = Some(etag.to_string());
+        self
+    }
+
     /// Get path from option.
     pub fn path(&self) -> &str {
         &self.path
     }
 
+    /// Replace path of this operation, used by layers that rebase paths.
+    pub(crate) fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
     /// Get offset from option.
     pub fn offset(&self) -> Option<u64> {
         self.offset
@@ -153,6 +218,11 @@ impl OpRead {
     pub fn size(&self) -> Option<u64> {
         self.size
     }
+
+    /// Get if_match from option.
+    pub fn if_match(&self) -> Option<&str> {
+        self.if_match.as_deref()
+    }
 }
 
 /// Args for `stat` operation.
@@ -175,6 +245,12 @@ impl OpStat {
     pub fn path(&self) -> &str {
         &self.path
     }
+
+    /// Replace path of this operation, used by layers that rebase paths.
+    pub(crate) fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
 }
 
 /// Args for `write` operation.
@@ -184,6 +260,8 @@ impl OpStat {
 pub struct OpWrite {
     path: String,
     size: u64,
+    content_md5: Option<String>,
+    checksum: Option<(ChecksumAlgorithm, String)>,
 }
 
 impl OpWrite {
@@ -202,18 +280,65 @@ impl OpWrite {
         Ok(Self {
             path: path.to_string(),
             size,
+            content_md5: None,
+            checksum: None,
         })
     }
 
+    /// Set the base64 encoded MD5 of the content.
+    ///
+    /// Services that support `Content-MD5` SHOULD reject the write if the
+    /// content doesn't match.
+    #[must_use]
+    pub fn with_content_md5(mut self, content_md5: &str) -> Self {
+        self.content_md5 = Some(content_md5.to_string());
+        self
+    }
+
+    /// Set the base64 encoded checksum of the content.
+    ///
+    /// Services that support the algorithm SHOULD reject the write if the
+    /// content doesn't match, others will ignore it.
+    #[must_use]
+    pub fn with_checksum(mut self, algorithm: ChecksumAlgorithm, checksum: &str) -> Self {
+        self.checksum = Some((algorithm, checksum.to_string()));
+        self
+    }
+
     /// Get path from option.
     pub fn path(&self) -> &str {
         &self.path
     }
 
+    /// Replace path of this operation, used by layers that rebase paths.
+    pub(crate) fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
     /// Get size from option.
     pub fn size(&self) -> u64 {
         self.size
     }
+
+    /// Get content_md5 from option.
+    pub fn content_md5(&self) -> Option<&str> {
+        self.content_md5.as_deref()
+    }
+
+    /// Get checksum from option.
+    pub fn checksum(&self) -> Option<(ChecksumAlgorithm, &str)> {
+        self.checksum.as_ref().map(|(a, v)| (*a, v.as_str()))
+    }
+}
+
+/// Algorithms of checksums that can be sent along with `write`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChecksumAlgorithm {
+    /// CRC32C, big-endian.
+    Crc32c,
+    /// SHA-256.
+    Sha256,
 }
 
 /// Args for `delete` operation.
@@ -236,6 +361,101 @@ impl OpDelete {
     pub fn path(&self) -> &str {
         &self.path
     }
+
+    /// Replace path of this operation, used by layers that rebase paths.
+    pub(crate) fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+}
+
+/// Args for `copy` operation.
+///
+/// The paths must be normalized.
+#[derive(Debug, Clone, Default)]
+pub struct OpCopy {
+    from: String,
+    to: String,
+}
+
+impl OpCopy {
+    /// Create a new `OpCopy`.
+    ///
+    /// If input paths are not file paths, an error will be returned.
+    pub fn new(from: &str, to: &str) -> Result<Self> {
+        check_file_paths("copy", from, to)?;
+
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+
+    /// Get the source path from option.
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// Get the target path from option.
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    /// Replace paths of this operation, used by layers that rebase paths.
+    pub(crate) fn with_paths(mut self, from: &str, to: &str) -> Self {
+        self.from = from.to_string();
+        self.to = to.to_string();
+        self
+    }
+}
+
+/// Args for `rename` operation.
+///
+/// The paths must be normalized.
+#[derive(Debug, Clone, Default)]
+pub struct OpRename {
+    from: String,
+    to: String,
+}
+
+impl OpRename {
+    /// Create a new `OpRename`.
+    ///
+    /// If input paths are not file paths, an error will be returned.
+    pub fn new(from: &str, to: &str) -> Result<Self> {
+        check_file_paths("rename", from, to)?;
+
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+
+    /// Get the source path from option.
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// Get the target path from option.
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    /// Replace paths of this operation, used by layers that rebase paths.
+    pub(crate) fn with_paths(mut self, from: &str, to: &str) -> Self {
+        self.from = from.to_string();
+        self.to = to.to_string();
+        self
+    }
+}
+
+fn check_file_paths(op: &'static str, from: &str, to: &str) -> Result<()> {
+    for path in [from, to] {
+        if path.ends_with('/') {
+            return Err(other(ObjectError::new(op, path, anyhow!("Is a directory"))));
+        }
+    }
+    Ok(())
 }
 
 /// Args for `list` operation.
@@ -268,6 +488,12 @@ impl OpList {
     pub fn path(&self) -> &str {
         &self.path
     }
+
+    /// Replace path of this operation, used by layers that rebase paths.
+    pub(crate) fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
 }
 
 /// BytesRange(offset, size) carries a range of content.
@@ -24,6 +24,9 @@ use crate::error::BackendError;
 pub enum Scheme {
     /// [azblob][crate::services::azblob]: Azure Storage Blob services.
     Azblob,
+    /// [erasure][crate::services::erasure]: Erasure coded storage across multiple backends.
+    #[cfg(feature = "services-erasure")]
+    Erasure,
     /// [fs][crate::services::fs]: POSIX alike file system.
     Fs,
     /// [hdfs][crate::services::hdfs]: Hadoop Distributed File System.
@@ -34,6 +37,8 @@ pub enum Scheme {
     Http,
     /// [memory][crate::services::memory]: In memory backend support.
     Memory,
+    /// [mount][crate::services::mount]: Mount table which routes paths to multiple backends.
+    Mount,
     /// [s3][crate::services::s3]: AWS S3 alike services.
     S3,
 }
@@ -16,6 +16,7 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::io::Error;
 use std::io::ErrorKind;
 use std::io::Result;
 use std::mem;
@@ -44,6 +45,7 @@ use crate::io_util::new_http_channel;
 use crate::io_util::parse_content_length;
 use crate::io_util::parse_error_response;
 use crate::io_util::parse_etag;
+use crate::io_util::parse_hyper_error_kind;
 use crate::io_util::parse_last_modified;
 use crate::io_util::HttpBodyWriter;
 use crate::io_util::HttpClient;
@@ -278,10 +280,13 @@ impl Accessor for Backend {
         increment_counter!("opendal_azblob_create_requests");
         let p = self.get_abs_path(args.path());
 
-        let req = self.put_blob(&p, 0, Body::empty()).await?;
+        let req = self.put_blob(&p, 0, None, Body::empty()).await?;
         let resp = self.client.request(req).await.map_err(|e| {
             error!("object {} put_object: {:?}", args.path(), e);
-            other(ObjectError::new("read", args.path(), e))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("read", args.path(), e),
+            )
         })?;
 
         match resp.status() {
@@ -309,7 +314,9 @@ impl Accessor for Backend {
             args.size()
         );
 
-        let resp = self.get_blob(&p, args.offset(), args.size()).await?;
+        let resp = self
+            .get_blob(&p, args.offset(), args.size(), args.if_match())
+            .await?;
         match resp.status() {
             StatusCode::OK | StatusCode::PARTIAL_CONTENT => {
                 debug!(
@@ -322,7 +329,9 @@ impl Accessor for Backend {
                 Ok(Box::new(
                     resp.into_body()
                         .into_stream()
-                        .map_err(move |e| other(ObjectError::new("read", &p, e)))
+                        .map_err(move |e| {
+                            Error::new(parse_hyper_error_kind(&e), ObjectError::new("read", &p, e))
+                        })
                         .into_async_read(),
                 ))
             }
@@ -341,7 +350,9 @@ impl Accessor for Backend {
 
         let (tx, body) = new_http_channel();
 
-        let req = self.put_blob(&p, args.size(), body).await?;
+        let req = self
+            .put_blob(&p, args.size(), args.content_md5(), body)
+            .await?;
 
         let bs = HttpBodyWriter::new(
             args,
@@ -457,6 +468,7 @@ impl Backend {
         path: &str,
         offset: Option<u64>,
         size: Option<u64>,
+        if_match: Option<&str>,
     ) -> Result<hyper::Response<hyper::Body>> {
         let url = format!("{}/{}/{}", self.endpoint, self.container, path);
 
@@ -469,6 +481,10 @@ impl Backend {
             );
         }
 
+        if let Some(etag) = if_match {
+            req = req.header(http::header::IF_MATCH, etag);
+        }
+
         let mut req = req.body(hyper::Body::empty()).map_err(|e| {
             error!("object {path} get_blob: {url} {e:?}");
             other(ObjectError::new(
@@ -489,11 +505,10 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} get_blob: {url} {e:?}");
-            other(ObjectError::new(
-                "read",
-                path,
-                anyhow!("send request {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("read", path, anyhow!("send request {url}: {e:?}")),
+            )
         })
     }
 
@@ -502,6 +517,7 @@ impl Backend {
         &self,
         path: &str,
         size: u64,
+        content_md5: Option<&str>,
         body: Body,
     ) -> Result<hyper::Request<hyper::Body>> {
         let url = format!("{}/{}/{}", self.endpoint, self.container, path);
@@ -510,6 +526,10 @@ impl Backend {
 
         req = req.header(http::header::CONTENT_LENGTH, size.to_string());
 
+        if let Some(v) = content_md5 {
+            req = req.header(HeaderName::from_static("content-md5"), v);
+        }
+
         req = req.header(HeaderName::from_static(X_MS_BLOB_TYPE), "BlockBlob");
 
         // Set body
@@ -563,11 +583,10 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} get_blob_properties: {url} {e:?}");
-            other(ObjectError::new(
-                "stat",
-                path,
-                anyhow!("send request {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("stat", path, anyhow!("send request {url}: {e:?}")),
+            )
         })
     }
 
@@ -597,11 +616,10 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} delete_object: {url} {e:?}");
-            other(ObjectError::new(
-                "delete",
-                path,
-                anyhow!("send request {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("delete", path, anyhow!("send request {url}: {e:?}")),
+            )
         })
     }
 
@@ -644,23 +662,88 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} list_blobs: {url} {e:?}");
-            other(ObjectError::new(
-                "list",
-                path,
-                anyhow!("send request {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("list", path, anyhow!("send request {url}: {e:?}")),
+            )
         })
     }
 }
 
-pub fn parse_error_kind(code: StatusCode) -> ErrorKind {
+/// Decide the [`ErrorKind`] via the status code and Azure Storage error code.
+///
+/// Read [Blob service error codes](https://docs.microsoft.com/en-us/rest/api/storageservices/blob-service-error-codes)
+/// for all error codes.
+pub fn parse_error_kind(status: StatusCode, code: Option<&str>) -> ErrorKind {
     match code {
+        Some("BlobNotFound" | "ContainerNotFound") => return ErrorKind::NotFound,
+        Some(
+            "AuthenticationFailed"
+            | "AuthorizationFailure"
+            | "AuthorizationPermissionMismatch"
+            | "InsufficientAccountPermissions",
+        ) => return ErrorKind::PermissionDenied,
+        Some("ServerBusy" | "InternalError" | "OperationTimedOut") => {
+            return ErrorKind::Interrupted
+        }
+        _ => {}
+    }
+
+    match status {
         StatusCode::NOT_FOUND => ErrorKind::NotFound,
         StatusCode::FORBIDDEN => ErrorKind::PermissionDenied,
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::REQUEST_TIMEOUT
+        | StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::INTERNAL_SERVER_ERROR
         | StatusCode::BAD_GATEWAY
         | StatusCode::SERVICE_UNAVAILABLE
         | StatusCode::GATEWAY_TIMEOUT => ErrorKind::Interrupted,
         _ => ErrorKind::Other,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_kind() {
+        let cases = vec![
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Some("ServerBusy"),
+                ErrorKind::Interrupted,
+            ),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Some("OperationTimedOut"),
+                ErrorKind::Interrupted,
+            ),
+            (
+                StatusCode::NOT_FOUND,
+                Some("BlobNotFound"),
+                ErrorKind::NotFound,
+            ),
+            (
+                StatusCode::FORBIDDEN,
+                Some("AuthorizationPermissionMismatch"),
+                ErrorKind::PermissionDenied,
+            ),
+            (StatusCode::FORBIDDEN, None, ErrorKind::PermissionDenied),
+            (StatusCode::TOO_MANY_REQUESTS, None, ErrorKind::Interrupted),
+            (
+                StatusCode::BAD_REQUEST,
+                Some("InvalidHeaderValue"),
+                ErrorKind::Other,
+            ),
+        ];
+
+        for (status, code, expected) in cases {
+            assert_eq!(
+                parse_error_kind(status, code),
+                expected,
+                "{status} {code:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,982 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::max;
+use std::cmp::min;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::future::join_all;
+use futures::future::BoxFuture;
+use futures::io::Cursor;
+use futures::ready;
+use futures::stream;
+use futures::AsyncReadExt;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use futures::FutureExt;
+use futures::TryStreamExt;
+use log::debug;
+use log::warn;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use time::OffsetDateTime;
+
+use crate::error::other;
+use crate::error::BackendError;
+use crate::error::ObjectError;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+use crate::Scheme;
+
+/// Magic bytes at the beginning of every shard.
+const SHARD_MAGIC: &[u8; 8] = b"ODALEC01";
+/// Size of the shard header.
+const HEADER_SIZE: usize = 48;
+
+/// Builder for erasure backend.
+#[derive(Default)]
+pub struct Builder {
+    shards: Vec<Arc<dyn Accessor>>,
+    parity_shards: usize,
+    write_quorum: Option<usize>,
+}
+
+impl Builder {
+    /// Add an accessor to store a shard.
+    ///
+    /// Every object will be stored as one shard on every accessor, the last
+    /// `parity_shards` accessors will store parity shards.
+    pub fn shard(&mut self, accessor: Arc<dyn Accessor>) -> &mut Self {
+        self.shards.push(accessor);
+
+        self
+    }
+
+    /// Set the count of parity shards, objects can be read as long as no
+    /// more than `parity_shards` shards are missing or corrupt.
+    pub fn parity_shards(&mut self, parity_shards: usize) -> &mut Self {
+        self.parity_shards = parity_shards;
+
+        self
+    }
+
+    /// Set the count of shards that must be written for a write to succeed.
+    ///
+    /// Default to all shards, and must not be less than data shards.
+    pub fn write_quorum(&mut self, write_quorum: usize) -> &mut Self {
+        self.write_quorum = Some(write_quorum);
+
+        self
+    }
+
+    /// Consume builder to build an erasure backend.
+    pub async fn finish(&mut self) -> Result<Arc<Backend>> {
+        let total = self.shards.len();
+        let parity = self.parity_shards;
+        let context = || {
+            HashMap::from([
+                ("shards".to_string(), total.to_string()),
+                ("parity_shards".to_string(), parity.to_string()),
+            ])
+        };
+
+        if parity == 0 || parity >= total {
+            return Err(other(BackendError::new(
+                context(),
+                anyhow!("parity shards must be in [1, shards)"),
+            )));
+        }
+        let data = total - parity;
+        let codec = ReedSolomon::new(data, parity)
+            .map_err(|e| other(BackendError::new(context(), anyhow!("{}", e))))?;
+
+        let write_quorum = self.write_quorum.unwrap_or(total);
+        if write_quorum < data || write_quorum > total {
+            return Err(other(BackendError::new(
+                context(),
+                anyhow!(
+                    "write quorum {} must be in [data shards, shards]",
+                    write_quorum
+                ),
+            )));
+        }
+
+        debug!(
+            "backend build finished: data shards {}, parity shards {}",
+            data, parity
+        );
+        Ok(Arc::new(Backend {
+            shards: Arc::new(std::mem::take(&mut self.shards)),
+            data_shards: data,
+            parity_shards: parity,
+            write_quorum,
+            codec: Arc::new(codec),
+            last_id: Arc::new(AtomicU64::new(0)),
+        }))
+    }
+}
+
+/// Header of a shard.
+///
+/// All integers are encoded in big endian:
+///
+/// ```text
+/// | magic (8) | data shards (2) | parity shards (2) | index (2) | reserved (2) |
+/// | object size (8) | write id (8) | md5 of shard data (16) |
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ShardHeader {
+    data_shards: u16,
+    parity_shards: u16,
+    index: u16,
+    size: u64,
+    id: u64,
+    digest: [u8; 16],
+}
+
+impl ShardHeader {
+    fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut bs = [0; HEADER_SIZE];
+        bs[..8].copy_from_slice(SHARD_MAGIC);
+        bs[8..10].copy_from_slice(&self.data_shards.to_be_bytes());
+        bs[10..12].copy_from_slice(&self.parity_shards.to_be_bytes());
+        bs[12..14].copy_from_slice(&self.index.to_be_bytes());
+        bs[16..24].copy_from_slice(&self.size.to_be_bytes());
+        bs[24..32].copy_from_slice(&self.id.to_be_bytes());
+        bs[32..48].copy_from_slice(&self.digest);
+        bs
+    }
+
+    fn decode(bs: &[u8]) -> Option<Self> {
+        if bs.len() < HEADER_SIZE || &bs[..8] != SHARD_MAGIC {
+            return None;
+        }
+
+        let u16_at = |idx: usize| u16::from_be_bytes([bs[idx], bs[idx + 1]]);
+        let u64_at = |idx: usize| {
+            let mut v = [0; 8];
+            v.copy_from_slice(&bs[idx..idx + 8]);
+            u64::from_be_bytes(v)
+        };
+        let mut digest = [0; 16];
+        digest.copy_from_slice(&bs[32..48]);
+
+        Some(ShardHeader {
+            data_shards: u16_at(8),
+            parity_shards: u16_at(10),
+            index: u16_at(12),
+            size: u64_at(16),
+            id: u64_at(24),
+            digest,
+        })
+    }
+}
+
+/// State of a shard loaded from its accessor.
+#[derive(Debug)]
+enum Shard {
+    Valid(ShardHeader, Vec<u8>),
+    Missing,
+    Broken(String),
+}
+
+/// Backend stores every object as Reed-Solomon coded data and parity shards
+/// on multiple accessors.
+///
+/// # Behavior
+///
+/// - `write` buffers the whole object, then splits it into data shards,
+///   encodes parity shards and writes one shard to every accessor.
+/// - `read` and `stat` fetch headers of all shards to find the latest
+///   write, which must have at least data shards count of valid headers.
+/// - `read` fetches only the data shards that cover the range, and
+///   reconstructs the object with parity shards if some of them are
+///   missing, corrupt or stale.
+/// - `stat` reports the logical size from shard headers.
+/// - `list` merges entries from all accessors.
+/// - [`Backend::repair`] rewrites missing, corrupt or stale shards.
+///
+/// Every shard has a header with the object size, a write id and the md5
+/// of shard data, so that corrupt shards and shards from older writes can
+/// be detected. Data shards are always read and verified as a whole, even
+/// for range reads, use [`Backend::repair`] to check all shards.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::services::erasure;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// let mut builder = erasure::Backend::build();
+/// for _ in 0..6 {
+///     builder.shard(memory::Backend::build().finish().await?);
+/// }
+/// let backend = builder.parity_shards(2).finish().await?;
+/// let op = Operator::new(backend.clone());
+///
+/// op.object("file").write("Hello, World!").await?;
+/// backend.repair("file").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Backend {
+    shards: Arc<Vec<Arc<dyn Accessor>>>,
+    data_shards: usize,
+    parity_shards: usize,
+    write_quorum: usize,
+    codec: Arc<ReedSolomon>,
+    last_id: Arc<AtomicU64>,
+}
+
+impl std::fmt::Debug for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Backend")
+            .field("shards", &self.shards)
+            .field("data_shards", &self.data_shards)
+            .field("parity_shards", &self.parity_shards)
+            .field("write_quorum", &self.write_quorum)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Backend {
+    /// Create a builder.
+    pub fn build() -> Builder {
+        Builder::default()
+    }
+
+    /// Repair the object by rewriting missing, corrupt or stale shards.
+    ///
+    /// Returns the count of shards that have been rewritten.
+    pub async fn repair(&self, path: &str) -> Result<usize> {
+        let mut shards = self.load(path, false).await;
+        let (header, data) = self.reconstruct(path, &mut shards, false)?;
+
+        let broken: Vec<usize> = shards
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !matches!(s, Shard::Valid(h, _) if h.id == header.id))
+            .map(|(idx, _)| idx)
+            .collect();
+        if broken.is_empty() {
+            return Ok(0);
+        }
+
+        let results = join_all(broken.iter().map(|idx| {
+            let header = ShardHeader {
+                index: *idx as u16,
+                digest: md5::compute(&data[*idx]).0,
+                ..header
+            };
+            self.write_shard(*idx, path, header, &data[*idx])
+        }))
+        .await;
+        for res in results {
+            res?;
+        }
+
+        debug!("object {} repaired shards: {:?}", path, broken);
+        Ok(broken.len())
+    }
+
+    fn shard_size(&self, size: u64) -> usize {
+        size.div_ceil(self.data_shards as u64) as usize
+    }
+
+    /// Generate a new write id which is larger than all previous ones.
+    fn next_id(&self) -> u64 {
+        let now = OffsetDateTime::now_utc().unix_timestamp_nanos() as u64;
+        let prev = self
+            .last_id
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| {
+                Some(max(now, v + 1))
+            })
+            .expect("closure always returns Some");
+        max(now, prev + 1)
+    }
+
+    /// Fill parity shards, `shards` must contain all data shards.
+    fn encode(&self, shards: &mut Vec<Vec<u8>>) -> Result<()> {
+        let size = shards[0].len();
+        shards.resize(self.shards.len(), vec![0; size]);
+        if size == 0 {
+            return Ok(());
+        }
+
+        self.codec
+            .encode(shards)
+            .map_err(|e| other(anyhow!("encode shards: {}", e)))
+    }
+
+    /// Check the header of shard `idx`.
+    fn check_header(&self, idx: usize, bs: &[u8]) -> std::result::Result<ShardHeader, String> {
+        let header = ShardHeader::decode(bs).ok_or_else(|| "invalid header".to_string())?;
+        if header.index as usize != idx
+            || header.data_shards as usize != self.data_shards
+            || header.parity_shards as usize != self.parity_shards
+        {
+            return Err(format!("unexpected header: {:?}", header));
+        }
+        Ok(header)
+    }
+
+    /// Read `size` bytes of shard `idx` from `offset`, which includes header.
+    async fn read_shard(
+        &self,
+        idx: usize,
+        path: &str,
+        offset: u64,
+        size: Option<u64>,
+    ) -> Result<Vec<u8>> {
+        let op = OpRead::new_with_offset(path, Some(offset), size)?;
+        let mut r = self.shards[idx].read(&op).await?;
+        let mut bs = Vec::new();
+        r.read_to_end(&mut bs).await?;
+        match size {
+            Some(size) if bs.len() as u64 != size => Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                ObjectError::new("read", path, anyhow!("shard {} is truncated", idx)),
+            )),
+            _ => Ok(bs),
+        }
+    }
+
+    /// Load the header of shard `idx` only, the data will be left empty.
+    async fn load_header(&self, idx: usize, path: &str) -> Shard {
+        let bs = match self
+            .read_shard(idx, path, 0, Some(HEADER_SIZE as u64))
+            .await
+        {
+            Ok(bs) => bs,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Shard::Missing,
+            Err(e) => return Shard::Broken(format!("read header: {}", e)),
+        };
+
+        match self.check_header(idx, &bs) {
+            Ok(header) => Shard::Valid(header, Vec::new()),
+            Err(reason) => Shard::Broken(reason),
+        }
+    }
+
+    async fn load_shard(&self, idx: usize, path: &str) -> Shard {
+        let bs = match self.read_shard(idx, path, 0, None).await {
+            Ok(bs) => bs,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Shard::Missing,
+            Err(e) => return Shard::Broken(format!("read: {}", e)),
+        };
+
+        let header = match self.check_header(idx, &bs) {
+            Ok(header) => header,
+            Err(reason) => return Shard::Broken(reason),
+        };
+        let data = bs[HEADER_SIZE..].to_vec();
+        if data.len() != self.shard_size(header.size) {
+            return Shard::Broken(format!("unexpected size {}", data.len()));
+        }
+        if md5::compute(&data).0 != header.digest {
+            return Shard::Broken("checksum mismatch".to_string());
+        }
+        Shard::Valid(header, data)
+    }
+
+    /// Load all shards concurrently, only headers will be loaded if
+    /// `header_only` is true.
+    async fn load(&self, path: &str, header_only: bool) -> Vec<Shard> {
+        let shards = join_all((0..self.shards.len()).map(|idx| async move {
+            if header_only {
+                self.load_header(idx, path).await
+            } else {
+                self.load_shard(idx, path).await
+            }
+        }))
+        .await;
+        for (idx, shard) in shards.iter().enumerate() {
+            if let Shard::Broken(reason) = shard {
+                warn!("object {} shard {} is broken: {}", path, idx, reason);
+            }
+        }
+        shards
+    }
+
+    /// Find the header of the latest write which has enough valid shards.
+    fn latest(&self, op: &'static str, path: &str, shards: &[Shard]) -> Result<ShardHeader> {
+        let mut latest: BTreeMap<u64, (ShardHeader, usize)> = BTreeMap::new();
+        for shard in shards.iter() {
+            if let Shard::Valid(h, _) = shard {
+                latest.entry(h.id).or_insert((*h, 0)).1 += 1;
+            }
+        }
+
+        match latest
+            .into_values()
+            .rev()
+            .find(|(_, count)| *count >= self.data_shards)
+        {
+            Some((header, _)) => Ok(header),
+            None if shards.iter().all(|s| matches!(s, Shard::Missing)) => Err(Error::new(
+                ErrorKind::NotFound,
+                ObjectError::new(op, path, anyhow!("object not exist")),
+            )),
+            None => Err(Error::new(
+                ErrorKind::InvalidData,
+                ObjectError::new(op, path, anyhow!("not enough valid shards to reconstruct")),
+            )),
+        }
+    }
+
+    /// Reconstruct shards of the latest write which has enough valid shards.
+    ///
+    /// Returns the header and all data shards (parity shards are returned
+    /// too if `data_only` is false).
+    fn reconstruct(
+        &self,
+        path: &str,
+        shards: &mut [Shard],
+        data_only: bool,
+    ) -> Result<(ShardHeader, Vec<Vec<u8>>)> {
+        let id = self.latest("read", path, shards)?.id;
+
+        let mut header = None;
+        let mut parts: Vec<Option<Vec<u8>>> = shards
+            .iter_mut()
+            .map(|s| match std::mem::replace(s, Shard::Missing) {
+                Shard::Valid(h, data) if h.id == id => {
+                    header = Some(h);
+                    *s = Shard::Valid(h, Vec::new());
+                    Some(data)
+                }
+                other => {
+                    *s = other;
+                    None
+                }
+            })
+            .collect();
+        let header = header.expect("header must exist");
+        parts.resize(self.shards.len(), None);
+
+        let missing = parts.iter().any(|v| v.is_none());
+        if missing && self.shard_size(header.size) > 0 {
+            let res = if data_only {
+                self.codec.reconstruct_data(&mut parts)
+            } else {
+                self.codec.reconstruct(&mut parts)
+            };
+            res.map_err(|e| {
+                other(ObjectError::new(
+                    "read",
+                    path,
+                    anyhow!("reconstruct shards: {}", e),
+                ))
+            })?;
+            debug!("object {} reconstructed from shards", path);
+        }
+
+        let take = if data_only {
+            self.data_shards
+        } else {
+            self.shards.len()
+        };
+        let size = self.shard_size(header.size);
+        Ok((
+            header,
+            parts
+                .into_iter()
+                .take(take)
+                .map(|v| v.unwrap_or_else(|| vec![0; size]))
+                .collect(),
+        ))
+    }
+
+    /// Read `[start, end)` of the object from the data shards that cover
+    /// it, returns `None` if any of them is unavailable.
+    ///
+    /// The whole shards will be read to verify their md5, so that corrupt
+    /// data is never returned.
+    async fn read_healthy(
+        &self,
+        path: &str,
+        header: ShardHeader,
+        shards: &[Shard],
+        start: u64,
+        end: u64,
+    ) -> Option<Vec<u8>> {
+        let size = self.shard_size(header.size) as u64;
+        let (first, last) = ((start / size) as usize, ((end - 1) / size) as usize);
+        let mut digests = Vec::with_capacity(last - first + 1);
+        for shard in &shards[first..=last] {
+            match shard {
+                Shard::Valid(h, _) if h.id == header.id => digests.push(h.digest),
+                _ => return None,
+            }
+        }
+
+        let results = join_all((first..=last).zip(digests).map(|(idx, digest)| async move {
+            let base = idx as u64 * size;
+            let (from, to) = (max(start, base) - base, min(end, base + size) - base);
+            let bs = self
+                .read_shard(idx, path, HEADER_SIZE as u64, Some(size))
+                .await?;
+            if md5::compute(&bs).0 != digest {
+                return Err(other(anyhow!("shard {} checksum mismatch", idx)));
+            }
+            Ok::<_, Error>(bs[from as usize..to as usize].to_vec())
+        }))
+        .await;
+
+        let mut bs = Vec::with_capacity((end - start) as usize);
+        for (idx, res) in (first..=last).zip(results) {
+            match res {
+                Ok(v) => bs.extend_from_slice(&v),
+                Err(e) => {
+                    warn!("object {} shard {} read: {:?}", path, idx, e);
+                    return None;
+                }
+            }
+        }
+        Some(bs)
+    }
+
+    /// Read the range of object, only the needed data shards will be read
+    /// unless some of them are unavailable.
+    async fn read_object(&self, path: &str, offset: u64, size: Option<u64>) -> Result<Vec<u8>> {
+        let out_of_bound = |total: u64| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                ObjectError::new(
+                    "read",
+                    path,
+                    anyhow!("offset out of bound {} > {}", offset, total),
+                ),
+            )
+        };
+        let end = |total: u64| match size {
+            Some(size) => min(offset.saturating_add(size), total),
+            None => total,
+        };
+
+        let shards = self.load(path, true).await;
+        let header = self.latest("read", path, &shards)?;
+        if offset > header.size {
+            return Err(out_of_bound(header.size));
+        }
+        if offset == end(header.size) {
+            return Ok(Vec::new());
+        }
+        if let Some(bs) = self
+            .read_healthy(path, header, &shards, offset, end(header.size))
+            .await
+        {
+            return Ok(bs);
+        }
+
+        debug!("object {} data shards are degraded", path);
+        let mut shards = self.load(path, false).await;
+        let (header, data) = self.reconstruct(path, &mut shards, true)?;
+        if offset > header.size {
+            return Err(out_of_bound(header.size));
+        }
+        let mut bs = data.concat();
+        bs.truncate(end(header.size) as usize);
+        Ok(bs.split_off(offset as usize))
+    }
+
+    async fn write_shard(
+        &self,
+        idx: usize,
+        path: &str,
+        header: ShardHeader,
+        data: &[u8],
+    ) -> Result<()> {
+        let size = (HEADER_SIZE + data.len()) as u64;
+        let mut w = self.shards[idx].write(&OpWrite::new(path, size)?).await?;
+        w.write_all(&header.encode()).await?;
+        w.write_all(data).await?;
+        w.close().await
+    }
+
+    /// Encode the object and write shards to all accessors.
+    async fn write_object(&self, path: &str, bs: &[u8]) -> Result<()> {
+        let size = self.shard_size(bs.len() as u64);
+        let mut shards: Vec<Vec<u8>> = (0..self.data_shards)
+            .map(|idx| {
+                let start = min(idx * size, bs.len());
+                let end = min(start + size, bs.len());
+                let mut shard = bs[start..end].to_vec();
+                shard.resize(size, 0);
+                shard
+            })
+            .collect();
+        self.encode(&mut shards)?;
+
+        let id = self.next_id();
+        let results = join_all(shards.iter().enumerate().map(|(idx, data)| {
+            let header = ShardHeader {
+                data_shards: self.data_shards as u16,
+                parity_shards: self.parity_shards as u16,
+                index: idx as u16,
+                size: bs.len() as u64,
+                id,
+                digest: md5::compute(data).0,
+            };
+            self.write_shard(idx, path, header, data)
+        }))
+        .await;
+
+        let mut written = 0;
+        let mut last_err = None;
+        for (idx, res) in results.into_iter().enumerate() {
+            match res {
+                Ok(_) => written += 1,
+                Err(e) => {
+                    warn!("object {} shard {} write: {:?}", path, idx, e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        match last_err {
+            Some(err) if written < self.write_quorum => Err(Error::new(
+                err.kind(),
+                ObjectError::new(
+                    "write",
+                    path,
+                    anyhow!(
+                        "only {} of {} shards written: {}",
+                        written,
+                        self.shards.len(),
+                        err
+                    ),
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run the operation on all accessors, succeed if all of them succeed.
+    async fn all<F, Fut>(&self, f: F) -> Result<()>
+    where
+        F: Fn(Arc<dyn Accessor>) -> Fut,
+        Fut: std::future::Future<Output = Result<()>>,
+    {
+        let results = join_all(self.shards.iter().map(|acc| f(acc.clone()))).await;
+        results.into_iter().collect()
+    }
+}
+
+#[async_trait]
+impl Accessor for Backend {
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = AccessorMetadata::default();
+        am.set_scheme(Scheme::Erasure)
+            .set_root("/")
+            .set_name("erasure");
+
+        am
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        match args.mode() {
+            ObjectMode::FILE => self.write_object(args.path(), &[]).await,
+            _ => self.all(|acc| async move { acc.create(args).await }).await,
+        }
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        let bs = self
+            .read_object(args.path(), args.offset().unwrap_or_default(), args.size())
+            .await?;
+
+        Ok(Box::new(Cursor::new(bs)))
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        Ok(Box::new(ErasureWriter {
+            backend: self.clone(),
+            path: args.path().to_string(),
+            buf: Vec::with_capacity(args.size() as usize),
+            state: None,
+        }))
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        let mut meta = ObjectMetadata::default();
+
+        if args.path().ends_with('/') {
+            for acc in self.shards.iter() {
+                match acc.stat(args).await {
+                    Ok(_) => {
+                        meta.set_mode(ObjectMode::DIR);
+                        return Ok(meta);
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                    Err(e) => warn!("object {} stat: {:?}", args.path(), e),
+                }
+            }
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                ObjectError::new("stat", args.path(), anyhow!("dir not exist")),
+            ));
+        }
+
+        let shards = self.load(args.path(), true).await;
+        let header = self.latest("stat", args.path(), &shards)?;
+        meta.set_mode(ObjectMode::FILE)
+            .set_content_length(header.size);
+        Ok(meta)
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.all(|acc| async move { acc.delete(args).await }).await
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let results = join_all(self.shards.iter().map(|acc| async move {
+            let s = acc.list(args).await?;
+            s.try_collect::<Vec<DirEntry>>().await
+        }))
+        .await;
+
+        // Shards could be missing on some accessors, so merge all of them.
+        let mut entries = BTreeMap::new();
+        let mut last_err = None;
+        for (idx, res) in results.into_iter().enumerate() {
+            match res {
+                Ok(des) => entries.extend(des.iter().map(|de| (de.path().to_string(), de.mode()))),
+                Err(e) => {
+                    warn!("object {} shard {} list: {:?}", args.path(), idx, e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        if entries.is_empty() {
+            if let Some(err) = last_err {
+                return Err(err);
+            }
+        }
+
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        Ok(Box::new(stream::iter(entries.into_iter().map(
+            move |(path, mode)| Ok(DirEntry::new(acc.clone(), mode, &path)),
+        ))))
+    }
+}
+
+/// ErasureWriter buffers the whole object and writes shards after closed.
+struct ErasureWriter {
+    backend: Backend,
+    path: String,
+    buf: Vec<u8>,
+    state: Option<BoxFuture<'static, Result<()>>>,
+}
+
+impl AsyncWrite for ErasureWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        if self.state.is_some() {
+            return Poll::Ready(Err(other(ObjectError::new(
+                "write",
+                &self.path,
+                anyhow!("writer has been closed"),
+            ))));
+        }
+
+        self.buf.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.state.is_none() {
+            let backend = self.backend.clone();
+            let path = self.path.clone();
+            let bs = std::mem::take(&mut self.buf);
+            self.state = Some(async move { backend.write_object(&path, &bs).await }.boxed());
+        }
+
+        let fut = self.state.as_mut().expect("state must be set");
+        let res = ready!(fut.poll_unpin(cx));
+        // Replace with a finished future so that closing again is a no-op.
+        self.state = Some(futures::future::ready(Ok(())).boxed());
+        Poll::Ready(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    async fn build(parity: usize) -> anyhow::Result<(Arc<Backend>, Vec<Operator>)> {
+        let mut builder = Backend::build();
+        let mut shards = Vec::new();
+        for _ in 0..5 {
+            let acc = memory::Backend::build().finish().await?;
+            shards.push(Operator::new(acc.clone()));
+            builder.shard(acc);
+        }
+        Ok((builder.parity_shards(parity).finish().await?, shards))
+    }
+
+    #[tokio::test]
+    async fn test_erasure() -> anyhow::Result<()> {
+        let (backend, shards) = build(2).await?;
+        let op = Operator::new(backend.clone());
+
+        let content = "Hello, World! ".repeat(10);
+        op.object("dir/file").write(content.as_str()).await?;
+        assert_eq!(op.object("dir/file").read().await?, content.as_bytes());
+        assert_eq!(op.object("dir/file").range_read(7..12).await?, b"World");
+        assert_eq!(
+            op.object("dir/file").metadata().await?.content_length(),
+            content.len() as u64
+        );
+
+        // Lose one shard and corrupt another.
+        shards[0].object("dir/file").delete().await?;
+        let mut bs = shards[3].object("dir/file").read().await?;
+        bs[HEADER_SIZE] ^= 0xff;
+        shards[3].object("dir/file").write(bs).await?;
+        assert_eq!(op.object("dir/file").read().await?, content.as_bytes());
+        assert_eq!(op.object("dir/file").range_read(7..12).await?, b"World");
+
+        // Too many shards lost.
+        shards[1].object("dir/file").delete().await?;
+        let err = op.object("dir/file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        op.object("dir/file").delete().await?;
+        let err = op.object("dir/file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_erasure_corrupt_range() -> anyhow::Result<()> {
+        let (backend, shards) = build(2).await?;
+        let op = Operator::new(backend.clone());
+
+        let content = "Hello, World! ".repeat(10);
+        op.object("file").write(content.as_str()).await?;
+
+        // Corrupt data inside the range of a data shard.
+        let mut bs = shards[1].object("file").read().await?;
+        let start = bs.len() - HEADER_SIZE;
+        bs[HEADER_SIZE + 1] ^= 0xff;
+        shards[1].object("file").write(bs).await?;
+        assert_eq!(
+            op.object("file")
+                .range_read(start as u64..start as u64 + 5)
+                .await?,
+            &content.as_bytes()[start..start + 5]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_erasure_stale_data_shards() -> anyhow::Result<()> {
+        let mut builder = Backend::build();
+        let mut shards = Vec::new();
+        for _ in 0..5 {
+            let acc = memory::Backend::build().finish().await?;
+            shards.push(Operator::new(acc.clone()));
+            builder.shard(acc);
+        }
+        let backend = builder.parity_shards(3).write_quorum(2).finish().await?;
+        let op = Operator::new(backend.clone());
+
+        op.object("file").write("Hello").await?;
+        let old: Vec<Vec<u8>> = vec![
+            shards[0].object("file").read().await?,
+            shards[1].object("file").read().await?,
+        ];
+
+        // The new write only landed on parity shards, which still reaches
+        // the write quorum.
+        op.object("file").write("Hello, World!").await?;
+        for (idx, bs) in old.into_iter().enumerate() {
+            shards[idx].object("file").write(bs).await?;
+        }
+
+        assert_eq!(op.object("file").read().await?, b"Hello, World!");
+        assert_eq!(op.object("file").range_read(..5).await?, b"Hello");
+        assert_eq!(op.object("file").metadata().await?.content_length(), 13);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_erasure_repair() -> anyhow::Result<()> {
+        let (backend, shards) = build(2).await?;
+        let op = Operator::new(backend.clone());
+
+        op.object("file").write("Hello").await?;
+        op.object("file").write("Hello, World!").await?;
+        assert_eq!(backend.repair("file").await?, 0);
+
+        // Lose a data shard and a parity shard.
+        shards[1].object("file").delete().await?;
+        shards[4].object("file").write("garbage").await?;
+        assert_eq!(backend.repair("file").await?, 2);
+        assert_eq!(backend.repair("file").await?, 0);
+
+        // Repaired shards are enough to read without the others.
+        shards[0].object("file").delete().await?;
+        shards[2].object("file").delete().await?;
+        assert_eq!(op.object("file").read().await?, b"Hello, World!");
+
+        // Empty objects.
+        op.object("empty").create().await?;
+        assert_eq!(op.object("empty").read().await?, b"");
+        shards[0].object("empty").delete().await?;
+        assert_eq!(backend.repair("empty").await?, 1);
+
+        Ok(())
+    }
+}
@@ -45,6 +45,7 @@ use crate::error::ObjectError;
 use crate::io_util::parse_content_length;
 use crate::io_util::parse_content_md5;
 use crate::io_util::parse_etag;
+use crate::io_util::parse_hyper_error_kind;
 use crate::io_util::parse_last_modified;
 use crate::io_util::HttpClient;
 use crate::ops::BytesRange;
@@ -214,7 +215,9 @@ impl Backend {
 impl Accessor for Backend {
     fn metadata(&self) -> AccessorMetadata {
         let mut ma = AccessorMetadata::default();
-        ma.set_scheme(Scheme::Http).set_root(&self.root);
+        ma.set_scheme(Scheme::Http)
+            .set_root(&self.root)
+            .set_read_only(true);
 
         ma
     }
@@ -233,7 +236,7 @@ impl Accessor for Backend {
         );
 
         let resp = self
-            .http_get(&p, args.offset(), args.size())
+            .http_get(&p, args.offset(), args.size(), args.if_match())
             .await
             .map_err(|e| {
                 error!("object {} http_get: {:?}", p, e);
@@ -252,7 +255,9 @@ impl Accessor for Backend {
                 Ok(Box::new(
                     resp.into_body()
                         .into_stream()
-                        .map_err(move |e| other(ObjectError::new("read", &p, e)))
+                        .map_err(move |e| {
+                            Error::new(parse_hyper_error_kind(&e), ObjectError::new("read", &p, e))
+                        })
                         .into_async_read(),
                 ))
             }
@@ -368,6 +373,7 @@ impl Backend {
         path: &str,
         offset: Option<u64>,
         size: Option<u64>,
+        if_match: Option<&str>,
     ) -> Result<hyper::Response<hyper::Body>> {
         let url = format!("{}{}", self.endpoint, path);
 
@@ -380,6 +386,10 @@ impl Backend {
             );
         }
 
+        if let Some(etag) = if_match {
+            req = req.header(http::header::IF_MATCH, etag);
+        }
+
         let req = req.body(hyper::Body::empty()).map_err(|e| {
             error!("object {path} http_get: {url} {e:?}");
             other(ObjectError::new(
@@ -391,11 +401,10 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} http_get: {url} {e:?}");
-            other(ObjectError::new(
-                "read",
-                path,
-                anyhow!("send request: {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("read", path, anyhow!("send request: {url}: {e:?}")),
+            )
         })
     }
 
@@ -415,11 +424,10 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} http_head: {url} {e:?}");
-            other(ObjectError::new(
-                "stat",
-                path,
-                anyhow!("send request {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("stat", path, anyhow!("send request {url}: {e:?}")),
+            )
         })
     }
 }
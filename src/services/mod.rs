@@ -20,10 +20,13 @@
 //! - Backend: the service backend which implements the [`Accessor`][crate::Accessor] trait.
 
 pub mod azblob;
+#[cfg(feature = "services-erasure")]
+pub mod erasure;
 pub mod fs;
 #[cfg(feature = "services-hdfs")]
 pub mod hdfs;
 #[cfg(feature = "services-http")]
 pub mod http;
 pub mod memory;
+pub mod mount;
 pub mod s3;
@@ -0,0 +1,469 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::stream;
+use futures::AsyncWriteExt;
+use futures::StreamExt;
+use log::debug;
+
+use crate::error::other;
+use crate::error::BackendError;
+use crate::error::ObjectError;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::path::normalize_path;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BytesReader;
+use crate::BytesWriter;
+use crate::DirEntry;
+use crate::DirStreamer;
+use crate::ObjectMetadata;
+use crate::ObjectMode;
+use crate::Scheme;
+
+/// Builder for mount backend.
+#[derive(Default)]
+pub struct Builder {
+    mounts: Vec<(String, Arc<dyn Accessor>)>,
+}
+
+impl Builder {
+    /// Mount `accessor` at `path`.
+    ///
+    /// `path` will be normalized as a dir path, mount at `/` to serve all
+    /// paths that don't belong to other mount points.
+    pub fn mount(&mut self, path: &str, accessor: Arc<dyn Accessor>) -> &mut Self {
+        self.mounts.push((path.to_string(), accessor));
+
+        self
+    }
+
+    /// Consume builder to build a mount backend.
+    pub async fn finish(&mut self) -> Result<Arc<Backend>> {
+        let mut mounts = Vec::with_capacity(self.mounts.len());
+        for (path, acc) in self.mounts.drain(..) {
+            if path.split('/').any(|v| v.trim() == "..") {
+                return Err(other(BackendError::new(
+                    [("mount".to_string(), path.clone())].into(),
+                    anyhow!("mount point must not contain `..`"),
+                )));
+            }
+
+            let mut path = normalize_path(&path);
+            if path == "/" {
+                path.clear();
+            } else if !path.ends_with('/') {
+                path.push('/');
+            }
+
+            if mounts.iter().any(|(v, _)| v == &path) {
+                return Err(other(BackendError::new(
+                    [("mount".to_string(), path.clone())].into(),
+                    anyhow!("mount point has been mounted"),
+                )));
+            }
+
+            debug!("backend mount {} to {:?}", acc.metadata().name(), path);
+            mounts.push((path, acc));
+        }
+
+        // Sort by length so that the longest mount point wins.
+        mounts.sort_by_key(|(v, _)| Reverse(v.len()));
+
+        Ok(Arc::new(Backend {
+            mounts: Arc::new(mounts),
+        }))
+    }
+}
+
+/// Backend routes paths to the accessor mounted at the longest matching
+/// prefix.
+///
+/// # Behavior
+///
+/// - Paths of the underlying accessors are relative to their mount points.
+/// - The root and parents of mount points are virtual dirs, listing them
+///   will return the mount points as dirs.
+/// - Operations on paths that don't belong to any mount point will return
+///   `NotFound`, except `delete` which is a no-op.
+/// - `copy` and `rename` inside the same mount point are forwarded to the
+///   mounted accessor, across mount points the content will be streamed
+///   through the client, and `rename` is not atomic.
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use opendal::services::memory;
+/// use opendal::services::mount;
+/// use opendal::Operator;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// let accessor = mount::Backend::build()
+///     .mount("/hot/", memory::Backend::build().finish().await?)
+///     .mount("/tmp/", memory::Backend::build().finish().await?)
+///     .finish()
+///     .await?;
+/// let op = Operator::new(accessor);
+///
+/// op.object("hot/file").write("Hello, World!").await?;
+/// op.object("hot/file").rename("tmp/file").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Backend {
+    /// Mount points sorted by length in descending order.
+    mounts: Arc<Vec<(String, Arc<dyn Accessor>)>>,
+}
+
+impl Backend {
+    /// Create a builder.
+    pub fn build() -> Builder {
+        Builder::default()
+    }
+
+    /// Find the mount point of path, returns the mount point, the accessor
+    /// and the path in the accessor.
+    fn route(&self, path: &str) -> Option<(&str, &Arc<dyn Accessor>, String)> {
+        self.mounts.iter().find_map(|(mp, acc)| {
+            if mp.is_empty() {
+                return Some((mp.as_str(), acc, path.to_string()));
+            }
+
+            match path.strip_prefix(mp.as_str()) {
+                Some("") => Some((mp.as_str(), acc, "/".to_string())),
+                Some(v) => Some((mp.as_str(), acc, v.to_string())),
+                None => None,
+            }
+        })
+    }
+
+    /// Returns mount points (or their parents) that are direct children of
+    /// the dir path.
+    fn virtual_children(&self, path: &str) -> BTreeSet<String> {
+        let base = if path == "/" { "" } else { path };
+        if !base.is_empty() && !base.ends_with('/') {
+            return BTreeSet::new();
+        }
+
+        self.mounts
+            .iter()
+            .filter_map(|(mp, _)| {
+                let rest = mp.strip_prefix(base)?;
+                let idx = rest.find('/')?;
+                Some(format!("{}{}", base, &rest[..=idx]))
+            })
+            .filter(|v| v != base)
+            .collect()
+    }
+
+    /// Build the error for paths that don't belong to any mount point.
+    fn unmounted(&self, op: &'static str, path: &str) -> Error {
+        Error::new(
+            ErrorKind::NotFound,
+            ObjectError::new(op, path, anyhow!("path doesn't belong to any mount point")),
+        )
+    }
+}
+
+#[async_trait]
+impl Accessor for Backend {
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = AccessorMetadata::default();
+        am.set_scheme(Scheme::Mount).set_root("/").set_name("mount");
+
+        am
+    }
+
+    async fn create(&self, args: &OpCreate) -> Result<()> {
+        match self.route(args.path()) {
+            Some((_, acc, p)) => acc.create(&args.clone().with_path(&p)).await,
+            None if args.mode() == ObjectMode::DIR
+                && !self.virtual_children(args.path()).is_empty() =>
+            {
+                Ok(())
+            }
+            None => Err(self.unmounted("create", args.path())),
+        }
+    }
+
+    async fn read(&self, args: &OpRead) -> Result<BytesReader> {
+        match self.route(args.path()) {
+            Some((_, acc, p)) => acc.read(&args.clone().with_path(&p)).await,
+            None => Err(self.unmounted("read", args.path())),
+        }
+    }
+
+    async fn write(&self, args: &OpWrite) -> Result<BytesWriter> {
+        match self.route(args.path()) {
+            Some((_, acc, p)) => acc.write(&args.clone().with_path(&p)).await,
+            None => Err(self.unmounted("write", args.path())),
+        }
+    }
+
+    async fn stat(&self, args: &OpStat) -> Result<ObjectMetadata> {
+        match self.route(args.path()) {
+            Some((_, acc, p)) => acc.stat(&args.clone().with_path(&p)).await,
+            None if !self.virtual_children(args.path()).is_empty() => {
+                let mut meta = ObjectMetadata::default();
+                meta.set_mode(ObjectMode::DIR);
+
+                Ok(meta)
+            }
+            None => Err(self.unmounted("stat", args.path())),
+        }
+    }
+
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        match self.route(args.path()) {
+            Some((_, acc, p)) => acc.delete(&args.clone().with_path(&p)).await,
+            // Virtual dirs can't be removed, and deleting not existing
+            // objects is not an error.
+            None => Ok(()),
+        }
+    }
+
+    async fn list(&self, args: &OpList) -> Result<DirStreamer> {
+        let path = args.path();
+        let children = self.virtual_children(path);
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+
+        let virtual_entries = children
+            .iter()
+            .map(|p| Ok(DirEntry::new(acc.clone(), ObjectMode::DIR, p)))
+            .collect::<Vec<_>>();
+
+        let (mp, inner, p) = match self.route(path) {
+            Some(v) => v,
+            None if !children.is_empty() => {
+                return Ok(Box::new(stream::iter(virtual_entries)));
+            }
+            None => return Err(self.unmounted("list", path)),
+        };
+
+        let s = inner.list(&args.clone().with_path(&p)).await?;
+        let mp = mp.to_string();
+        let s = s
+            .map(move |de| {
+                de.map(|de| {
+                    let p = format!("{}{}", mp, de.path());
+                    DirEntry::new(acc.clone(), de.mode(), &p)
+                })
+            })
+            // Mount points will be returned as virtual entries.
+            .filter(move |de| {
+                let shadowed = matches!(de, Ok(de) if children.contains(de.path()));
+                futures::future::ready(!shadowed)
+            })
+            .chain(stream::iter(virtual_entries));
+
+        Ok(Box::new(s))
+    }
+
+    async fn copy(&self, args: &OpCopy) -> Result<()> {
+        let (from, to) = (args.from(), args.to());
+        let (src_mp, src, src_path) = self
+            .route(from)
+            .ok_or_else(|| self.unmounted("copy", from))?;
+        let (dst_mp, dst, dst_path) = self.route(to).ok_or_else(|| self.unmounted("copy", to))?;
+        if src_mp == dst_mp {
+            return src
+                .copy(&args.clone().with_paths(&src_path, &dst_path))
+                .await;
+        }
+
+        let meta = src.stat(&OpStat::new(&src_path)?).await?;
+        if meta.mode() != ObjectMode::FILE {
+            return Err(other(ObjectError::new(
+                "copy",
+                from,
+                anyhow!("only files can be copied"),
+            )));
+        }
+
+        let r = src.read(&OpRead::new(&src_path, ..)?).await?;
+        let mut w = dst
+            .write(&OpWrite::new(&dst_path, meta.content_length())?)
+            .await?;
+        futures::io::copy(r, &mut w).await?;
+        w.close().await?;
+
+        debug!("object {} copied to {}", from, to);
+        Ok(())
+    }
+
+    async fn rename(&self, args: &OpRename) -> Result<()> {
+        let (from, to) = (args.from(), args.to());
+        let (src_mp, src, src_path) = self
+            .route(from)
+            .ok_or_else(|| self.unmounted("rename", from))?;
+        let (dst_mp, _, dst_path) = self.route(to).ok_or_else(|| self.unmounted("rename", to))?;
+        if src_mp == dst_mp {
+            return src
+                .rename(&args.clone().with_paths(&src_path, &dst_path))
+                .await;
+        }
+
+        self.copy(&OpCopy::new(from, to)?).await?;
+        src.delete(&OpDelete::new(&src_path)?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    async fn list(op: &Operator, path: &str) -> anyhow::Result<Vec<String>> {
+        let entries: Vec<DirEntry> = op.object(path).list().await?.try_collect().await?;
+        let mut paths: Vec<String> = entries.iter().map(|de| de.path().to_string()).collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    #[tokio::test]
+    async fn test_mount() -> anyhow::Result<()> {
+        let hot = memory::Backend::build().finish().await?;
+        let cold = memory::Backend::build().finish().await?;
+        let op = Operator::new(
+            Backend::build()
+                .mount("/hot", hot.clone())
+                .mount("/data/cold/", cold.clone())
+                .finish()
+                .await?,
+        );
+
+        op.object("hot/file").write("Hello, World!").await?;
+        op.object("data/cold/file").write("Hello, Cold!").await?;
+        assert_eq!(
+            Operator::new(hot.clone()).object("file").read().await?,
+            b"Hello, World!"
+        );
+        assert_eq!(
+            Operator::new(cold.clone()).object("file").read().await?,
+            b"Hello, Cold!"
+        );
+
+        assert_eq!(list(&op, "/").await?, vec!["data/", "hot/"]);
+        assert_eq!(list(&op, "data/").await?, vec!["data/cold/"]);
+        assert_eq!(list(&op, "data/cold/").await?, vec!["data/cold/file"]);
+        assert_eq!(op.object("data/").metadata().await?.mode(), ObjectMode::DIR);
+
+        let err = op.object("warm/file").read().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(op.object("warm/file").write("Hello").await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mount_copy() -> anyhow::Result<()> {
+        let hot = memory::Backend::build().finish().await?;
+        let tmp = memory::Backend::build().finish().await?;
+        let op = Operator::new(
+            Backend::build()
+                .mount("/hot/", hot.clone())
+                .mount("/tmp/", tmp.clone())
+                .finish()
+                .await?,
+        );
+
+        op.object("hot/file").write("Hello, World!").await?;
+        op.object("hot/file").copy("tmp/copied").await?;
+        op.object("hot/file").copy("hot/copied").await?;
+        assert_eq!(op.object("tmp/copied").read().await?, b"Hello, World!");
+        assert_eq!(op.object("hot/copied").read().await?, b"Hello, World!");
+
+        op.object("tmp/copied").rename("hot/renamed").await?;
+        assert_eq!(op.object("hot/renamed").read().await?, b"Hello, World!");
+        assert!(!op.object("tmp/copied").is_exist().await?);
+        assert!(!Operator::new(tmp).object("copied").is_exist().await?);
+
+        let err = op.object("warm/file").copy("hot/file").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        let err = op
+            .object("hot/not_exist")
+            .rename("tmp/file")
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        // Copying to itself still requires the source to exist.
+        op.object("hot/file").copy("hot/file").await?;
+        let err = op
+            .object("hot/not_exist")
+            .copy("hot/not_exist")
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mount_nested() -> anyhow::Result<()> {
+        let root = memory::Backend::build().finish().await?;
+        let tmp = memory::Backend::build().finish().await?;
+        let op = Operator::new(
+            Backend::build()
+                .mount("/", root.clone())
+                .mount("tmp/", tmp.clone())
+                .finish()
+                .await?,
+        );
+
+        op.object("file").write("Hello, World!").await?;
+        op.object("tmp/file").write("Hello, Tmp!").await?;
+        assert_eq!(
+            Operator::new(root.clone()).object("file").read().await?,
+            b"Hello, World!"
+        );
+        assert_eq!(
+            Operator::new(tmp.clone()).object("file").read().await?,
+            b"Hello, Tmp!"
+        );
+
+        assert_eq!(list(&op, "/").await?, vec!["file", "tmp/"]);
+
+        assert!(Backend::build()
+            .mount("tmp", tmp.clone())
+            .mount("/tmp/", tmp.clone())
+            .finish()
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
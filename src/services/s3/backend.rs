@@ -16,6 +16,7 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::io::Error;
 use std::io::ErrorKind;
 use std::io::Result;
 use std::sync::Arc;
@@ -46,10 +47,12 @@ use crate::io_util::new_http_channel;
 use crate::io_util::parse_content_length;
 use crate::io_util::parse_error_response;
 use crate::io_util::parse_etag;
+use crate::io_util::parse_hyper_error_kind;
 use crate::io_util::parse_last_modified;
 use crate::io_util::HttpBodyWriter;
 use crate::io_util::HttpClient;
 use crate::ops::BytesRange;
+use crate::ops::ChecksumAlgorithm;
 use crate::ops::OpCreate;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
@@ -478,10 +481,13 @@ impl Builder {
 
         let res = client.request(req).await.map_err(|e| {
             error!("backend detect_region: {}: {:?}", url, e);
-            other(BackendError::new(
-                context.clone(),
-                anyhow!("sending request: {}: {:?}", url, e),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                BackendError::new(
+                    context.clone(),
+                    anyhow!("sending request: {}: {:?}", url, e),
+                ),
+            )
         })?;
 
         debug!(
@@ -828,10 +834,13 @@ impl Accessor for Backend {
         increment_counter!("opendal_s3_create_requests");
         let p = self.get_abs_path(args.path());
 
-        let req = self.put_object(&p, 0, Body::empty()).await?;
+        let req = self.put_object(&p, 0, None, None, Body::empty()).await?;
         let resp = self.client.request(req).await.map_err(|e| {
             error!("object {} put_object: {:?}", args.path(), e);
-            other(ObjectError::new("read", args.path(), e))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("read", args.path(), e),
+            )
         })?;
 
         match resp.status() {
@@ -860,7 +869,7 @@ impl Accessor for Backend {
         );
 
         let resp = self
-            .get_object(&p, args.offset(), args.size())
+            .get_object(&p, args.offset(), args.size(), args.if_match())
             .await
             .map_err(|e| {
                 error!("object {} get_object: {:?}", p, e);
@@ -879,7 +888,9 @@ impl Accessor for Backend {
                 Ok(Box::new(
                     resp.into_body()
                         .into_stream()
-                        .map_err(move |e| other(ObjectError::new("read", &p, e)))
+                        .map_err(move |e| {
+                            Error::new(parse_hyper_error_kind(&e), ObjectError::new("read", &p, e))
+                        })
                         .into_async_read(),
                 ))
             }
@@ -898,7 +909,9 @@ impl Accessor for Backend {
 
         let (tx, body) = new_http_channel();
 
-        let req = self.put_object(&p, args.size(), body).await?;
+        let req = self
+            .put_object(&p, args.size(), args.content_md5(), args.checksum(), body)
+            .await?;
 
         let bs = HttpBodyWriter::new(
             args,
@@ -1020,6 +1033,7 @@ impl Backend {
         path: &str,
         offset: Option<u64>,
         size: Option<u64>,
+        if_match: Option<&str>,
     ) -> Result<hyper::Response<hyper::Body>> {
         let url = format!("{}/{}", self.endpoint, path);
 
@@ -1032,6 +1046,10 @@ impl Backend {
             );
         }
 
+        if let Some(etag) = if_match {
+            req = req.header(http::header::IF_MATCH, etag);
+        }
+
         // Set SSE headers.
         req = self.insert_sse_headers(req, false);
 
@@ -1055,11 +1073,10 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} get_object: {url} {e:?}");
-            other(ObjectError::new(
-                "read",
-                path,
-                anyhow!("send request: {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("read", path, anyhow!("send request: {url}: {e:?}")),
+            )
         })
     }
 
@@ -1068,6 +1085,8 @@ impl Backend {
         &self,
         path: &str,
         size: u64,
+        content_md5: Option<&str>,
+        checksum: Option<(ChecksumAlgorithm, &str)>,
         body: hyper::Body,
     ) -> Result<hyper::Request<hyper::Body>> {
         let url = format!("{}/{}", self.endpoint, path);
@@ -1077,6 +1096,19 @@ impl Backend {
         // Set content length.
         req = req.header(http::header::CONTENT_LENGTH, size.to_string());
 
+        if let Some(v) = content_md5 {
+            req = req.header(HeaderName::from_static("content-md5"), v);
+        }
+        match checksum {
+            Some((ChecksumAlgorithm::Crc32c, v)) => {
+                req = req.header(HeaderName::from_static("x-amz-checksum-crc32c"), v)
+            }
+            Some((ChecksumAlgorithm::Sha256, v)) => {
+                req = req.header(HeaderName::from_static("x-amz-checksum-sha256"), v)
+            }
+            None => {}
+        }
+
         // Set SSE headers.
         req = self.insert_sse_headers(req, true);
 
@@ -1131,11 +1163,10 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} head_object: {url} {e:?}");
-            other(ObjectError::new(
-                "stat",
-                path,
-                anyhow!("send request {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("stat", path, anyhow!("send request {url}: {e:?}")),
+            )
         })
     }
 
@@ -1165,11 +1196,10 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} delete_object: {url} {e:?}");
-            other(ObjectError::new(
-                "delete",
-                path,
-                anyhow!("send request {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("delete", path, anyhow!("send request {url}: {e:?}")),
+            )
         })
     }
 
@@ -1206,20 +1236,39 @@ impl Backend {
 
         self.client.request(req).await.map_err(|e| {
             error!("object {path} list_object: {url} {e:?}");
-            other(ObjectError::new(
-                "list",
-                path,
-                anyhow!("send request {url}: {e:?}"),
-            ))
+            Error::new(
+                parse_hyper_error_kind(&e),
+                ObjectError::new("list", path, anyhow!("send request {url}: {e:?}")),
+            )
         })
     }
 }
 
-pub fn parse_error_kind(code: StatusCode) -> ErrorKind {
+/// Decide the [`ErrorKind`] via the status code and S3 error code.
+///
+/// Read [Error Responses](https://docs.aws.amazon.com/AmazonS3/latest/API/ErrorResponses.html#ErrorCodeList)
+/// for all error codes.
+pub fn parse_error_kind(status: StatusCode, code: Option<&str>) -> ErrorKind {
     match code {
+        Some("NoSuchKey" | "NoSuchBucket") => return ErrorKind::NotFound,
+        Some("AccessDenied" | "AllAccessDisabled" | "InvalidAccessKeyId") => {
+            return ErrorKind::PermissionDenied
+        }
+        Some(
+            "SlowDown" | "InternalError" | "RequestTimeout" | "ServiceUnavailable"
+            | "OperationAborted",
+        ) => return ErrorKind::Interrupted,
+        // Retrying with the same clock skew can't succeed.
+        Some("RequestTimeTooSkewed") => return ErrorKind::Other,
+        _ => {}
+    }
+
+    match status {
         StatusCode::NOT_FOUND => ErrorKind::NotFound,
         StatusCode::FORBIDDEN => ErrorKind::PermissionDenied,
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::REQUEST_TIMEOUT
+        | StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::INTERNAL_SERVER_ERROR
         | StatusCode::BAD_GATEWAY
         | StatusCode::SERVICE_UNAVAILABLE
         | StatusCode::GATEWAY_TIMEOUT => ErrorKind::Interrupted,
@@ -1233,6 +1282,52 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_parse_error_kind() {
+        let cases = vec![
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Some("SlowDown"),
+                ErrorKind::Interrupted,
+            ),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Some("InternalError"),
+                ErrorKind::Interrupted,
+            ),
+            (
+                StatusCode::BAD_REQUEST,
+                Some("RequestTimeout"),
+                ErrorKind::Interrupted,
+            ),
+            (
+                StatusCode::FORBIDDEN,
+                Some("RequestTimeTooSkewed"),
+                ErrorKind::Other,
+            ),
+            (
+                StatusCode::NOT_FOUND,
+                Some("NoSuchKey"),
+                ErrorKind::NotFound,
+            ),
+            (StatusCode::FORBIDDEN, None, ErrorKind::PermissionDenied),
+            (StatusCode::TOO_MANY_REQUESTS, None, ErrorKind::Interrupted),
+            (
+                StatusCode::BAD_REQUEST,
+                Some("InvalidArgument"),
+                ErrorKind::Other,
+            ),
+        ];
+
+        for (status, code, expected) in cases {
+            assert_eq!(
+                parse_error_kind(status, code),
+                expected,
+                "{status} {code:?}"
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_detect_region() {
         let client = HttpClient::new();